@@ -1,219 +1,4419 @@
-use crate::qbittorrent;
+use crate::{expression, qbittorrent, schedule};
+use indexmap::IndexMap;
 use serde::de::Unexpected;
 use serde::{Deserialize, Deserializer};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fs::File;
 use std::io::{self, Read};
-use std::path::Path;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 #[derive(Debug)]
 pub enum ConfigError {
     Deserialization(serde_yaml::Error),
+    Invalid(String),
     Io(io::Error),
 }
 
 #[derive(Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct Config {
     pub server: ServerConfig,
+    /// Honour destructive actions; without it, rules with them only run in dry-run mode.
+    #[serde(default)]
+    pub allow_destructive: bool,
+    /// Shorthand for rules that only match a category.
+    #[serde(default)]
+    categories: IndexMap<String, RuleLimits>,
+    /// Rule without conditions for the torrents that no other rule matches.
+    default: Option<Rule>,
+    /// What to do with the limits of torrents that no rule matches.
+    #[serde(default)]
+    pub default_action: DefaultAction,
+    #[serde(default)]
+    pub ignore: IgnoreList,
+    /// Format of the message logged when a rule is applied to a torrent.
+    pub log_template: Option<Template>,
+    /// Skip applying changes for a cycle if it would change more torrents than this.
+    pub max_changes_per_cycle: Option<usize>,
+    /// Torrents changed per cycle at most; further changes wait for the next cycles.
+    pub max_changes_per_run: Option<usize>,
+    /// Torrents deleted per cycle at most; further deletions wait for the next cycles.
+    #[serde(default = "default_max_deletions_per_cycle")]
+    pub max_deletions_per_cycle: usize,
+    pub tunnel: Option<TunnelConfig>,
+    /// Tag managed torrents with the name of the rule that matched them.
+    #[serde(default)]
+    pub rule_tags: bool,
+    /// Tag added to torrents matched by a rule and removed once they're reset to global limits.
+    pub managed_tag: Option<String>,
+    /// Directory for the statistics database.
+    pub state_dir: Option<PathBuf>,
+    /// Path of the JSON file describing the latest cycle.
+    pub state_file: Option<PathBuf>,
+    /// Default for rules that don't set `seedingTimeSource` themselves.
+    #[serde(default)]
+    pub seeding_time_source: SeedingTimeSource,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub polling: PollingConfig,
+    #[serde(default)]
     pub rules: RuleList,
+    /// Settings filled in for every rule that doesn't set them.
+    rule_defaults: Option<RuleDefaults>,
+    /// Shorthand for rules that only match torrents with a tag.
+    #[serde(default)]
+    tags: IndexMap<String, RuleLimits>,
+    /// Example torrents checked by `jeanne test`.
+    #[serde(default)]
+    pub tests: Vec<TestCase>,
+    /// Files mapping tracker host names to their share limit requirements.
+    #[serde(default)]
+    tracker_presets: Vec<PathBuf>,
+}
+
+fn default_max_deletions_per_cycle() -> usize {
+    5
+}
+
+/// Example torrent and the outcome the rules are expected to have on it.
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub torrent: TorrentFixture,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+/// Torrent properties used by the rules. Share limits default to the global limits.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TorrentFixture {
+    /// Unix timestamp of when the torrent was added.
+    added_on: i64,
+    auto_tmm: bool,
+    availability: f64,
+    category: String,
+    /// Unix timestamp of when the download finished.
+    completion_on: i64,
+    content_path: String,
+    /// Number of other torrents with the same content path.
+    cross_seeds: usize,
+    downloaded: Bytes,
+    /// Download speed limit in bytes per second.
+    dl_limit: i64,
+    /// Estimated seconds until the torrent finishes.
+    eta: usize,
+    /// Number of files in the torrent.
+    file_count: usize,
+    force_start: bool,
+    inactive_seeding_time_limit: Option<qbittorrent::MaxSeedingTime>,
+    priority: i64,
+    /// Other maindata fields by their qBittorrent names.
+    fields: serde_json::Map<String, serde_json::Value>,
+    /// Unix timestamp of the last upload or download.
+    last_activity: i64,
+    /// Unix timestamp of when the share limits last changed.
+    limits_changed_on: i64,
+    max_inactive_seeding_time: qbittorrent::MaxSeedingTime,
+    max_ratio: qbittorrent::Ratio,
+    max_seeding_time: qbittorrent::MaxSeedingTime,
+    name: String,
+    /// Number of seeders in the swarm.
+    num_complete: usize,
+    num_incomplete: usize,
+    progress: f64,
+    private: bool,
+    ratio: f64,
+    /// Ratio limit set on the torrent itself, where -2 is the global limit.
+    ratio_limit: Option<qbittorrent::Ratio>,
+    /// Directory the torrent is saved in.
+    save_path: String,
+    /// Seeding time in seconds.
+    seeding_time: usize,
+    seeding_time_limit: Option<qbittorrent::MaxSeedingTime>,
+    size: Bytes,
+    state: String,
+    super_seeding: bool,
+    seq_dl: bool,
+    tags: qbittorrent::TagList,
+    /// Announce URL of the tracker.
+    tracker: String,
+    /// Upload speed limit in bytes per second.
+    up_limit: i64,
+    uploaded: Bytes,
+    /// Upload speed per second.
+    upspeed: Bytes,
+}
+
+impl Default for TorrentFixture {
+    fn default() -> Self {
+        Self {
+            added_on: 0,
+            auto_tmm: false,
+            availability: 0.0,
+            category: String::new(),
+            completion_on: -1,
+            content_path: String::new(),
+            cross_seeds: 0,
+            downloaded: Bytes(0),
+            dl_limit: -1,
+            eta: 8_640_000,
+            file_count: 1,
+            force_start: false,
+            inactive_seeding_time_limit: None,
+            priority: 0,
+            fields: serde_json::Map::new(),
+            last_activity: 0,
+            limits_changed_on: 0,
+            max_inactive_seeding_time: -2,
+            max_ratio: -2.0,
+            max_seeding_time: -2,
+            name: String::new(),
+            num_complete: 0,
+            num_incomplete: 0,
+            progress: 1.0,
+            private: false,
+            ratio: 0.0,
+            ratio_limit: None,
+            save_path: String::new(),
+            seeding_time: 0,
+            seeding_time_limit: None,
+            size: Bytes(0),
+            state: String::new(),
+            super_seeding: false,
+            seq_dl: false,
+            tags: qbittorrent::TagList::default(),
+            tracker: String::new(),
+            up_limit: -1,
+            uploaded: Bytes(0),
+            upspeed: Bytes(0),
+        }
+    }
+}
+
+impl From<&TorrentFixture> for qbittorrent::Torrent {
+    fn from(fixture: &TorrentFixture) -> Self {
+        Self {
+            added_on: fixture.added_on,
+            auto_tmm: fixture.auto_tmm,
+            availability: fixture.availability,
+            category: fixture.category.clone(),
+            completion_on: fixture.completion_on,
+            content_path: fixture.content_path.clone(),
+            cross_seeds: fixture.cross_seeds,
+            downloaded: fixture.downloaded.0,
+            dl_limit: fixture.dl_limit,
+            eta: fixture.eta,
+            file_count: Some(fixture.file_count),
+            force_start: fixture.force_start,
+            inactive_seeding_time_limit: fixture.inactive_seeding_time_limit,
+            priority: fixture.priority,
+            last_activity: fixture.last_activity,
+            limits_changed_on: fixture.limits_changed_on,
+            max_inactive_seeding_time: fixture.max_inactive_seeding_time,
+            max_ratio: fixture.max_ratio,
+            max_seeding_time: fixture.max_seeding_time,
+            name: fixture.name.clone(),
+            num_complete: fixture.num_complete,
+            num_incomplete: fixture.num_incomplete,
+            other_fields: fixture.fields.clone(),
+            progress: fixture.progress,
+            private: Some(fixture.private),
+            ratio: fixture.ratio,
+            ratio_limit: fixture.ratio_limit,
+            save_path: fixture.save_path.clone(),
+            seeding_time: fixture.seeding_time,
+            seeding_time_limit: fixture.seeding_time_limit,
+            size: fixture.size.0,
+            state: fixture.state.clone(),
+            super_seeding: fixture.super_seeding,
+            seq_dl: fixture.seq_dl,
+            tags: fixture.tags.clone(),
+            tracker: fixture.tracker.clone(),
+            up_limit: fixture.up_limit,
+            trackers: Vec::new(),
+            uploaded: fixture.uploaded.0,
+            upspeed: fixture.upspeed.0,
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Expectation {
+    /// Rule expected to match, or `None` if no rule should match.
+    pub rule: Option<String>,
+    /// Limits the matched rule should set; not checked if unset.
+    pub limits: Option<RuleLimits>,
+    /// Whether the torrent should be ignored.
+    pub ignored: bool,
+}
+
+/// Comparison of a torrent's value, written as a bound such as `>5` or an inclusive range such
+/// as `1d..7d` or `[1d, 7d]`.
+#[derive(Debug, PartialEq)]
+enum Comparison<T> {
+    Bound(ComparisonOperator, T),
+    Between(T, T),
+}
+
+impl<T: PartialOrd> Comparison<T> {
+    fn compare(&self, value: T) -> bool {
+        match self {
+            Self::Bound(ComparisonOperator::GreaterThan, bound) => value > *bound,
+            Self::Bound(ComparisonOperator::GreaterThanOrEqual, bound) => value >= *bound,
+            Self::Bound(ComparisonOperator::LessThan, bound) => value < *bound,
+            Self::Bound(ComparisonOperator::LessThanOrEqual, bound) => value <= *bound,
+            Self::Between(lower, upper) => value >= *lower && value <= *upper,
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Comparison<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bound(operator, value) => write!(f, "{} {}", operator, value),
+            Self::Between(lower, upper) => write!(f, "between {} and {}", lower, upper),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ComparisonOperator {
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+}
+
+impl fmt::Display for ComparisonOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            ComparisonOperator::GreaterThan => ">",
+            ComparisonOperator::GreaterThanOrEqual => ">=",
+            ComparisonOperator::LessThan => "<",
+            ComparisonOperator::LessThanOrEqual => "<=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+fn parse_comparison_value<T: FromStr, E: serde::de::Error>(value: &str) -> Result<T, E> {
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| E::invalid_value(Unexpected::Str(value), &"a suitable number"))
+}
+
+fn parse_range<T: FromStr + PartialOrd, E: serde::de::Error>(
+    lower: &str,
+    upper: &str,
+) -> Result<Comparison<T>, E> {
+    let lower = parse_comparison_value(lower)?;
+    let upper = parse_comparison_value(upper)?;
+    if lower > upper {
+        return Err(E::custom(
+            "the lower end of the range is above the upper end",
+        ));
+    }
+    Ok(Comparison::Between(lower, upper))
+}
+
+impl<'de, T: FromStr + PartialOrd> serde::Deserialize<'de> for Comparison<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Scalar {
+            Number(serde_json::Number),
+            Text(String),
+        }
+
+        impl Scalar {
+            fn into_string(self) -> String {
+                match self {
+                    Scalar::Number(number) => number.to_string(),
+                    Scalar::Text(text) => text,
+                }
+            }
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Text(String),
+            Range(Scalar, Scalar),
+        }
+
+        let s = match Value::deserialize(d)? {
+            Value::Text(s) => s,
+            Value::Range(lower, upper) => {
+                return parse_range(&lower.into_string(), &upper.into_string());
+            }
+        };
+        if let Some((lower, upper)) = s.split_once("..") {
+            return parse_range(lower, upper);
+        }
+        let Some(pos) = s.find(|c| c != '>' && c != '<' && c != '=') else {
+            return Err(Error::invalid_value(
+                Unexpected::Str(&s),
+                &"a number prefixed with '>', '>=', '<' or '<='",
+            ));
+        };
+        let (prefix, value) = s.split_at(pos);
+        let operator = match prefix {
+            "<" => ComparisonOperator::LessThan,
+            "<=" => ComparisonOperator::LessThanOrEqual,
+            ">" => ComparisonOperator::GreaterThan,
+            ">=" => ComparisonOperator::GreaterThanOrEqual,
+            prefix => {
+                return Err(Error::invalid_value(
+                    Unexpected::Other(&format!("prefix \"{}\"", &prefix)),
+                    &"prefix '>', '>=', '<' or '<='",
+                ))
+            }
+        };
+        Ok(Self::Bound(operator, parse_comparison_value(value)?))
+    }
+}
+
+/// Number of bytes, written with an optional unit such as `50GB` or `1.5TiB`.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Bytes(u64);
+
+impl Bytes {
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let pos = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(pos);
+        let number: f64 = number.parse().map_err(|_| ())?;
+        let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "kb" => 1_000,
+            "mb" => 1_000_000,
+            "gb" => 1_000_000_000,
+            "tb" => 1_000_000_000_000,
+            "kib" => 1 << 10,
+            "mib" => 1 << 20,
+            "gib" => 1 << 30,
+            "tib" => 1 << 40,
+            _ => return Err(()),
+        };
+        let bytes = number * multiplier as f64;
+        // Casting would silently saturate sizes that don't fit.
+        if bytes >= u64::MAX as f64 {
+            return Err(());
+        }
+        Ok(Self(bytes as u64))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Number(u64),
+            Text(String),
+        }
+
+        match Value::deserialize(d)? {
+            Value::Number(bytes) => Ok(Self(bytes)),
+            Value::Text(text) => text.parse().map_err(|_| {
+                Error::invalid_value(Unexpected::Str(&text), &"a size such as 50GB or 1.5TiB")
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: [(&str, u64); 4] = [
+            ("TB", 1_000_000_000_000),
+            ("GB", 1_000_000_000),
+            ("MB", 1_000_000),
+            ("kB", 1_000),
+        ];
+        match UNITS.iter().find(|(_, size)| self.0 >= *size) {
+            Some((unit, size)) => write!(f, "{}{}", self.0 as f64 / *size as f64, unit),
+            None => write!(f, "{}B", self.0),
+        }
+    }
+}
+
+/// Length of time in seconds, written as a number followed by a unit, such as `30d` or `12h`.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct TimeSpan(usize);
+
+impl TimeSpan {
+    pub fn seconds(self) -> usize {
+        self.0
+    }
+
+    const UNITS: [(&'static str, usize); 5] = [
+        ("w", 7 * 24 * 60 * 60),
+        ("d", 24 * 60 * 60),
+        ("h", 60 * 60),
+        ("m", 60),
+        ("s", 1),
+    ];
+}
+
+impl FromStr for TimeSpan {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let pos = s.find(|c: char| !c.is_ascii_digit()).ok_or(())?;
+        let (number, unit) = s.split_at(pos);
+        let number: usize = number.parse().map_err(|_| ())?;
+        let (_, seconds) = Self::UNITS
+            .iter()
+            .find(|(name, _)| *name == unit.trim())
+            .ok_or(())?;
+        number.checked_mul(*seconds).map(Self).ok_or(())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TimeSpan {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let text = String::deserialize(d)?;
+        text.parse().map_err(|_| {
+            Error::invalid_value(
+                Unexpected::Str(&text),
+                &"a length of time such as 1h or 30d",
+            )
+        })
+    }
+}
+
+impl fmt::Display for TimeSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (unit, seconds) = Self::UNITS
+            .iter()
+            .find(|(_, seconds)| self.0 > 0 && self.0.is_multiple_of(*seconds))
+            .unwrap_or(&("s", 1));
+        write!(f, "{}{}", self.0 / seconds, unit)
+    }
+}
+
+/// Length of time in minutes, written as a number of minutes or with a unit, such as `2w`.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Minutes(usize);
+
+impl FromStr for Minutes {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(minutes) = s.trim().parse() {
+            return Ok(Self(minutes));
+        }
+        let seconds = s.parse::<TimeSpan>()?.seconds();
+        if !seconds.is_multiple_of(60) {
+            return Err(());
+        }
+        Ok(Self(seconds / 60))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Minutes {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Number(usize),
+            Text(String),
+        }
+
+        match Value::deserialize(d)? {
+            Value::Number(minutes) => Ok(Self(minutes)),
+            Value::Text(text) => text.parse().map_err(|_| {
+                Error::invalid_value(Unexpected::Str(&text), &"minutes or a duration such as 30d")
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Minutes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern(glob::Pattern);
+
+impl Pattern {
+    /// Pattern that only matches the value itself.
+    fn literal(value: &str) -> Self {
+        Self(glob::Pattern::new(&glob::Pattern::escape(value)).expect("escaped pattern is valid"))
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        self.0.matches(value)
+    }
+
+    pub fn matches_ignore_case(&self, value: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.0.matches_with(value, options)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let s = String::deserialize(d)?;
+        match glob::Pattern::new(&s) {
+            Ok(pattern) => Ok(Self(pattern)),
+            Err(_) => Err(Error::invalid_value(
+                Unexpected::Str(&s),
+                &"a glob pattern using '*', '?' or '[...]'",
+            )),
+        }
+    }
+}
+
+/// Deserializes a glob that can match anywhere in the value, so that `unregistered` also matches
+/// `Unregistered torrent`.
+fn deserialize_substring_pattern<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<Pattern>, D::Error> {
+    use serde::de::value::StrDeserializer;
+
+    let text = String::deserialize(d)?;
+    let start = if text.starts_with('*') { "" } else { "*" };
+    let end = if text.ends_with('*') { "" } else { "*" };
+    let pattern = format!("{}{}{}", start, text, end);
+    Pattern::deserialize(StrDeserializer::new(&pattern)).map(Some)
+}
+
+/// Condition on a raw maindata field: a number comparison such as `<1000`, or a value that the
+/// field has to equal.
+#[derive(Debug, PartialEq)]
+enum FieldCondition {
+    Comparison(Comparison<f64>),
+    Equal(String),
+}
+
+impl FieldCondition {
+    fn matches(&self, value: Option<&serde_json::Value>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        match self {
+            Self::Comparison(comparison) => value.as_f64().is_some_and(|x| comparison.compare(x)),
+            Self::Equal(expected) => field_text(value) == *expected,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
-struct Comparison<T> {
-    operator: ComparisonOperator,
-    value: T,
-}
+/// Maindata value as written in the configuration, so that `true` and `"true"` are the same.
+fn field_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Number(number) => number
+            .as_f64()
+            .map_or(number.to_string(), |x| x.to_string()),
+        value => value.to_string(),
+    }
+}
+
+impl fmt::Display for FieldCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Comparison(comparison) => {
+                write!(f, "{}", comparison)
+            }
+            Self::Equal(value) => write!(f, "= {}", value),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for FieldCondition {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::value::StrDeserializer;
+
+        let value = serde_json::Value::deserialize(d)?;
+        match &value {
+            serde_json::Value::String(text) if text.starts_with(['<', '>']) => {
+                Comparison::deserialize(StrDeserializer::new(text)).map(Self::Comparison)
+            }
+            value => Ok(Self::Equal(field_text(value))),
+        }
+    }
+}
+
+/// Categories a rule applies to, given as a single glob pattern or a list of them.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Categories(Vec<Pattern>);
+
+impl Categories {
+    fn matches(&self, category: &str) -> bool {
+        self.0.iter().any(|x| x.matches(category))
+    }
+}
+
+impl fmt::Display for Categories {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let categories: Vec<String> = self.0.iter().map(|x| x.to_string()).collect();
+        match categories.as_slice() {
+            [category] => write!(f, "{}", category),
+            categories => write!(f, "[{}]", categories.join(", ")),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Categories {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            One(Pattern),
+            Many(Vec<Pattern>),
+        }
+
+        Ok(match Value::deserialize(d)? {
+            Value::One(category) => Self(vec![category]),
+            Value::Many(categories) => Self(categories),
+        })
+    }
+}
+
+/// Value that can be substituted into a message template.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TemplateField {
+    Category,
+    Hash,
+    Name,
+    NewMinutes,
+    NewRatio,
+    OldMinutes,
+    OldRatio,
+    Rule,
+}
+
+impl FromStr for TemplateField {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "category" => Ok(Self::Category),
+            "hash" => Ok(Self::Hash),
+            "name" => Ok(Self::Name),
+            "new_minutes" => Ok(Self::NewMinutes),
+            "new_ratio" => Ok(Self::NewRatio),
+            "old_minutes" => Ok(Self::OldMinutes),
+            "old_ratio" => Ok(Self::OldRatio),
+            "rule" => Ok(Self::Rule),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum TemplateSegment {
+    Text(String),
+    Field(TemplateField),
+}
+
+/// Message format with `{field}` placeholders. Literal braces are written as `{{` and `}}`.
+#[derive(Debug, PartialEq)]
+pub struct Template(Vec<TemplateSegment>);
+
+impl Template {
+    pub fn render(&self, value: impl Fn(TemplateField) -> String) -> String {
+        let mut rendered = String::new();
+        for segment in &self.0 {
+            match segment {
+                TemplateSegment::Text(text) => rendered.push_str(text),
+                TemplateSegment::Field(field) => rendered.push_str(&value(*field)),
+            }
+        }
+        rendered
+    }
+}
+
+impl FromStr for Template {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let (name, rest) = chars.as_str().split_once('}').ok_or(())?;
+                    if !text.is_empty() {
+                        segments.push(TemplateSegment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(TemplateSegment::Field(name.parse()?));
+                    chars = rest.chars();
+                }
+                '}' => return Err(()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(TemplateSegment::Text(text));
+        }
+        Ok(Self(segments))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Template {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let s = String::deserialize(d)?;
+        s.parse().map_err(|_| {
+            Error::invalid_value(
+                Unexpected::Str(&s),
+                &"a template with placeholders such as {name}, {hash}, {rule}, {category}, \
+                {old_ratio}, {new_ratio}, {old_minutes} or {new_minutes}",
+            )
+        })
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let yaml = Self::load_file(path).map_err(ConfigError::Io)?;
+        let mut value: serde_yaml::Value =
+            serde_yaml::from_str(&yaml).map_err(ConfigError::Deserialization)?;
+        // Deserializing the text rather than the value keeps the locations in error messages.
+        let mut config: Self =
+            if crate::templates::expand(&mut value).map_err(ConfigError::Invalid)? {
+                serde_yaml::from_value(value)
+            } else {
+                serde_yaml::from_str(&yaml)
+            }
+            .map_err(ConfigError::Deserialization)?;
+        config.expand_shorthands();
+        config.expand_default()?;
+        config.expand_rule_defaults();
+        config.validate()?;
+        config.apply_defaults();
+        config.load_tracker_presets(path.parent().unwrap_or(Path::new(".")))?;
+        Ok(config)
+    }
+
+    /// Appends rules for the shorthand sections after the explicit rules. Tags come before
+    /// categories, as they are usually exceptions to the limits of a category. `retire` actions
+    /// are expanded into pausing and tagging.
+    fn expand_shorthands(&mut self) {
+        for (tag, limits) in std::mem::take(&mut self.tags) {
+            self.rules.0.push(Rule {
+                conditions: Conditions {
+                    tags_any: Some(vec![tag]),
+                    ..Default::default()
+                },
+                limits: Some(Limits::Fixed(limits)),
+                ..Default::default()
+            });
+        }
+        for (category, limits) in std::mem::take(&mut self.categories) {
+            self.rules.0.push(Rule {
+                conditions: Conditions {
+                    category: Some(Categories(vec![Pattern::literal(&category)])),
+                    ..Default::default()
+                },
+                limits: Some(Limits::Fixed(limits)),
+                ..Default::default()
+            });
+        }
+        for rule in &mut self.rules.0 {
+            let actions = &mut rule.actions;
+            if let Some(tag) = &actions.retire {
+                actions.pause = true;
+                if !actions.add_tags.contains(tag) {
+                    actions.add_tags.push(tag.clone());
+                }
+            }
+        }
+    }
+
+    /// Appends the `default` rule after all other rules, so that it matches every torrent they
+    /// don't.
+    fn expand_default(&mut self) -> Result<(), ConfigError> {
+        let Some(mut rule) = self.default.take() else {
+            return Ok(());
+        };
+        if rule.conditions != Conditions::default()
+            || !rule.hashes.is_empty()
+            || !rule.exclude_hashes.is_empty()
+        {
+            return Err(ConfigError::Invalid(
+                "the default rule can't have conditions".to_string(),
+            ));
+        }
+        if self.default_action != DefaultAction::ResetToGlobal {
+            return Err(ConfigError::Invalid(
+                "default can't be combined with defaultAction".to_string(),
+            ));
+        }
+        rule.id.get_or_insert_with(|| "default".to_string());
+        self.rules.0.push(rule);
+        Ok(())
+    }
+
+    /// Fills in `ruleDefaults` under every rule, including those of the shorthand sections and
+    /// the `default` rule.
+    fn expand_rule_defaults(&mut self) {
+        let Some(defaults) = self.rule_defaults.take() else {
+            return;
+        };
+        for rule in self.rules.0.iter_mut() {
+            defaults.fill(rule);
+        }
+    }
+
+    /// Loads the tracker preset files, relative to the configuration directory, into the rules
+    /// using `limits: trackerPolicy`. Later files take precedence.
+    fn load_tracker_presets(&mut self, directory: &Path) -> Result<(), ConfigError> {
+        let mut presets = TrackerPresets::default();
+        for path in &self.tracker_presets {
+            presets
+                .0
+                .extend(TrackerPresets::load(&directory.join(path))?.0);
+        }
+        for rule in self.rules.0.iter_mut() {
+            if let Some(Limits::TrackerPolicy(rule_presets)) = &mut rule.limits {
+                rule_presets.clone_from(&presets);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in rule settings that fall back to a global setting.
+    fn apply_defaults(&mut self) {
+        for (i, rule) in self.rules.0.iter_mut().enumerate() {
+            rule.seeding_time_source
+                .get_or_insert(self.seeding_time_source);
+            if rule.is_destructive()
+                && !self.allow_destructive
+                && rule.dry_run != Some(true)
+                && rule.is_enabled()
+            {
+                log::warn!(
+                    "Rule #{} has destructive actions but allowDestructive is not set: \
+                    running it in dry-run mode",
+                    i + 1
+                );
+                rule.dry_run = Some(true);
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(tag) = &self.managed_tag {
+            if !is_valid_tag(tag) {
+                return Err(ConfigError::Invalid(format!(
+                    "managedTag \"{}\" is not a valid tag",
+                    tag
+                )));
+            }
+        }
+        if self.tunnel.is_some() {
+            // The tunnel listens on 127.0.0.1, so other hosts would bypass it.
+            let host = url::Url::parse(&self.server.address)
+                .ok()
+                .and_then(|x| x.host_str().map(str::to_string));
+            if !matches!(host.as_deref(), Some("localhost" | "127.0.0.1")) {
+                return Err(ConfigError::Invalid(format!(
+                    "tunnel needs server.address to be on localhost or 127.0.0.1, not {}",
+                    self.server.address
+                )));
+            }
+        }
+        if self.max_changes_per_cycle == Some(0) {
+            return Err(ConfigError::Invalid(
+                "maxChangesPerCycle is 0, which would skip every cycle with changes".to_string(),
+            ));
+        }
+        if self.max_changes_per_run == Some(0) {
+            return Err(ConfigError::Invalid(
+                "maxChangesPerRun is 0, which would never change torrents".to_string(),
+            ));
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.action.is_some() {
+                if rule.continues == Some(true) {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} deletes torrents but also has continue",
+                        i + 1
+                    )));
+                }
+                if rule.limits.is_some() || !rule.tiers.is_empty() || !rule.actions.is_empty() {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} deletes torrents but also has limits or actions",
+                        i + 1
+                    )));
+                }
+                continue;
+            }
+            match (&rule.limits, rule.tiers.is_empty()) {
+                (None, true) if rule.actions.is_empty() && rule.actions.exec.is_some() => {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} only has exec, which needs limits or other actions to run",
+                        i + 1
+                    )))
+                }
+                (None, true) if rule.actions.is_empty() => {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} has no limits, tiers or actions",
+                        i + 1
+                    )))
+                }
+                (Some(_), false) => {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} has both limits and tiers",
+                        i + 1
+                    )))
+                }
+                _ => {}
+            }
+            if rule
+                .actions
+                .exec
+                .as_ref()
+                .is_some_and(|x| x.split().is_none())
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} has an empty exec command",
+                    i + 1
+                )));
+            }
+            if !rule.tiers.windows(2).all(|x| x[0].after < x[1].after) {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} has tiers that are not in ascending order",
+                    i + 1
+                )));
+            }
+            if matches!(rule.limits, Some(Limits::TrackerPolicy(_)))
+                && self.tracker_presets.is_empty()
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} uses trackerPolicy without trackerPresets",
+                    i + 1
+                )));
+            }
+            if let Some(tag) = rule
+                .actions
+                .add_tags
+                .iter()
+                .find(|&x| rule.actions.remove_tags.contains(x))
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} both adds and removes tag \"{}\"",
+                    i + 1,
+                    tag
+                )));
+            }
+            if let Some(tag) = &rule.actions.retire {
+                if !is_valid_tag(tag) {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} retires torrents with tag \"{}\", which is not a valid tag",
+                        i + 1,
+                        tag
+                    )));
+                }
+                if rule.limits.is_none() && rule.tiers.is_empty() {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} retires torrents without setting limits",
+                        i + 1
+                    )));
+                }
+            }
+            if rule.interval == Some(0) {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} has interval 0, which would never evaluate it",
+                    i + 1
+                )));
+            }
+            if rule.max_per_run == Some(0) {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} has maxPerRun 0, which would never change torrents",
+                    i + 1
+                )));
+            }
+            if rule.only_tighten == Some(true) && rule.only_loosen == Some(true) {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} sets both onlyTighten and onlyLoosen",
+                    i + 1
+                )));
+            }
+            let channels = match &rule.notify {
+                Some(Notify::Channels(channels)) => channels.as_slice(),
+                Some(Notify::Target(target)) if !is_webhook_url(target) => {
+                    std::slice::from_ref(target)
+                }
+                _ => &[],
+            };
+            if let Some(channel) = channels
+                .iter()
+                .find(|&x| !self.notifications.channels.contains_key(x))
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "rule #{} notifies unknown channel \"{}\"",
+                    i + 1,
+                    channel
+                )));
+            }
+            if let Some(id) = rule.id.as_ref().filter(|_| self.rule_tags) {
+                if !is_valid_tag(id) || id.trim() != id {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule #{} has id \"{}\", which can't be used in its rule tag",
+                        i + 1,
+                        id
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_file(path: &Path) -> Result<String, io::Error> {
+        let mut file = File::open(path)?;
+        let mut file_content = String::new();
+        file.read_to_string(&mut file_content)?;
+        Ok(file_content)
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct IgnoreList {
+    categories: Vec<String>,
+    hashes: Vec<String>,
+    names: Vec<Pattern>,
+    tags: Vec<String>,
+}
+
+impl IgnoreList {
+    pub fn matches(&self, hash: &str, torrent: &qbittorrent::Torrent) -> bool {
+        self.categories.contains(&torrent.category)
+            || self.hashes.iter().any(|x| x.eq_ignore_ascii_case(hash))
+            || self.names.iter().any(|x| x.matches(&torrent.name))
+            || self.tags.iter().any(|x| torrent.tags.contains(x))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty()
+            && self.hashes.is_empty()
+            && self.names.is_empty()
+            && self.tags.is_empty()
+    }
+}
+
+impl fmt::Display for IgnoreList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut entries = Vec::<String>::new();
+        if !self.categories.is_empty() {
+            entries.push(format!("categories = [{}]", self.categories.join(", ")));
+        }
+        if !self.hashes.is_empty() {
+            entries.push(format!("{} hashes", self.hashes.len()));
+        }
+        if !self.names.is_empty() {
+            let names: Vec<String> = self.names.iter().map(|x| x.to_string()).collect();
+            entries.push(format!("names = [{}]", names.join(", ")));
+        }
+        if !self.tags.is_empty() {
+            entries.push(format!("tags = [{}]", self.tags.join(", ")));
+        }
+        write!(f, "{}", entries.join(", "))
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NotificationConfig {
+    pub channels: BTreeMap<String, Channel>,
+    /// Send the report produced on the first cycle after startup to all channels.
+    pub startup_report: bool,
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Channel {
+    Telegram { bot_token: String, chat_id: String },
+    Webhook { url: String },
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PollingConfig {
+    /// Seconds between syncs.
+    pub interval: u64,
+    /// Seconds between syncs for a while after new torrents have been added.
+    pub new_torrent_interval: Option<u64>,
+    /// Seconds that `new_torrent_interval` is used for after new torrents have been added.
+    pub new_torrent_duration: u64,
+    /// Upper bound in seconds for backing off the interval when syncs contain no changes.
+    pub max_interval: Option<u64>,
+    /// Number of consecutive syncs without changes before the interval is backed off.
+    pub idle_syncs: u32,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            interval: 60,
+            new_torrent_interval: None,
+            new_torrent_duration: 300,
+            max_interval: None,
+            idle_syncs: 3,
+        }
+    }
+}
+
+/// Whether applying a rule sends notifications, either to all channels, to the listed ones or
+/// to a single channel or webhook URL.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum Notify {
+    Enabled(bool),
+    Channels(Vec<String>),
+    Target(String),
+}
+
+/// Whether a rule's notification target is a webhook URL rather than the name of a channel.
+pub fn is_webhook_url(target: &str) -> bool {
+    target.starts_with("http://") || target.starts_with("https://")
+}
+
+/// Whether qBittorrent accepts the tag: tags can't be empty or contain commas.
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.trim().is_empty() && !tag.contains(',')
+}
+
+/// Conditions that a torrent has to meet for a rule to apply to it. All of the set conditions
+/// have to match.
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Conditions {
+    /// Time since the torrent was added.
+    age: Option<Comparison<TimeSpan>>,
+    /// Automatic Torrent Management is enabled for the torrent.
+    auto_tmm: Option<bool>,
+    /// Distributed copies of the torrent in the swarm.
+    availability: Option<Comparison<f64>>,
+    category: Option<Categories>,
+    /// Torrent has no category.
+    uncategorized: Option<bool>,
+    /// Torrent is from a private tracker.
+    is_private: Option<bool>,
+    /// Another torrent has the same content path.
+    is_cross_seed: Option<bool>,
+    /// Torrent has finished downloading the selected files.
+    completed: Option<bool>,
+    /// Time since the torrent finished downloading; never matches unfinished torrents.
+    completed_age: Option<Comparison<TimeSpan>>,
+    /// Amount of data downloaded.
+    downloaded: Option<Comparison<Bytes>>,
+    /// Estimated time until the torrent finishes.
+    eta: Option<Comparison<TimeSpan>>,
+    /// Number of files in the torrent; never matches torrents whose files aren't known.
+    file_count: Option<Comparison<usize>>,
+    /// Torrent was force-started.
+    force_started: Option<bool>,
+    /// Number of leechers in the swarm.
+    leechers: Option<Comparison<usize>>,
+    /// Seeders per leecher in the swarm.
+    swarm_ratio: Option<Comparison<f64>>,
+    limit_reached: Option<bool>,
+    /// Time since the share limits were last seen changing. Limits of torrents present at startup
+    /// count as changed at startup, and limits of torrents added later when they were added.
+    limit_changed_age: Option<Comparison<TimeSpan>>,
+    /// Shell-style glob matched against the torrent name.
+    name: Option<Pattern>,
+    /// Fraction of the selected files downloaded, from 0 to 1.
+    progress: Option<Comparison<f64>>,
+    /// Current share ratio of the torrent.
+    ratio: Option<Comparison<f64>>,
+    seeding_time: Option<Comparison<Minutes>>,
+    /// Minutes since the torrent last uploaded or downloaded anything.
+    inactive_seeding_time: Option<Comparison<Minutes>>,
+    /// Total size of the selected files.
+    size: Option<Comparison<Bytes>>,
+    /// Torrent is in one of the states.
+    state: Option<Vec<StateFilter>>,
+    /// Torrent is in super-seeding mode.
+    super_seeding: Option<bool>,
+    tags: Option<qbittorrent::TagList>,
+    /// Torrent has at least one of the tags.
+    tags_any: Option<Vec<String>>,
+    /// Torrent has all of the tags, and possibly others.
+    tags_all: Option<Vec<String>>,
+    /// Torrent has none of the tags.
+    tags_none: Option<Vec<String>>,
+    /// Torrent has no tags.
+    untagged: Option<bool>,
+    /// Host name of one of the torrent's trackers; also matches subdomains.
+    tracker: Option<String>,
+    /// One of the torrent's trackers has the status.
+    tracker_status: Option<qbittorrent::TrackerStatus>,
+    /// Glob matched case-insensitively anywhere in the messages of the torrent's trackers.
+    #[serde(default, deserialize_with = "deserialize_substring_pattern")]
+    tracker_message: Option<Pattern>,
+    /// Number of trackers the torrent announces to.
+    tracker_count: Option<Comparison<usize>>,
+    /// Amount of data uploaded.
+    uploaded: Option<Comparison<Bytes>>,
+    /// Current upload speed per second.
+    up_speed: Option<Comparison<Bytes>>,
+    /// Conditions on raw maindata fields by their qBittorrent names.
+    #[serde(default)]
+    fields: BTreeMap<String, FieldCondition>,
+    /// Time windows in which the rule applies.
+    schedule: Option<schedule::Schedule>,
+    /// Expression evaluated against the torrent data.
+    when: Option<expression::Expression>,
+    /// Groups of conditions of which the torrent has to meet at least one.
+    #[serde(default)]
+    any_of: Vec<Conditions>,
+    /// Groups of conditions that the torrent has to meet all of.
+    #[serde(default)]
+    all_of: Vec<Conditions>,
+    /// Conditions that the torrent must not meet.
+    not: Option<Box<Conditions>>,
+}
+
+impl Conditions {
+    /// Whether the torrent meets the conditions. Tag conditions ignore the rule tags and
+    /// `managed_tag`, which jeanne adds to the torrents itself.
+    fn matches(
+        &self,
+        torrent: &qbittorrent::Torrent,
+        source: SeedingTimeSource,
+        managed_tag: Option<&str>,
+    ) -> bool {
+        if let Some(age) = &self.age {
+            if !age.compare(TimeSpan(torrent.age())) {
+                return false;
+            }
+        }
+        if let Some(auto_tmm) = self.auto_tmm {
+            if auto_tmm != torrent.auto_tmm {
+                return false;
+            }
+        }
+        if let Some(availability) = &self.availability {
+            if !availability.compare(torrent.availability) {
+                return false;
+            }
+        }
+        if let Some(category) = &self.category {
+            if !category.matches(&torrent.category) {
+                return false;
+            }
+        }
+        if let Some(uncategorized) = self.uncategorized {
+            if uncategorized != torrent.category.is_empty() {
+                return false;
+            }
+        }
+        if let Some(is_private) = self.is_private {
+            if torrent.private != Some(is_private) {
+                return false;
+            }
+        }
+        if let Some(is_cross_seed) = self.is_cross_seed {
+            if is_cross_seed != (torrent.cross_seeds > 0) {
+                return false;
+            }
+        }
+        if let Some(completed) = self.completed {
+            if completed != (torrent.progress >= 1.0) {
+                return false;
+            }
+        }
+        if let Some(completed_age) = &self.completed_age {
+            if torrent.completion_on <= 0
+                || !completed_age.compare(TimeSpan(torrent.time_since_completion()))
+            {
+                return false;
+            }
+        }
+        if let Some(downloaded) = &self.downloaded {
+            if !downloaded.compare(Bytes(torrent.downloaded)) {
+                return false;
+            }
+        }
+        if let Some(eta) = &self.eta {
+            if !eta.compare(TimeSpan(torrent.eta)) {
+                return false;
+            }
+        }
+        if let Some(file_count) = &self.file_count {
+            if !torrent.file_count.is_some_and(|x| file_count.compare(x)) {
+                return false;
+            }
+        }
+        if let Some(force_started) = self.force_started {
+            if force_started != torrent.force_start {
+                return false;
+            }
+        }
+        if let Some(leechers) = &self.leechers {
+            if !leechers.compare(torrent.num_incomplete) {
+                return false;
+            }
+        }
+        if let Some(swarm_ratio) = &self.swarm_ratio {
+            if !swarm_ratio.compare(torrent.swarm_ratio()) {
+                return false;
+            }
+        }
+        if let Some(limit_reached) = self.limit_reached {
+            if limit_reached != torrent.has_reached_limit() {
+                return false;
+            }
+        }
+        if let Some(limit_changed_age) = &self.limit_changed_age {
+            if !limit_changed_age.compare(TimeSpan(torrent.time_since_limit_change())) {
+                return false;
+            }
+        }
+        if let Some(name) = &self.name {
+            if !name.matches(&torrent.name) {
+                return false;
+            }
+        }
+        if let Some(progress) = &self.progress {
+            if !progress.compare(torrent.progress) {
+                return false;
+            }
+        }
+        if let Some(ratio) = &self.ratio {
+            if !ratio.compare(torrent.ratio) {
+                return false;
+            }
+        }
+        if let Some(seeding_time) = &self.seeding_time {
+            if !seeding_time.compare(Minutes(seeding_minutes(torrent, source))) {
+                return false;
+            }
+        }
+        if let Some(inactive_seeding_time) = &self.inactive_seeding_time {
+            if !inactive_seeding_time.compare(Minutes(torrent.inactive_time() / 60)) {
+                return false;
+            }
+        }
+        if let Some(size) = &self.size {
+            if !size.compare(Bytes(torrent.size)) {
+                return false;
+            }
+        }
+        if let Some(state) = &self.state {
+            if !state.iter().any(|x| x.matches(&torrent.state)) {
+                return false;
+            }
+        }
+        if let Some(super_seeding) = self.super_seeding {
+            if super_seeding != torrent.super_seeding {
+                return false;
+            }
+        }
+        let has_tag = |tag: &String| user_tags(torrent, managed_tag).any(|x| x == tag);
+        if let Some(tags) = &self.tags {
+            if !tags.iter().eq(user_tags(torrent, managed_tag)) {
+                return false;
+            }
+        }
+        if let Some(tags_any) = &self.tags_any {
+            if !tags_any.iter().any(has_tag) {
+                return false;
+            }
+        }
+        if let Some(tags_all) = &self.tags_all {
+            if !tags_all.iter().all(has_tag) {
+                return false;
+            }
+        }
+        if let Some(tags_none) = &self.tags_none {
+            if tags_none.iter().any(has_tag) {
+                return false;
+            }
+        }
+        if let Some(untagged) = self.untagged {
+            if untagged != user_tags(torrent, managed_tag).next().is_none() {
+                return false;
+            }
+        }
+        if let Some(tracker) = &self.tracker {
+            let current = torrent.tracker_host();
+            let mut hosts = torrent
+                .trackers
+                .iter()
+                .map(|x| &x.host)
+                .chain(current.as_ref());
+            if !hosts.any(|host| host_matches(host, tracker)) {
+                return false;
+            }
+        }
+        if let Some(status) = self.tracker_status {
+            if !torrent.trackers.iter().any(|x| x.status == status) {
+                return false;
+            }
+        }
+        if let Some(message) = &self.tracker_message {
+            if !torrent
+                .trackers
+                .iter()
+                .any(|x| message.matches_ignore_case(&x.message))
+            {
+                return false;
+            }
+        }
+        if let Some(tracker_count) = &self.tracker_count {
+            if !tracker_count.compare(torrent.trackers.len()) {
+                return false;
+            }
+        }
+        if let Some(uploaded) = &self.uploaded {
+            if !uploaded.compare(Bytes(torrent.uploaded)) {
+                return false;
+            }
+        }
+        if let Some(up_speed) = &self.up_speed {
+            if !up_speed.compare(Bytes(torrent.upspeed)) {
+                return false;
+            }
+        }
+        for (name, condition) in &self.fields {
+            if !condition.matches(torrent.field(name).as_ref()) {
+                return false;
+            }
+        }
+        if let Some(schedule) = &self.schedule {
+            if !schedule.matches() {
+                return false;
+            }
+        }
+        if let Some(when) = &self.when {
+            if !when.matches(torrent) {
+                return false;
+            }
+        }
+        if !self.any_of.is_empty()
+            && !self
+                .any_of
+                .iter()
+                .any(|x| x.matches(torrent, source, managed_tag))
+        {
+            return false;
+        }
+        if !self
+            .all_of
+            .iter()
+            .all(|x| x.matches(torrent, source, managed_tag))
+        {
+            return false;
+        }
+        if let Some(not) = &self.not {
+            if not.matches(torrent, source, managed_tag) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Human-readable descriptions of the set conditions.
+    fn describe(&self, source: SeedingTimeSource) -> Vec<String> {
+        let mut conditions = Vec::<String>::new();
+        if let Some(age) = &self.age {
+            conditions.push(format!("age {}", age));
+        }
+        if let Some(auto_tmm) = self.auto_tmm {
+            conditions.push(if auto_tmm {
+                "automatic management".to_string()
+            } else {
+                "manual management".to_string()
+            });
+        }
+        if let Some(availability) = &self.availability {
+            conditions.push(format!("availability {}", availability));
+        }
+        if let Some(category) = &self.category {
+            conditions.push(match category.0.as_slice() {
+                [_] => format!("category matches {}", category),
+                _ => format!("category matches any of {}", category),
+            });
+        }
+        if let Some(uncategorized) = self.uncategorized {
+            conditions.push(if uncategorized {
+                "uncategorized".to_string()
+            } else {
+                "has a category".to_string()
+            });
+        }
+        if let Some(is_private) = self.is_private {
+            conditions.push(if is_private {
+                "private".to_string()
+            } else {
+                "public".to_string()
+            });
+        }
+        if let Some(is_cross_seed) = self.is_cross_seed {
+            conditions.push(if is_cross_seed {
+                "cross-seeded".to_string()
+            } else {
+                "not cross-seeded".to_string()
+            });
+        }
+        if let Some(completed) = self.completed {
+            conditions.push(if completed {
+                "completed".to_string()
+            } else {
+                "not completed".to_string()
+            });
+        }
+        if let Some(completed_age) = &self.completed_age {
+            conditions.push(format!("completed age {}", completed_age));
+        }
+        if let Some(downloaded) = &self.downloaded {
+            conditions.push(format!("downloaded {}", downloaded));
+        }
+        if let Some(eta) = &self.eta {
+            conditions.push(format!("ETA {}", eta));
+        }
+        if let Some(file_count) = &self.file_count {
+            conditions.push(format!("file count {}", file_count));
+        }
+        if let Some(force_started) = self.force_started {
+            conditions.push(if force_started {
+                "force-started".to_string()
+            } else {
+                "not force-started".to_string()
+            });
+        }
+        if let Some(leechers) = &self.leechers {
+            conditions.push(format!("leechers {}", leechers));
+        }
+        if let Some(swarm_ratio) = &self.swarm_ratio {
+            conditions.push(format!("swarm ratio {}", swarm_ratio));
+        }
+        if let Some(limit_reached) = self.limit_reached {
+            conditions.push(if limit_reached {
+                "limit reached".to_string()
+            } else {
+                "limit not reached".to_string()
+            });
+        }
+        if let Some(limit_changed_age) = &self.limit_changed_age {
+            conditions.push(format!("time since limit change {}", limit_changed_age));
+        }
+        if let Some(name) = &self.name {
+            conditions.push(format!("name matches {}", name));
+        }
+        if let Some(progress) = &self.progress {
+            conditions.push(format!("progress {}", progress));
+        }
+        if let Some(ratio) = &self.ratio {
+            conditions.push(format!("ratio {}", ratio));
+        }
+        if let Some(seeding_time) = &self.seeding_time {
+            conditions.push(format!(
+                "{} {} minutes",
+                match source {
+                    SeedingTimeSource::SeedingTime => "seeding time",
+                    SeedingTimeSource::Completion => "time since completion",
+                },
+                seeding_time
+            ));
+        }
+        if let Some(inactive_seeding_time) = &self.inactive_seeding_time {
+            conditions.push(format!(
+                "inactive seeding time {} minutes",
+                inactive_seeding_time
+            ));
+        }
+        if let Some(size) = &self.size {
+            conditions.push(format!("size {}", size));
+        }
+        if let Some(state) = &self.state {
+            let states: Vec<String> = state.iter().map(|x| x.to_string()).collect();
+            conditions.push(format!("state is one of [{}]", states.join(", ")));
+        }
+        if let Some(super_seeding) = self.super_seeding {
+            conditions.push(if super_seeding {
+                "super-seeding".to_string()
+            } else {
+                "not super-seeding".to_string()
+            });
+        }
+        if let Some(tags) = &self.tags {
+            conditions.push(format!("tags = {}", tags));
+        }
+        if let Some(tags_any) = &self.tags_any {
+            conditions.push(format!("tags include any of [{}]", tags_any.join(", ")));
+        }
+        if let Some(tags_all) = &self.tags_all {
+            conditions.push(format!("tags include all of [{}]", tags_all.join(", ")));
+        }
+        if let Some(tags_none) = &self.tags_none {
+            conditions.push(format!("tags include none of [{}]", tags_none.join(", ")));
+        }
+        if let Some(untagged) = self.untagged {
+            conditions.push(if untagged {
+                "untagged".to_string()
+            } else {
+                "has tags".to_string()
+            });
+        }
+        if let Some(tracker) = &self.tracker {
+            conditions.push(format!("tracker = {}", tracker));
+        }
+        if let Some(status) = self.tracker_status {
+            conditions.push(format!("tracker status = {}", status));
+        }
+        if let Some(message) = &self.tracker_message {
+            conditions.push(format!("tracker message matches {}", message));
+        }
+        if let Some(tracker_count) = &self.tracker_count {
+            conditions.push(format!("tracker count {}", tracker_count));
+        }
+        if let Some(uploaded) = &self.uploaded {
+            conditions.push(format!("uploaded {}", uploaded));
+        }
+        if let Some(up_speed) = &self.up_speed {
+            conditions.push(format!("upload speed {}/s", up_speed));
+        }
+        for (name, condition) in &self.fields {
+            conditions.push(format!("{} {}", name, condition));
+        }
+        if let Some(schedule) = &self.schedule {
+            conditions.push(format!("during {}", schedule));
+        }
+        if let Some(when) = &self.when {
+            conditions.push(format!("when {}", when));
+        }
+        if !self.any_of.is_empty() {
+            let groups: Vec<String> = self
+                .any_of
+                .iter()
+                .map(|x| format!("({})", x.describe(source).join(", ")))
+                .collect();
+            conditions.push(format!("any of [{}]", groups.join(", ")));
+        }
+        for group in &self.all_of {
+            conditions.push(format!("({})", group.describe(source).join(", ")));
+        }
+        if let Some(not) = &self.not {
+            conditions.push(format!("not ({})", not.describe(source).join(", ")));
+        }
+        conditions
+    }
+
+    /// Whether the conditions or any of the nested conditions satisfy the predicate.
+    fn any(&self, predicate: &impl Fn(&Self) -> bool) -> bool {
+        predicate(self)
+            || self.any_of.iter().any(|x| x.any(predicate))
+            || self.all_of.iter().any(|x| x.any(predicate))
+            || self.not.as_ref().is_some_and(|x| x.any(predicate))
+    }
+}
+
+/// Tags of the torrent other than the rule tags and the managed tag that jeanne adds itself.
+fn user_tags<'a>(
+    torrent: &'a qbittorrent::Torrent,
+    managed_tag: Option<&'a str>,
+) -> impl Iterator<Item = &'a String> {
+    torrent
+        .tags
+        .iter()
+        .filter(move |x| !x.starts_with(crate::RULE_TAG_PREFIX) && Some(x.as_str()) != managed_tag)
+}
+
+/// Seeding time of the torrent in minutes, measured as the source describes.
+fn seeding_minutes(torrent: &qbittorrent::Torrent, source: SeedingTimeSource) -> usize {
+    let seconds = match source {
+        SeedingTimeSource::SeedingTime => torrent.seeding_time,
+        SeedingTimeSource::Completion => torrent.time_since_completion(),
+    };
+    seconds / 60
+}
+
+/// Settings of `ruleDefaults`. Only settings of how a rule is applied can have defaults, not
+/// what it matches or changes.
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RuleDefaults {
+    enabled: Option<bool>,
+    seeding_time_source: Option<SeedingTimeSource>,
+    notify: Option<Notify>,
+    apply_to_cross_seeds: Option<bool>,
+    only_tighten: Option<bool>,
+    only_loosen: Option<bool>,
+    dry_run: Option<bool>,
+    max_per_run: Option<usize>,
+    #[serde(rename = "continue")]
+    continues: Option<bool>,
+    run_schedule: Option<schedule::Schedule>,
+    interval: Option<u64>,
+    cooldown: Option<TimeSpan>,
+}
+
+impl RuleDefaults {
+    /// Fills in the settings that the rule doesn't set. A rule setting either direction keeps
+    /// it, and rules with an `action` don't continue.
+    fn fill(&self, rule: &mut Rule) {
+        rule.enabled = rule.enabled.or(self.enabled);
+        rule.seeding_time_source = rule.seeding_time_source.or(self.seeding_time_source);
+        if rule.notify.is_none() {
+            rule.notify.clone_from(&self.notify);
+        }
+        rule.apply_to_cross_seeds = rule.apply_to_cross_seeds.or(self.apply_to_cross_seeds);
+        if rule.only_tighten.is_none() && rule.only_loosen.is_none() {
+            rule.only_tighten = self.only_tighten;
+            rule.only_loosen = self.only_loosen;
+        }
+        rule.dry_run = rule.dry_run.or(self.dry_run);
+        rule.max_per_run = rule.max_per_run.or(self.max_per_run);
+        if rule.action.is_none() {
+            rule.continues = rule.continues.or(self.continues);
+        }
+        if rule.run_schedule.is_none() {
+            rule.run_schedule.clone_from(&self.run_schedule);
+        }
+        rule.interval = rule.interval.or(self.interval);
+        rule.cooldown = rule.cooldown.or(self.cooldown);
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    /// Name of the rule; defaults to the position of the rule in the list.
+    id: Option<String>,
+    /// Set to `false` to keep the rule in the configuration without applying it.
+    enabled: Option<bool>,
+    /// Torrents that the rule applies to regardless of its conditions.
+    #[serde(default)]
+    hashes: Vec<String>,
+    /// Torrents that the rule never applies to.
+    #[serde(default)]
+    exclude_hashes: Vec<String>,
+    #[serde(flatten)]
+    conditions: Conditions,
+    seeding_time_source: Option<SeedingTimeSource>,
+    limits: Option<Limits>,
+    /// Limits depending on seeding time, used instead of `limits`.
+    #[serde(default)]
+    tiers: Vec<Tier>,
+    /// Action taken on matched torrents instead of setting limits.
+    pub action: Option<Action>,
+    /// Changes made to matched torrents along with their limits.
+    #[serde(default)]
+    pub actions: Actions,
+    pub notify: Option<Notify>,
+    /// Also apply the rule to the cross-seeds of the torrents it matches.
+    pub apply_to_cross_seeds: Option<bool>,
+    /// Only change limits that become stricter.
+    only_tighten: Option<bool>,
+    /// Only change limits that become more generous.
+    only_loosen: Option<bool>,
+    /// Log the changes of the rule without applying them.
+    pub dry_run: Option<bool>,
+    /// Maximum number of torrents the rule changes per cycle.
+    pub max_per_run: Option<usize>,
+    /// Let the next rule that applies to the torrent fill in the limits and actions that this
+    /// rule doesn't set.
+    #[serde(rename = "continue")]
+    pub continues: Option<bool>,
+    /// Times at which the rule applies its changes; in between, its changes are held back.
+    pub run_schedule: Option<schedule::Schedule>,
+    /// Number of cycles between evaluations of the rule; in between, its changes are held back.
+    interval: Option<u64>,
+    /// Time after the rule has changed a torrent during which it doesn't change it again.
+    pub cooldown: Option<TimeSpan>,
+}
+
+impl Rule {
+    pub fn name(&self, index: usize) -> Cow<'_, str> {
+        match &self.id {
+            Some(id) => Cow::from(id.as_str()),
+            None => Cow::from(format!("rule-{}", index + 1)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != Some(false)
+    }
+
+    /// Whether the rule applies its changes in the cycle, counted from 0 at startup, that started
+    /// at `to` after the previous one started at `from`.
+    pub fn is_due(&self, cycle: u64, from: i64, to: i64) -> bool {
+        self.is_evaluated(cycle)
+            && self
+                .run_schedule
+                .as_ref()
+                .is_none_or(|x| x.fired_between(from, to))
+    }
+
+    /// Whether the rule's `interval` has it evaluated in the cycle.
+    fn is_evaluated(&self, cycle: u64) -> bool {
+        self.interval.is_none_or(|x| cycle.is_multiple_of(x))
+    }
+
+    fn uses_tracker_statuses(&self) -> bool {
+        self.conditions
+            .any(&|x| x.tracker_status.is_some() || x.tracker_message.is_some())
+    }
+
+    /// Whether the rule can start or stop matching a torrent as time passes, without anything
+    /// about the torrent changing.
+    fn uses_clock(&self) -> bool {
+        let completion = self.seeding_time_source == Some(SeedingTimeSource::Completion);
+        (completion && !self.tiers.is_empty())
+            || self.conditions.any(&|x| {
+                x.age.is_some()
+                    || x.completed_age.is_some()
+                    || x.limit_changed_age.is_some()
+                    || x.inactive_seeding_time.is_some()
+                    || x.limit_reached.is_some()
+                    || x.schedule.is_some()
+                    || (completion && x.seeding_time.is_some())
+                    || x.when.as_ref().is_some_and(|x| {
+                        ["age", "completed_age", "inactive_time"]
+                            .iter()
+                            .any(|field| x.uses(field))
+                    })
+            })
+    }
+
+    /// Whether the rule can apply to the hash at all. Disabled rules apply to no torrents and
+    /// excluded hashes are never matched.
+    pub fn admits(&self, hash: &str) -> bool {
+        self.is_enabled()
+            && !self
+                .exclude_hashes
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case(hash))
+    }
+
+    /// Whether the rule lists the hash in `hashes`.
+    pub fn pins(&self, hash: &str) -> bool {
+        self.admits(hash) && self.hashes.iter().any(|x| x.eq_ignore_ascii_case(hash))
+    }
+
+    /// Whether the rule applies to the torrent through its conditions.
+    fn applies_to(
+        &self,
+        hash: &str,
+        torrent: &qbittorrent::Torrent,
+        managed_tag: Option<&str>,
+    ) -> bool {
+        self.admits(hash) && self.matches(torrent, managed_tag)
+    }
+
+    fn matches(&self, torrent: &qbittorrent::Torrent, managed_tag: Option<&str>) -> bool {
+        let source = self.seeding_time_source.unwrap_or_default();
+        if !self.conditions.matches(torrent, source, managed_tag) {
+            return false;
+        }
+        if !self.tiers.is_empty() && self.tier(torrent).is_none() {
+            return false;
+        }
+        if let Some(Limits::TrackerPolicy(presets)) = &self.limits {
+            let host = torrent.tracker_host();
+            if host.and_then(|x| presets.get(&x)).is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Last tier whose threshold the torrent has reached.
+    fn tier(&self, torrent: &qbittorrent::Torrent) -> Option<&Tier> {
+        let minutes = seeding_minutes(torrent, self.seeding_time_source.unwrap_or_default());
+        self.tiers.iter().rev().find(|tier| minutes >= tier.after.0)
+    }
+
+    fn limits_for(&self, torrent: &qbittorrent::Torrent) -> Option<&RuleLimits> {
+        match &self.limits {
+            Some(Limits::Fixed(limits)) => Some(limits),
+            Some(Limits::TrackerPolicy(presets)) => presets.get(&torrent.tracker_host()?),
+            None => self.tier(torrent).map(|tier| &tier.limits),
+        }
+    }
+
+    /// Share limits the rule wants the torrent to have, after the direction guard.
+    pub fn resolved_limits(&self, torrent: &qbittorrent::Torrent) -> Option<RuleLimits> {
+        let mut target = self.limits_for(torrent)?.clone();
+        if let Some(direction) = self.direction() {
+            target.ratio =
+                direction.guard(torrent.max_ratio, torrent.ratio_limit, target.ratio, -1.0);
+            target.minutes = direction.guard(
+                torrent.max_seeding_time,
+                torrent.seeding_time_limit,
+                target.minutes,
+                -1,
+            );
+            // Unset inactive limits stay global instead of keeping the current limit.
+            if target.inactive_minutes.is_some() {
+                target.inactive_minutes = direction.guard(
+                    torrent.max_inactive_seeding_time,
+                    torrent.inactive_seeding_time_limit,
+                    target.inactive_minutes,
+                    -1,
+                );
+            }
+            if target.upload_ki_bps.is_some() {
+                target.upload_ki_bps =
+                    direction.guard(torrent.upload_limit(), None, target.upload_ki_bps, 0);
+            }
+            if target.download_ki_bps.is_some() {
+                target.download_ki_bps =
+                    direction.guard(torrent.download_limit(), None, target.download_ki_bps, 0);
+            }
+        }
+        Some(target)
+    }
+
+    /// Whether the rule has actions that can lose data, such as deleting, pausing or moving
+    /// torrents.
+    fn is_destructive(&self) -> bool {
+        // Enabling Automatic Torrent Management moves the data to the category's save path.
+        self.action.is_some()
+            || self.actions.set_location.is_some()
+            || self.actions.auto_tmm == Some(true)
+            || self.actions.pause
+    }
+
+    fn direction(&self) -> Option<Direction> {
+        if self.only_tighten == Some(true) {
+            Some(Direction::Tighten)
+        } else if self.only_loosen == Some(true) {
+            Some(Direction::Loosen)
+        } else {
+            None
+        }
+    }
+}
+
+/// Action that a rule takes on the torrents it matches instead of setting limits.
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum Action {
+    /// Removes the torrent from qBittorrent and keeps the downloaded data.
+    Delete,
+    /// Removes the torrent along with the downloaded data.
+    DeleteWithData,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Delete => write!(f, "delete"),
+            Self::DeleteWithData => write!(f, "delete with data"),
+        }
+    }
+}
+
+/// Direction in which a rule moves the torrents it matches in the queue.
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum QueueMove {
+    Top,
+    Bottom,
+    Up,
+    Down,
+}
+
+impl fmt::Display for QueueMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Top => write!(f, "to the top of the queue"),
+            Self::Bottom => write!(f, "to the bottom of the queue"),
+            Self::Up => write!(f, "up in the queue"),
+            Self::Down => write!(f, "down in the queue"),
+        }
+    }
+}
+
+/// Changes that a rule makes to the torrents it matches besides their limits.
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Actions {
+    /// Category to move the torrents to.
+    pub set_category: Option<String>,
+    /// Tags to add to the torrents.
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    /// Tags to remove from the torrents.
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+    /// Directory to move the torrents' data to.
+    pub set_location: Option<String>,
+    /// Whether to enable or disable super seeding on the torrents.
+    pub super_seeding: Option<bool>,
+    /// Whether to recheck the torrents' data, e.g. to recover torrents in the error state.
+    #[serde(default)]
+    pub recheck: bool,
+    /// Where to move the torrents in the queue.
+    pub queue: Option<QueueMove>,
+    /// Whether to enable or disable Automatic Torrent Management on the torrents.
+    pub auto_tmm: Option<bool>,
+    /// Announce URLs to add to the torrents.
+    #[serde(default)]
+    pub add_trackers: Vec<String>,
+    /// Globs matched case-insensitively against the announce URLs to remove from the torrents.
+    #[serde(default)]
+    pub remove_trackers: Vec<Pattern>,
+    /// Whether to download the torrents in sequential order.
+    pub sequential_download: Option<bool>,
+    /// Whether to pause the torrents, or stop them in qBittorrent 5.0 and later.
+    #[serde(default)]
+    pub pause: bool,
+    /// Command to run with the torrent described in environment variables.
+    pub exec: Option<ExecCommand>,
+    /// Tag to add when pausing the torrents along with setting the rule's limits; expanded into
+    /// `pause` and `addTags` when the configuration is loaded.
+    pub retire: Option<String>,
+}
+
+impl Actions {
+    fn is_empty(&self) -> bool {
+        self.set_category.is_none()
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+            && self.set_location.is_none()
+            && self.super_seeding.is_none()
+            && !self.recheck
+            && self.queue.is_none()
+            && self.auto_tmm.is_none()
+            && self.add_trackers.is_empty()
+            && self.remove_trackers.is_empty()
+            && self.sequential_download.is_none()
+            && !self.pause
+            && self.retire.is_none()
+    }
+}
+
+impl fmt::Display for Actions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut actions = Vec::new();
+        if let Some(category) = &self.set_category {
+            actions.push(format!("set category to {}", category));
+        }
+        if !self.add_tags.is_empty() {
+            actions.push(format!("add tags [{}]", self.add_tags.join(", ")));
+        }
+        if !self.remove_tags.is_empty() {
+            actions.push(format!("remove tags [{}]", self.remove_tags.join(", ")));
+        }
+        if let Some(location) = &self.set_location {
+            actions.push(format!("move to {}", location));
+        }
+        match self.super_seeding {
+            Some(true) => actions.push("enable super seeding".to_string()),
+            Some(false) => actions.push("disable super seeding".to_string()),
+            None => {}
+        }
+        if self.recheck {
+            actions.push("recheck".to_string());
+        }
+        if let Some(queue) = self.queue {
+            actions.push(format!("move {}", queue));
+        }
+        match self.auto_tmm {
+            Some(true) => actions.push("enable automatic management".to_string()),
+            Some(false) => actions.push("disable automatic management".to_string()),
+            None => {}
+        }
+        if !self.add_trackers.is_empty() {
+            actions.push(format!("add trackers [{}]", self.add_trackers.join(", ")));
+        }
+        if !self.remove_trackers.is_empty() {
+            let patterns: Vec<String> =
+                self.remove_trackers.iter().map(|x| x.to_string()).collect();
+            actions.push(format!("remove trackers [{}]", patterns.join(", ")));
+        }
+        match self.sequential_download {
+            Some(true) => actions.push("enable sequential download".to_string()),
+            Some(false) => actions.push("disable sequential download".to_string()),
+            None => {}
+        }
+        if self.pause {
+            actions.push("pause".to_string());
+        }
+        if let Some(command) = &self.exec {
+            actions.push(format!("run {}", command));
+        }
+        write!(f, "{}", actions.join(", "))
+    }
+}
+
+/// External command, given either as a program or as a program and its arguments. The command is
+/// not run through a shell.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ExecCommand {
+    Program(String),
+    Args(Vec<String>),
+}
+
+impl ExecCommand {
+    /// The program and its arguments, or `None` for an empty list.
+    pub fn split(&self) -> Option<(&str, &[String])> {
+        match self {
+            Self::Program(program) => Some((program, &[])),
+            Self::Args(args) => args.split_first().map(|(x, rest)| (x.as_str(), rest)),
+        }
+    }
+}
+
+impl fmt::Display for ExecCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Program(program) => write!(f, "{}", program),
+            Self::Args(args) => write!(f, "{}", args.join(" ")),
+        }
+    }
+}
+
+/// Direction in which a rule is allowed to change limits.
+#[derive(Clone, Copy)]
+enum Direction {
+    Tighten,
+    Loosen,
+}
+
+impl Direction {
+    /// Returns the target limit if changing to it is allowed, or keeps the limit otherwise.
+    /// Unset targets are kept too instead of falling back to the global limit. `current` is the
+    /// limit in effect and `own` the one set on the torrent itself, if known. Keeping the own
+    /// limit, which may be -2, keeps torrents that use the global limit following it.
+    fn guard<T: Copy + Into<f64>>(
+        self,
+        current: T,
+        own: Option<T>,
+        target: Option<T>,
+        unlimited: T,
+    ) -> Option<T> {
+        let kept = Some(own.unwrap_or(current));
+        let Some(target_value) = target else {
+            return kept;
+        };
+        // Unlimited is the loosest possible limit; other negative values (global) are unknown.
+        let rank = |value: T| match value.into() {
+            value if value == unlimited.into() => Some(f64::INFINITY),
+            value if value < 0.0 => None,
+            value => Some(value),
+        };
+        let allowed = match (rank(current), rank(target_value)) {
+            (Some(current), Some(target)) => match self {
+                Self::Tighten => target < current,
+                Self::Loosen => target > current,
+            },
+            _ => true,
+        };
+        if allowed {
+            target
+        } else {
+            kept
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = self.seeding_time_source.unwrap_or_default();
+        let mut conditions = self.conditions.describe(source);
+        if !self.hashes.is_empty() {
+            conditions.push(format!("{} pinned hashes", self.hashes.len()));
+        }
+        if !self.exclude_hashes.is_empty() {
+            conditions.push(format!("{} excluded hashes", self.exclude_hashes.len()));
+        }
+        if conditions.is_empty() {
+            conditions.push("any torrent".to_string());
+        }
+        write!(f, "{} => ", conditions.join(", "))?;
+        if let Some(limits) = &self.limits {
+            write!(f, "{}", limits)?;
+        }
+        if let Some(action) = self.action {
+            write!(f, "{}", action)?;
+        }
+        if !self.tiers.is_empty() {
+            let tiers: Vec<String> = self
+                .tiers
+                .iter()
+                .map(|tier| format!("after {} minutes: {}", tier.after, tier.limits))
+                .collect();
+            write!(f, "tiers [{}]", tiers.join("; "))?;
+        }
+        if !self.actions.is_empty() {
+            if self.limits.is_some() || !self.tiers.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", self.actions)?;
+        }
+        if self.only_tighten == Some(true) {
+            write!(f, " (only tightening)")?;
+        } else if self.only_loosen == Some(true) {
+            write!(f, " (only loosening)")?;
+        }
+        if self.dry_run == Some(true) {
+            write!(f, " (dry run)")?;
+        }
+        if self.continues == Some(true) {
+            write!(f, " (continue)")?;
+        }
+        if let Some(schedule) = &self.run_schedule {
+            write!(f, " (runs at {})", schedule)?;
+        }
+        if let Some(interval) = self.interval {
+            write!(f, " (every {} cycles)", interval)?;
+        }
+        if let Some(cooldown) = self.cooldown {
+            write!(f, " (cooldown {})", cooldown)?;
+        }
+        Ok(())
+    }
+}
+
+/// Limits that apply once the seeding time of a torrent reaches `after`.
+#[derive(Deserialize, PartialEq, Debug)]
+pub struct Tier {
+    after: Minutes,
+    limits: RuleLimits,
+}
+
+/// What the `seedingTime` condition is compared against.
+#[derive(Deserialize, PartialEq, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum SeedingTimeSource {
+    /// qBittorrent's seeding time counter, which only advances while the client is running.
+    #[default]
+    SeedingTime,
+    /// Wall-clock time since the torrent finished downloading.
+    Completion,
+}
+
+/// A qBittorrent torrent state, such as `stalledUP`, or one of the groups `seeding`, `paused`
+/// and `errored`.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(from = "String")]
+pub enum StateFilter {
+    Seeding,
+    Paused,
+    Errored,
+    Exact(String),
+}
+
+impl StateFilter {
+    fn matches(&self, state: &str) -> bool {
+        match self {
+            Self::Seeding => matches!(state, "uploading" | "stalledUP" | "queuedUP" | "forcedUP"),
+            Self::Paused => qbittorrent::is_paused(state),
+            Self::Errored => matches!(state, "error" | "missingFiles"),
+            Self::Exact(exact) => exact == state,
+        }
+    }
+}
+
+impl From<String> for StateFilter {
+    fn from(state: String) -> Self {
+        match state.as_str() {
+            "seeding" => Self::Seeding,
+            "paused" => Self::Paused,
+            "errored" => Self::Errored,
+            _ => Self::Exact(state),
+        }
+    }
+}
+
+impl fmt::Display for StateFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Seeding => write!(f, "seeding"),
+            Self::Paused => write!(f, "paused"),
+            Self::Errored => write!(f, "errored"),
+            Self::Exact(state) => write!(f, "{}", state),
+        }
+    }
+}
+
+/// Whether the host is the domain or one of its subdomains.
+fn host_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|x| x.ends_with('.'))
+}
+
+/// Share limit requirements by tracker host name. A host also covers its subdomains.
+#[derive(Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct TrackerPresets(IndexMap<String, RuleLimits>);
+
+impl TrackerPresets {
+    fn load(path: &Path) -> Result<Self, ConfigError> {
+        let yaml = Config::load_file(path).map_err(ConfigError::Io)?;
+        serde_yaml::from_str(&yaml).map_err(ConfigError::Deserialization)
+    }
+
+    /// Requirements of the most specific preset covering the host.
+    fn get(&self, host: &str) -> Option<&RuleLimits> {
+        self.0
+            .iter()
+            .filter(|(preset, _)| host_matches(host, preset))
+            .max_by_key(|(preset, _)| preset.len())
+            .map(|(_, limits)| limits)
+    }
+}
+
+/// Share limits set by a rule.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Limits {
+    Fixed(RuleLimits),
+    /// The requirements of the torrent's tracker, filled in from the presets when loading.
+    TrackerPolicy(TrackerPresets),
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Fixed(limits) => write!(f, "{}", limits),
+            Self::TrackerPolicy(_) => write!(f, "tracker policy"),
+        }
+    }
+}
+
+/// A keyword or share limits, for settings that accept either.
+enum KeywordOrLimits {
+    Keyword(String),
+    Limits(RuleLimits),
+}
+
+impl<'de> serde::Deserialize<'de> for KeywordOrLimits {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        // An untagged enum would replace the errors of the limits with a generic one.
+        match serde_yaml::Value::deserialize(d)? {
+            serde_yaml::Value::String(keyword) => Ok(Self::Keyword(keyword)),
+            value => RuleLimits::deserialize(value)
+                .map(Self::Limits)
+                .map_err(Error::custom),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Limits {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        match KeywordOrLimits::deserialize(d)? {
+            KeywordOrLimits::Limits(limits) => Ok(Self::Fixed(limits)),
+            KeywordOrLimits::Keyword(keyword) if keyword == "trackerPolicy" => {
+                Ok(Self::TrackerPolicy(TrackerPresets::default()))
+            }
+            KeywordOrLimits::Keyword(keyword) => Err(Error::invalid_value(
+                Unexpected::Str(&keyword),
+                &"share limits or trackerPolicy",
+            )),
+        }
+    }
+}
+
+/// What happens to the limits of torrents that don't match any rule.
+#[derive(PartialEq, Debug, Default)]
+pub enum DefaultAction {
+    /// Reset torrents with their own limits to the global limits.
+    #[default]
+    ResetToGlobal,
+    /// Keep whatever limits the torrents have, such as ones set manually in the WebUI.
+    LeaveAlone,
+    /// Set the limits as a rule would.
+    Limits(RuleLimits),
+}
+
+impl<'de> serde::Deserialize<'de> for DefaultAction {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        match KeywordOrLimits::deserialize(d)? {
+            KeywordOrLimits::Limits(limits) => Ok(Self::Limits(limits)),
+            KeywordOrLimits::Keyword(keyword) if keyword == "resetToGlobal" => {
+                Ok(Self::ResetToGlobal)
+            }
+            KeywordOrLimits::Keyword(keyword) if keyword == "leaveAlone" => Ok(Self::LeaveAlone),
+            KeywordOrLimits::Keyword(keyword) => Err(Error::invalid_value(
+                Unexpected::Str(&keyword),
+                &"resetToGlobal, leaveAlone or share limits",
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleLimits {
+    #[serde(default, deserialize_with = "deserialize_ratio_limit")]
+    pub ratio: Option<qbittorrent::Ratio>,
+    #[serde(default, deserialize_with = "deserialize_minutes_limit")]
+    pub minutes: Option<qbittorrent::MaxSeedingTime>,
+    /// Minutes the torrent may seed without uploading anything; needs qBittorrent 4.6 or later.
+    #[serde(default, deserialize_with = "deserialize_minutes_limit")]
+    pub inactive_minutes: Option<qbittorrent::MaxSeedingTime>,
+    /// Upload speed limit in KiB/s, where 0 is unlimited. Unset leaves the current limit alone.
+    pub upload_ki_bps: Option<u32>,
+    /// Download speed limit in KiB/s, where 0 is unlimited. Unset leaves the current limit alone.
+    pub download_ki_bps: Option<u32>,
+}
+
+impl RuleLimits {
+    /// The limits as `Client::share_limits` reports them, with global limits and speed limits
+    /// unset.
+    pub fn as_reported(&self) -> Self {
+        Self {
+            ratio: self.ratio.filter(|&x| x != GLOBAL_RATIO),
+            minutes: self.minutes.filter(|&x| x != GLOBAL_MINUTES),
+            inactive_minutes: self.inactive_minutes.filter(|&x| x != GLOBAL_MINUTES),
+            upload_ki_bps: None,
+            download_ki_bps: None,
+        }
+    }
+
+    /// Whether the share limits are the same as `other`'s, allowing for how qBittorrent rounds
+    /// ratio limits. Speed limits are compared exactly.
+    pub fn same_as(&self, other: &Self) -> bool {
+        same_limit(self.ratio, other.ratio)
+            && same_limit(self.minutes, other.minutes)
+            && same_limit(self.inactive_minutes, other.inactive_minutes)
+            && self.upload_ki_bps == other.upload_ki_bps
+            && self.download_ki_bps == other.download_ki_bps
+    }
+
+    /// The limits with the ones that aren't set taken from `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            ratio: self.ratio.or(other.ratio),
+            minutes: self.minutes.or(other.minutes),
+            inactive_minutes: self.inactive_minutes.or(other.inactive_minutes),
+            upload_ki_bps: self.upload_ki_bps.or(other.upload_ki_bps),
+            download_ki_bps: self.download_ki_bps.or(other.download_ki_bps),
+        }
+    }
+
+    /// Whether the torrent has different limits than the ones that are set.
+    pub fn differs_from(&self, torrent: &qbittorrent::Torrent) -> bool {
+        let mut update = false;
+        if self
+            .ratio
+            .is_some_and(|x| limit_differs(x, torrent.max_ratio, torrent.ratio_limit, GLOBAL_RATIO))
+        {
+            log::debug!("Torrent {} has incorrect ratio", torrent.name);
+            update = true;
+        }
+        if self.minutes.is_some_and(|x| {
+            limit_differs(
+                x,
+                torrent.max_seeding_time,
+                torrent.seeding_time_limit,
+                GLOBAL_MINUTES,
+            )
+        }) {
+            log::debug!("Torrent {} has incorrect max seeding time", torrent.name);
+            update = true;
+        }
+        if self.inactive_minutes.is_some_and(|x| {
+            limit_differs(
+                x,
+                torrent.max_inactive_seeding_time,
+                torrent.inactive_seeding_time_limit,
+                GLOBAL_MINUTES,
+            )
+        }) {
+            log::debug!(
+                "Torrent {} has incorrect max inactive seeding time",
+                torrent.name
+            );
+            update = true;
+        }
+        if self
+            .upload_ki_bps
+            .is_some_and(|x| x != torrent.upload_limit())
+        {
+            log::debug!("Torrent {} has incorrect upload limit", torrent.name);
+            update = true;
+        }
+        if self
+            .download_ki_bps
+            .is_some_and(|x| x != torrent.download_limit())
+        {
+            log::debug!("Torrent {} has incorrect download limit", torrent.name);
+            update = true;
+        }
+        update
+    }
+}
+
+const GLOBAL_RATIO: qbittorrent::Ratio = -2.0;
+const GLOBAL_MINUTES: qbittorrent::MaxSeedingTime = -2;
+
+/// Largest difference between ratio limits that are considered the same. qBittorrent doesn't
+/// always report a ratio limit exactly as it was set, such as 1.0499999523162842 for 1.05.
+const RATIO_TOLERANCE: qbittorrent::Ratio = 0.0005;
+
+/// Share limit value as qBittorrent reports it.
+pub trait LimitValue: Copy {
+    /// Whether the values are the same limit.
+    fn same_as(self, other: Self) -> bool;
+}
+
+impl LimitValue for qbittorrent::Ratio {
+    fn same_as(self, other: Self) -> bool {
+        (self - other).abs() <= RATIO_TOLERANCE
+    }
+}
+
+impl LimitValue for qbittorrent::MaxSeedingTime {
+    fn same_as(self, other: Self) -> bool {
+        self == other
+    }
+}
+
+/// Whether the torrent's share limit differs from the target. qBittorrent reports the global
+/// limit in effect rather than -2, so a global target is compared against the limit set on the
+/// torrent itself, if it's known.
+pub fn limit_differs<T: LimitValue>(target: T, current: T, own: Option<T>, global: T) -> bool {
+    if target.same_as(global) {
+        own.is_some_and(|x| !x.same_as(global))
+    } else {
+        !target.same_as(current)
+    }
+}
+
+/// Whether both limits are unset or the same limit.
+fn same_limit<T: LimitValue>(a: Option<T>, b: Option<T>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.same_as(b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// Formats a share limit with `unlimited` and `global` written out; unset limits are global.
+pub fn format_limit<T: PartialEq + fmt::Display + From<i8>>(limit: Option<T>) -> Cow<'static, str> {
+    match limit {
+        Some(x) if x == T::from(-1) => Cow::from(crate::UNLIMITED),
+        Some(x) if x == T::from(-2) => Cow::from(crate::GLOBAL),
+        Some(x) => Cow::from(x.to_string()),
+        None => Cow::from(crate::GLOBAL),
+    }
+}
+
+/// Value of a limit that qBittorrent uses for `unlimited` and `global`.
+fn limit_keyword(s: &str) -> Option<i32> {
+    match s.trim() {
+        "unlimited" => Some(-1),
+        "global" => Some(-2),
+        _ => None,
+    }
+}
+
+/// Deserializes a ratio written as a number, `unlimited` or `global`.
+fn deserialize_ratio_limit<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<qbittorrent::Ratio>, D::Error> {
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(qbittorrent::Ratio),
+        Text(String),
+    }
+
+    match Value::deserialize(d)? {
+        Value::Number(ratio) => Ok(Some(ratio)),
+        Value::Text(text) => match limit_keyword(&text) {
+            Some(ratio) => Ok(Some(ratio.into())),
+            None => Err(Error::invalid_value(
+                Unexpected::Str(&text),
+                &"a ratio, 'unlimited' or 'global'",
+            )),
+        },
+    }
+}
+
+/// Deserializes minutes written as a number, a duration such as `30d`, `unlimited` or `global`.
+fn deserialize_minutes_limit<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<qbittorrent::MaxSeedingTime>, D::Error> {
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(qbittorrent::MaxSeedingTime),
+        Text(String),
+    }
+
+    match Value::deserialize(d)? {
+        Value::Number(minutes) => Ok(Some(minutes)),
+        Value::Text(text) => limit_keyword(&text)
+            .or_else(|| {
+                let minutes = text.parse::<Minutes>().ok()?;
+                qbittorrent::MaxSeedingTime::try_from(minutes.0).ok()
+            })
+            .map(Some)
+            .ok_or_else(|| {
+                Error::invalid_value(
+                    Unexpected::Str(&text),
+                    &"minutes, a duration such as 30d, 'unlimited' or 'global'",
+                )
+            }),
+    }
+}
+
+/// Formats an upload or download speed limit in KiB/s.
+pub fn format_speed_limit(ki_bps: u32) -> Cow<'static, str> {
+    match ki_bps {
+        0 => Cow::from(crate::UNLIMITED),
+        ki_bps => Cow::from(format!("{} KiB/s", ki_bps)),
+    }
+}
+
+impl fmt::Display for RuleLimits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![
+            format!("{} ratio", format_limit(self.ratio)),
+            format!("{} minutes", format_limit(self.minutes)),
+        ];
+        if self.inactive_minutes.is_some() {
+            parts.push(format!(
+                "{} inactive minutes",
+                format_limit(self.inactive_minutes)
+            ));
+        }
+        if let Some(upload_ki_bps) = self.upload_ki_bps {
+            parts.push(format!("{} upload", format_speed_limit(upload_ki_bps)));
+        }
+        if let Some(download_ki_bps) = self.download_ki_bps {
+            parts.push(format!("{} download", format_speed_limit(download_ki_bps)));
+        }
+        let last = parts.pop().unwrap_or_default();
+        write!(f, "{} and {}", parts.join(", "), last)
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Default)]
+pub struct RuleList(Vec<Rule>);
+
+impl RuleList {
+    /// Whether any rule needs the full tracker lists of the torrents.
+    pub fn use_trackers(&self) -> bool {
+        self.0.iter().any(|rule| {
+            rule.conditions.any(&|x| {
+                x.tracker.is_some()
+                    || x.tracker_count.is_some()
+                    || x.when.as_ref().is_some_and(|x| x.uses("trackers"))
+            }) || !rule.actions.add_trackers.is_empty()
+                || !rule.actions.remove_trackers.is_empty()
+        }) || self.use_tracker_statuses()
+    }
+
+    /// Whether any rule needs the current status of the trackers.
+    pub fn use_tracker_statuses(&self) -> bool {
+        self.0.iter().any(Rule::uses_tracker_statuses)
+    }
+
+    /// Whether a rule that is evaluated in the cycle needs the current status of the trackers.
+    /// Other cycles make do with the statuses fetched earlier.
+    pub fn refresh_tracker_statuses(&self, cycle: u64) -> bool {
+        self.0
+            .iter()
+            .any(|rule| rule.uses_tracker_statuses() && rule.is_evaluated(cycle))
+    }
+
+    /// Whether any rule needs the number of files of the torrents.
+    pub fn use_file_counts(&self) -> bool {
+        self.0
+            .iter()
+            .any(|rule| rule.conditions.any(&|x| x.file_count.is_some()))
+    }
+
+    /// Whether any rule needs to know which torrents are private.
+    pub fn use_private_flags(&self) -> bool {
+        self.0.iter().any(|rule| {
+            rule.conditions.any(&|x| {
+                x.is_private.is_some() || x.when.as_ref().is_some_and(|x| x.uses("private"))
+            })
+        })
+    }
+
+    /// The first rule that applies to the torrent. `managed_tag` is ignored by tag conditions.
+    pub fn find(
+        &self,
+        hash: &str,
+        torrent: &qbittorrent::Torrent,
+        managed_tag: Option<&str>,
+    ) -> Option<(usize, &Rule)> {
+        self.find_from(0, hash, torrent, managed_tag)
+    }
+
+    /// The first rule from `start` on that applies to the torrent. A rule pinning the hash wins
+    /// over every rule that only matches through its conditions, wherever it is in the list.
+    fn find_from(
+        &self,
+        start: usize,
+        hash: &str,
+        torrent: &qbittorrent::Torrent,
+        managed_tag: Option<&str>,
+    ) -> Option<(usize, &Rule)> {
+        let mut rules = self.0.iter().enumerate().skip(start);
+        rules
+            .clone()
+            .find(|(_, rule)| rule.pins(hash))
+            .or_else(|| rules.find(|(_, rule)| rule.applies_to(hash, torrent, managed_tag)))
+    }
+
+    /// The matched rule followed by the rules it continues to: while the last rule has
+    /// `continue`, the next rule that applies to the torrent is added.
+    pub fn layers<'a>(
+        &'a self,
+        matched: (usize, &'a Rule),
+        hash: &str,
+        torrent: &qbittorrent::Torrent,
+        managed_tag: Option<&str>,
+    ) -> Vec<(usize, &'a Rule)> {
+        let mut layers = vec![matched];
+        let mut last = matched;
+        while last.1.continues == Some(true) {
+            let Some(next) = self.find_from(last.0 + 1, hash, torrent, managed_tag) else {
+                break;
+            };
+            layers.push(next);
+            last = next;
+        }
+        layers
+    }
+
+    /// Position of the first rule whose matches can change as time passes. Earlier matches of
+    /// torrents that haven't changed remain valid if they are before that rule.
+    pub fn first_using_clock(&self) -> Option<usize> {
+        self.0.iter().position(Rule::uses_clock)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Rule> {
+        self.0.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Rule> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfig {
+    pub address: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Use HTTP/2 without negotiating it first.
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+    /// Seconds after which idle pooled connections are closed.
+    pub pool_idle_timeout: Option<u64>,
+    /// Maximum number of idle connections kept in the pool.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Interval in seconds for TCP keep-alive probes.
+    pub tcp_keepalive: Option<u64>,
+    /// Fixed IP address to connect to instead of resolving the host name of `address`.
+    pub resolve: Option<IpAddr>,
+    /// Session timeout of the WebUI in seconds; sessions are renewed shortly before it.
+    #[serde(default = "default_session_timeout")]
+    pub session_timeout: u64,
+}
+
+fn default_session_timeout() -> u64 {
+    3600
+}
+
+/// SSH tunnel that forwards a local port to the WebUI on a remote machine.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelConfig {
+    pub host: String,
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+    pub user: Option<String>,
+    /// Path to the private key used for authentication.
+    pub key: Option<PathBuf>,
+    /// Host the WebUI listens on, as seen from the SSH server.
+    #[serde(default = "default_remote_host")]
+    pub remote_host: String,
+    pub remote_port: u16,
+}
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+fn default_remote_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Share limits the rule sets on the torrent, or `None` if it already has them.
+    fn target_limits(rule: &Rule, torrent: &qbittorrent::Torrent) -> Option<RuleLimits> {
+        rule.resolved_limits(torrent)
+            .filter(|target| target.differs_from(torrent))
+    }
+
+    mod ignore_list {
+        use super::*;
+        use test_case::test_case;
+
+        fn ignore_list() -> IgnoreList {
+            serde_yaml::from_str(
+                "categories: [Alien]\n\
+                hashes: [0123456789ABCDEF0123456789ABCDEF01234567]\n\
+                names: ['*.S??E??.*']\n\
+                tags: [keep]",
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn test_empty() {
+            let ignore = IgnoreList::default();
+            assert!(ignore.is_empty());
+            assert!(!ignore.matches("abc", &qbittorrent::Torrent::default()));
+        }
+
+        #[test_case("abc", "Alien", "Okarun", "", true ; "category")]
+        #[test_case("0123456789abcdef0123456789abcdef01234567", "Ghost", "Okarun", "", true ; "hash")]
+        #[test_case("abc", "Ghost", "Dandadan.S01E01.mkv", "", true ; "name")]
+        #[test_case("abc", "Ghost", "Okarun", "seen, keep", true ; "tag")]
+        #[test_case("abc", "Ghost", "Dandadan.mkv", "keeper", false ; "none")]
+        fn test_matches(hash: &str, category: &str, name: &str, tags: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                category: category.to_string(),
+                name: name.to_string(),
+                tags: qbittorrent::TagList::from(tags.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(ignore_list().matches(hash, &torrent), expected);
+        }
+    }
+
+    mod config {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case("resetToGlobal", Some(DefaultAction::ResetToGlobal) ; "reset")]
+        #[test_case("leaveAlone", Some(DefaultAction::LeaveAlone) ; "leave alone")]
+        #[test_case("{ratio: 2.0}", Some(DefaultAction::Limits(RuleLimits { ratio: Some(2.0), ..Default::default() })) ; "limits")]
+        #[test_case("forget", None ; "invalid")]
+        fn test_deserialize_default_action(yaml: &str, expected: Option<DefaultAction>) {
+            assert_eq!(serde_yaml::from_str(yaml).ok(), expected);
+        }
+
+        #[test]
+        fn test_deserialize_default_action_error_limits() {
+            let error = serde_yaml::from_str::<DefaultAction>("{ratio: abc}").unwrap_err();
+            assert!(error
+                .to_string()
+                .contains("expected a ratio, 'unlimited' or 'global'"));
+        }
+
+        #[test]
+        fn test_validate_unknown_channel() {
+            let config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                notifications: {channels: {hook: {type: webhook, url: http://localhost}}}\n\
+                rules:\n\
+                - {category: Alien, limits: {}, notify: [hook]}\n\
+                - {category: Ghost, limits: {}, notify: [telegram]}",
+            )
+            .unwrap();
+            match config.validate() {
+                Err(ConfigError::Invalid(error)) => {
+                    assert_eq!(error, "rule #2 notifies unknown channel \"telegram\"")
+                }
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+
+        #[test_case("notify: telegram", Some("rule #1 notifies unknown channel \"telegram\"") ; "unknown channel")]
+        #[test_case("notify: hook", None ; "channel")]
+        #[test_case("notify: https://localhost/hook", None ; "webhook url")]
+        fn test_validate_notify_target(notify: &str, expected: Option<&str>) {
+            let config: Config = serde_yaml::from_str(&format!(
+                "server: {{address: http://localhost}}\n\
+                notifications: {{channels: {{hook: {{type: webhook, url: http://localhost}}}}}}\n\
+                rules:\n\
+                - {{category: Alien, limits: {{}}, {}}}",
+                notify
+            ))
+            .unwrap();
+            match (config.validate(), expected) {
+                (Ok(()), None) => {}
+                (Err(ConfigError::Invalid(error)), Some(expected)) => assert_eq!(error, expected),
+                (result, _) => panic!("Unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn test_validate_managed_tag() {
+            let config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                managedTag: 'jeanne,managed'\n\
+                rules: []",
+            )
+            .unwrap();
+            match config.validate() {
+                Err(ConfigError::Invalid(error)) => {
+                    assert_eq!(error, "managedTag \"jeanne,managed\" is not a valid tag")
+                }
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+
+        #[test_case("alien", true, None ; "valid")]
+        #[test_case("alien,ghost", true, Some("rule #1 has id \"alien,ghost\", which can't be used in its rule tag") ; "comma")]
+        #[test_case(" alien", true, Some("rule #1 has id \" alien\", which can't be used in its rule tag") ; "leading whitespace")]
+        #[test_case("alien ", true, Some("rule #1 has id \"alien \", which can't be used in its rule tag") ; "trailing whitespace")]
+        #[test_case("", true, Some("rule #1 has id \"\", which can't be used in its rule tag") ; "empty")]
+        #[test_case("alien,ghost", false, None ; "without rule tags")]
+        fn test_validate_rule_tag_id(id: &str, rule_tags: bool, expected: Option<&str>) {
+            let config: Config = serde_yaml::from_str(&format!(
+                "server: {{address: http://localhost}}\n\
+                ruleTags: {}\n\
+                rules: [{{id: '{}', category: Alien, limits: {{ratio: 2.0}}}}]",
+                rule_tags, id
+            ))
+            .unwrap();
+            match (config.validate(), expected) {
+                (Ok(()), None) => {}
+                (Err(ConfigError::Invalid(error)), Some(expected)) => assert_eq!(error, expected),
+                (result, _) => panic!("Unexpected result: {:?}", result),
+            }
+        }
+
+        #[test_case("http://localhost:8080", None ; "localhost")]
+        #[test_case("http://127.0.0.1:8080", None ; "loopback")]
+        #[test_case("http://seedbox.example.com:8080", Some("tunnel needs server.address to be on localhost or 127.0.0.1, not http://seedbox.example.com:8080") ; "remote")]
+        fn test_validate_tunnel(address: &str, expected: Option<&str>) {
+            let config: Config = serde_yaml::from_str(&format!(
+                "server: {{address: '{}'}}\n\
+                tunnel: {{host: seedbox.example.com, remotePort: 8080}}\n\
+                rules: []",
+                address
+            ))
+            .unwrap();
+            match (config.validate(), expected) {
+                (Ok(()), None) => {}
+                (Err(ConfigError::Invalid(error)), Some(expected)) => assert_eq!(error, expected),
+                (result, _) => panic!("Unexpected result: {:?}", result),
+            }
+        }
+
+        #[test_case("maxChangesPerCycle", "maxChangesPerCycle is 0, which would skip every cycle with changes" ; "per cycle")]
+        #[test_case("maxChangesPerRun", "maxChangesPerRun is 0, which would never change torrents" ; "per run")]
+        fn test_validate_max_changes(setting: &str, expected: &str) {
+            let config: Config = serde_yaml::from_str(&format!(
+                "server: {{address: http://localhost}}\n{}: 0\nrules: []",
+                setting
+            ))
+            .unwrap();
+            match config.validate() {
+                Err(ConfigError::Invalid(error)) => assert_eq!(error, expected),
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    mod shorthands {
+        use super::*;
+        use test_case::test_case;
+
+        #[test]
+        fn test_expand_categories() {
+            let mut config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                rules: [{category: Alien, limits: {ratio: 20.0}}]\n\
+                categories:\n  \
+                  movies: {ratio: 2.0, minutes: 4320}\n  \
+                  tv: {ratio: 1.0}",
+            )
+            .unwrap();
+            config.expand_shorthands();
+            let rules: Vec<_> = config
+                .rules
+                .iter()
+                .map(|x| {
+                    (
+                        x.conditions.category.as_ref().map(|x| x.to_string()),
+                        x.limits.clone(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                rules,
+                vec![
+                    (
+                        Some("Alien".to_string()),
+                        Some(Limits::Fixed(RuleLimits {
+                            ratio: Some(20.0),
+                            minutes: None,
+                            inactive_minutes: None,
+                            upload_ki_bps: None,
+                            download_ki_bps: None,
+                        }))
+                    ),
+                    (
+                        Some("movies".to_string()),
+                        Some(Limits::Fixed(RuleLimits {
+                            ratio: Some(2.0),
+                            minutes: Some(4320),
+                            inactive_minutes: None,
+                            upload_ki_bps: None,
+                            download_ki_bps: None,
+                        }))
+                    ),
+                    (
+                        Some("tv".to_string()),
+                        Some(Limits::Fixed(RuleLimits {
+                            ratio: Some(1.0),
+                            minutes: None,
+                            inactive_minutes: None,
+                            upload_ki_bps: None,
+                            download_ki_bps: None,
+                        }))
+                    ),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_expand_tags() {
+            let mut config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                categories: {movies: {ratio: 2.0}}\n\
+                tags: {permaseed: {ratio: -1}}",
+            )
+            .unwrap();
+            config.expand_shorthands();
+            let rules: Vec<_> = config.rules.iter().collect();
+            assert_eq!(rules.len(), 2);
+            assert_eq!(
+                rules[0].conditions.tags_any,
+                Some(vec!["permaseed".to_string()])
+            );
+            assert_eq!(
+                rules[1].conditions.category.as_ref().map(|x| x.to_string()),
+                Some("movies".to_string())
+            );
+            let torrent = qbittorrent::Torrent {
+                category: "movies".to_string(),
+                tags: qbittorrent::TagList::from("seen, permaseed".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                config.rules.find("abc", &torrent, None).map(|(i, _)| i),
+                Some(0)
+            );
+        }
+
+        #[test]
+        fn test_expand_rule_defaults() {
+            let mut config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                ruleDefaults: {cooldown: 1h, dryRun: true, continue: true}\n\
+                rules:\n\
+                - {category: Alien, dryRun: false, limits: {ratio: 2.0}}\n\
+                - {category: Ghost, action: delete}\n\
+                tags: {permaseed: {ratio: -1}}\n\
+                default: {limits: {ratio: 1.0}}",
+            )
+            .unwrap();
+            config.expand_shorthands();
+            config.expand_default().unwrap();
+            config.expand_rule_defaults();
+            config.validate().unwrap();
+            let rules = &config.rules.0;
+            assert_eq!(rules.len(), 4);
+            assert!(rules.iter().all(|x| x.cooldown == Some(TimeSpan(3600))));
+            // The rule's own setting takes precedence.
+            assert_eq!(rules[0].dry_run, Some(false));
+            assert_eq!(rules[0].continues, Some(true));
+            // Rules deleting torrents can't continue, but take over the other settings.
+            assert_eq!(rules[1].dry_run, Some(true));
+            assert_eq!(rules[1].continues, None);
+            assert_eq!(rules[1].limits, None);
+            // The shorthand and default rules keep their own limits.
+            assert_eq!(rules[2].dry_run, Some(true));
+            assert_eq!(rules[2].continues, Some(true));
+            assert_eq!(rules[3].name(3), "default");
+            assert_eq!(rules[3].dry_run, Some(true));
+            assert!(matches!(&rules[3].limits, Some(Limits::Fixed(x)) if x.ratio == Some(1.0)));
+        }
+
+        #[test_case("{limits: {ratio: 2.0}}", "limits" ; "limits")]
+        #[test_case("{category: Alien}", "category" ; "conditions")]
+        #[test_case("{action: delete}", "action" ; "action")]
+        #[test_case("{actions: {pause: true}}", "actions" ; "actions")]
+        fn test_rule_defaults_invalid(defaults: &str, field: &str) {
+            let error = serde_yaml::from_str::<Config>(&format!(
+                "server: {{address: http://localhost}}\nruleDefaults: {}",
+                defaults
+            ))
+            .unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .contains(&format!("unknown field `{}`", field)),
+                "{}",
+                error
+            );
+        }
+
+        #[test]
+        fn test_expand_default() {
+            let mut config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                rules: [{category: Alien, limits: {ratio: 20.0}}]\n\
+                categories: {movies: {ratio: 2.0}}\n\
+                default: {limits: {ratio: 1.0}, actions: {addTags: [unmatched]}}",
+            )
+            .unwrap();
+            config.expand_shorthands();
+            config.expand_default().unwrap();
+            let names: Vec<_> = config
+                .rules
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x.name(i).into_owned())
+                .collect();
+            assert_eq!(names, ["rule-1", "rule-2", "default"]);
+            let torrent = qbittorrent::Torrent::default();
+            assert_eq!(
+                config.rules.find("abc", &torrent, None).map(|(i, _)| i),
+                Some(2)
+            );
+        }
+
+        #[test_case("default: {category: Alien, limits: {}}", "the default rule can't have conditions" ; "conditions")]
+        #[test_case("default: {limits: {}}\ndefaultAction: leaveAlone", "default can't be combined with defaultAction" ; "default action")]
+        fn test_expand_default_invalid(yaml: &str, expected: &str) {
+            let mut config: Config =
+                serde_yaml::from_str(&format!("server: {{address: http://localhost}}\n{}", yaml))
+                    .unwrap();
+            match config.expand_default() {
+                Err(ConfigError::Invalid(error)) => assert_eq!(error, expected),
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn test_expand_retire() {
+            let mut config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                rules: [{limits: {ratio: 2.0}, actions: {addTags: [done], retire: retired}}]",
+            )
+            .unwrap();
+            config.expand_shorthands();
+            config.validate().unwrap();
+            let actions = &config.rules.0[0].actions;
+            assert!(actions.pause);
+            assert_eq!(actions.add_tags, ["done", "retired"]);
+            assert_eq!(actions.to_string(), "add tags [done, retired], pause");
+        }
+    }
+
+    mod rule {
+        use super::*;
+        use test_case::test_case;
+
+        fn rule(yaml: &str) -> Rule {
+            serde_yaml::from_str(yaml).unwrap()
+        }
+
+        #[test_case("abc", true ; "pinned")]
+        #[test_case("ABC", true ; "pinned uppercase")]
+        #[test_case("def", false ; "excluded")]
+        #[test_case("ghi", true ; "conditions")]
+        fn test_applies_to_hashes(hash: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                category: "Alien".to_string(),
+                ..Default::default()
+            };
+            let rule = rule("category: Alien\nhashes: [abc]\nexcludeHashes: [def]\nlimits: {}");
+            assert_eq!(rule.applies_to(hash, &torrent, None), expected);
+            assert_eq!(rule.pins(hash), hash.eq_ignore_ascii_case("abc"));
+        }
+
+        #[test_case("abc", vec![2, 3] ; "pinned")]
+        #[test_case("def", vec![3] ; "pinned and excluded earlier")]
+        #[test_case("ghi", vec![0, 1] ; "conditions")]
+        fn test_find_pinned(hash: &str, expected: Vec<usize>) {
+            // The broader rules come first and match the torrent through its category, but the
+            // rules pinning a hash win over them.
+            let config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                rules:\n\
+                - {category: Alien, excludeHashes: [def], continue: true, limits: {ratio: 1.0}}\n\
+                - {category: Alien, excludeHashes: [abc], continue: true, limits: {ratio: 2.0}}\n\
+                - {category: Ghost, hashes: [abc], continue: true, limits: {ratio: 3.0}}\n\
+                - {category: Ghost, hashes: [abc, def], limits: {ratio: 4.0}}",
+            )
+            .unwrap();
+            let torrent = qbittorrent::Torrent {
+                category: "Alien".to_string(),
+                ..Default::default()
+            };
+            let matched = config.rules.find(hash, &torrent, None).unwrap();
+            let layers: Vec<_> = config
+                .rules
+                .layers(matched, hash, &torrent, None)
+                .into_iter()
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(layers, expected);
+        }
+
+        #[test]
+        fn test_applies_to_disabled() {
+            let torrent = qbittorrent::Torrent {
+                category: "Alien".to_string(),
+                ..Default::default()
+            };
+            let rule = rule("category: Alien\nhashes: [abc]\nenabled: false\nlimits: {}");
+            assert!(!rule.is_enabled());
+            assert!(!rule.applies_to("abc", &torrent, None));
+            assert!(!rule.applies_to("def", &torrent, None));
+            assert!(!rule.pins("abc"));
+        }
+
+        #[test_case("pausedUP", 2.0, 0, true ; "paused")]
+        #[test_case("stoppedUP", 2.0, 0, true ; "stopped")]
+        #[test_case("stoppedUP", 0.5, 600, true ; "seeding time")]
+        #[test_case("stoppedUP", 0.5, 0, false ; "stopped by hand")]
+        #[test_case("pausedUP", 1.99, 599, false ; "paused by hand")]
+        #[test_case("stalledUP", 2.0, 600, false ; "stalled")]
+        #[test_case("uploading", 2.0, 600, false ; "uploading")]
+        fn test_matches_limit_reached(state: &str, ratio: f64, minutes: usize, expected: bool) {
+            // Limits of ratio 2 and 600 minutes, with the inactive limit unset.
+            let torrent = qbittorrent::Torrent {
+                state: state.to_string(),
+                ratio,
+                max_ratio: 2.0,
+                seeding_time: minutes * 60,
+                max_seeding_time: 600,
+                max_inactive_seeding_time: -1,
+                ..Default::default()
+            };
+            let reached = rule("limitReached: true\nlimits: {}");
+            let not_reached = rule("limitReached: false\nlimits: {}");
+            assert_eq!(reached.matches(&torrent, None), expected);
+            assert_eq!(not_reached.matches(&torrent, None), !expected);
+        }
+
+        #[test_case("category: tv", "tv", true ; "single")]
+        #[test_case("category: [tv, tv-archive]", "tv-archive", true ; "list")]
+        #[test_case("category: [tv, tv-archive]", "movies", false ; "not in list")]
+        #[test_case("category: tv*", "tv/anime", true ; "wildcard")]
+        #[test_case("category: tv/*", "tv", false ; "wildcard subcategory")]
+        #[test_case("category: [movies, 'tv/*']", "tv/docs", true ; "wildcard in list")]
+        #[test_case("uncategorized: true", "", true ; "uncategorized")]
+        #[test_case("uncategorized: true", "tv", false ; "categorized")]
+        #[test_case("uncategorized: false", "tv", true ; "expected category")]
+        #[test_case("uncategorized: false", "", false ; "expected category missing")]
+        fn test_matches_category(condition: &str, category: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                category: category.to_string(),
+                ..Default::default()
+            };
+            let rule = rule(&format!("{}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(true, Some(true), true ; "private")]
+        #[test_case(true, Some(false), false ; "public")]
+        #[test_case(false, Some(false), true ; "expected public")]
+        #[test_case(false, None, false ; "unknown")]
+        fn test_matches_private(is_private: bool, private: Option<bool>, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                private,
+                ..Default::default()
+            };
+            let rule = rule(&format!("isPrivate: {}\nlimits: {{}}", is_private));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(true, 1, true ; "cross-seeded")]
+        #[test_case(true, 0, false ; "single")]
+        #[test_case(false, 0, true ; "expected single")]
+        fn test_matches_cross_seed(is_cross_seed: bool, cross_seeds: usize, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                cross_seeds,
+                ..Default::default()
+            };
+            let rule = rule(&format!("isCrossSeed: {}\nlimits: {{}}", is_cross_seed));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">0", 3, true ; "active")]
+        #[test_case(">0", 0, false ; "dead")]
+        fn test_matches_leechers(condition: &str, leechers: usize, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                num_incomplete: leechers,
+                ..Default::default()
+            };
+            let rule = rule(&format!("leechers: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("<1", 2, 4, true ; "under-served")]
+        #[test_case("<1", 4, 4, false ; "balanced")]
+        #[test_case(">10", 50, 2, true ; "over-served")]
+        #[test_case(">10", 5, 0, true ; "no leechers")]
+        fn test_matches_swarm_ratio(
+            condition: &str,
+            seeders: usize,
+            leechers: usize,
+            expected: bool,
+        ) {
+            let torrent = qbittorrent::Torrent {
+                num_complete: seeders,
+                num_incomplete: leechers,
+                ..Default::default()
+            };
+            let rule = rule(&format!("swarmRatio: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">=10GiB", 10_737_418_240, true ; "reached")]
+        #[test_case(">=10GiB", 10_000_000_000, false ; "not reached")]
+        fn test_matches_uploaded(condition: &str, uploaded: u64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                uploaded,
+                ..Default::default()
+            };
+            let rule = rule(&format!("uploaded: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">=1.0", 1.5, true ; "available")]
+        #[test_case(">=1.0", 0.8, false ; "only full seed")]
+        fn test_matches_availability(condition: &str, availability: f64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                availability,
+                ..Default::default()
+            };
+            let rule = rule(&format!("availability: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("<1MB", 200_000, true ; "idle")]
+        #[test_case("<1MB", 5_000_000, false ; "bursting")]
+        fn test_matches_up_speed(condition: &str, upspeed: u64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                upspeed,
+                ..Default::default()
+            };
+            let rule = rule(&format!("upSpeed: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(true, true ; "automatic")]
+        #[test_case(false, false ; "manual")]
+        fn test_matches_auto_tmm(auto_tmm: bool, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                auto_tmm,
+                ..Default::default()
+            };
+            let rule = rule("autoTmm: true\nlimits: {}");
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">24h", 3600, false ; "recently changed")]
+        #[test_case(">24h", 2 * 86_400, true ; "changed long ago")]
+        #[test_case("<1h", 600, true ; "changed recently")]
+        fn test_matches_limit_changed_age(condition: &str, seconds: i64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                limits_changed_on: crate::clock::now() - seconds,
+                ..Default::default()
+            };
+            let rule = rule(&format!("limitChangedAge: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">60", 7200, true ; "inactive")]
+        #[test_case(">60", 600, false ; "active")]
+        #[test_case(">1h", 7200, true ; "unit")]
+        fn test_matches_inactive_seeding_time(condition: &str, seconds: i64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                last_activity: crate::clock::now() - seconds,
+                ..Default::default()
+            };
+            let rule = rule(&format!(
+                "inactiveSeedingTime: \"{}\"\nlimits: {{}}",
+                condition
+            ));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(-2, true ; "global")]
+        #[test_case(720, true ; "different")]
+        #[test_case(1440, false ; "same")]
+        fn test_target_limits_inactive(current: qbittorrent::MaxSeedingTime, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                max_inactive_seeding_time: current,
+                ..Default::default()
+            };
+            let rule = rule("limits: {inactiveMinutes: 1440}");
+            assert_eq!(target_limits(&rule, &torrent).is_some(), expected);
+        }
+
+        #[test_case("<1", 0, true ; "injected")]
+        #[test_case("<1", 4096, false ; "downloaded")]
+        fn test_matches_downloaded(condition: &str, downloaded: u64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                downloaded,
+                ..Default::default()
+            };
+            let rule = rule(&format!("downloaded: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">1h", 600, false ; "about to finish")]
+        #[test_case(">1h", 86_400, true ; "far from finishing")]
+        #[test_case(">1h", 8_640_000, true ; "unknown")]
+        fn test_matches_eta(condition: &str, eta: usize, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                eta,
+                ..Default::default()
+            };
+            let rule = rule(&format!("eta: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">1", Some(24), true ; "season pack")]
+        #[test_case(">1", Some(1), false ; "single file")]
+        #[test_case("<=1", None, false ; "unknown")]
+        fn test_matches_file_count(condition: &str, file_count: Option<usize>, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                file_count,
+                ..Default::default()
+            };
+            let rule = rule(&format!("fileCount: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(true, true, true ; "forced")]
+        #[test_case(true, false, false ; "queued")]
+        #[test_case(false, true, false ; "skip forced")]
+        fn test_matches_force_started(condition: bool, force_start: bool, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                force_start,
+                ..Default::default()
+            };
+            let rule = rule(&format!("forceStarted: {}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("progress: '>=1.0'", 1.0, true ; "progress complete")]
+        #[test_case("progress: '>=1.0'", 0.5, false ; "progress incomplete")]
+        #[test_case("completed: true", 1.0, true ; "completed")]
+        #[test_case("completed: true", 0.99, false ; "not completed")]
+        #[test_case("completed: false", 0.5, true ; "expected incomplete")]
+        fn test_matches_progress(condition: &str, progress: f64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                progress,
+                ..Default::default()
+            };
+            let rule = rule(&format!("{}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("'>=2w'", 20160, true ; "weeks")]
+        #[test_case("'>3d'", 4320, false ; "days")]
+        #[test_case("'>36h'", 2161, true ; "hours")]
+        #[test_case("'<=10080'", 10080, true ; "bare minutes")]
+        #[test_case("'1d..7d'", 2880, true ; "range")]
+        #[test_case("'1d..7d'", 20160, false ; "outside range")]
+        #[test_case("[1d, 7d]", 1440, true ; "range list")]
+        fn test_matches_seeding_time(condition: &str, minutes: usize, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                seeding_time: minutes * 60,
+                ..Default::default()
+            };
+            let rule = rule(&format!("seedingTime: {}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
 
-impl<T: PartialOrd> Comparison<T> {
-    fn compare(&self, value: T) -> bool {
-        match self.operator {
-            ComparisonOperator::GreaterThan => value > self.value,
-            ComparisonOperator::GreaterThanOrEqual => value >= self.value,
-            ComparisonOperator::LessThan => value < self.value,
-            ComparisonOperator::LessThanOrEqual => value <= self.value,
+        #[test_case(">=2.0", 2.0, true ; "reached")]
+        #[test_case(">=2.0", 1.99, false ; "not reached")]
+        #[test_case("<1", 0.5, true ; "integer")]
+        fn test_matches_ratio(condition: &str, ratio: f64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                ratio,
+                ..Default::default()
+            };
+            let rule = rule(&format!("ratio: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">50GB", 60_000_000_000, true ; "larger")]
+        #[test_case(">50GB", 50_000_000_000, false ; "equal")]
+        #[test_case("<=1.5GiB", 1_610_612_736, true ; "binary unit")]
+        #[test_case("<100", 99, true ; "bytes")]
+        fn test_matches_size(condition: &str, size: u64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                size,
+                ..Default::default()
+            };
+            let rule = rule(&format!("size: \"{}\"\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("[stalledUP]", "stalledUP", true ; "exact")]
+        #[test_case("[stalledUP]", "uploading", false ; "other state")]
+        #[test_case("[seeding]", "uploading", true ; "seeding")]
+        #[test_case("[seeding]", "pausedUP", false ; "seeding paused")]
+        #[test_case("[paused, errored]", "stoppedUP", true ; "paused")]
+        #[test_case("[paused, errored]", "missingFiles", true ; "errored")]
+        fn test_matches_state(condition: &str, state: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                state: state.to_string(),
+                ..Default::default()
+            };
+            let rule = rule(&format!("state: {}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(true, true, true ; "super-seeding")]
+        #[test_case(true, false, false ; "normal")]
+        #[test_case(false, false, true ; "expected normal")]
+        #[test_case(false, true, false ; "skip super-seeding")]
+        fn test_matches_super_seeding(condition: bool, super_seeding: bool, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                super_seeding,
+                ..Default::default()
+            };
+            let rule = rule(&format!("superSeeding: {}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("tagsAny: [anime, keep]", "seen, keep", true ; "any")]
+        #[test_case("tagsAny: [anime, keep]", "seen", false ; "any missing")]
+        #[test_case("tagsAll: [anime, keep]", "anime, seen, keep", true ; "all")]
+        #[test_case("tagsAll: [anime, keep]", "anime, seen", false ; "all missing one")]
+        #[test_case("tagsNone: [anime, keep]", "seen", true ; "none")]
+        #[test_case("tagsNone: [anime, keep]", "seen, keep", false ; "none present")]
+        #[test_case("tagsNone: [anime]", "", true ; "none untagged")]
+        #[test_case("untagged: true", "", true ; "untagged")]
+        #[test_case("untagged: true", "seen", false ; "tagged")]
+        #[test_case("untagged: true", "jeanne:rule-1", true ; "rule tag")]
+        #[test_case("untagged: false", "seen", true ; "expected tags")]
+        fn test_matches_tags(condition: &str, tags: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                tags: qbittorrent::TagList::from(tags.to_string()),
+                ..Default::default()
+            };
+            let rule = rule(&format!("{}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("tags: [seen]", true ; "exact")]
+        #[test_case("tagsAny: [jeanne]", false ; "any")]
+        #[test_case("tagsAll: [seen, jeanne]", false ; "all")]
+        #[test_case("tagsNone: [jeanne]", true ; "none")]
+        #[test_case("untagged: false", true ; "tagged")]
+        fn test_matches_managed_tag(condition: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                tags: qbittorrent::TagList::from("jeanne, seen".to_string()),
+                ..Default::default()
+            };
+            let rule = rule(&format!("{}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, Some("jeanne")), expected);
+        }
+
+        #[test_case("not: {category: Anime}", "Movies", "", true ; "other category")]
+        #[test_case("not: {category: Anime}", "Anime", "", false ; "negated category")]
+        #[test_case(
+            "category: Anime\nnot: {tagsAny: [keep]}",
+            "Anime",
+            "seen",
+            true ;
+            "combined"
+        )]
+        #[test_case(
+            "category: Anime\nnot: {tagsAny: [keep]}",
+            "Anime",
+            "keep",
+            false ;
+            "combined negated"
+        )]
+        #[test_case(
+            "not: {category: Anime, tagsAny: [keep]}",
+            "Anime",
+            "seen",
+            true ;
+            "partial match"
+        )]
+        #[test_case("not: {not: {category: Anime}}", "Anime", "", true ; "double negation")]
+        #[test_case(
+            "anyOf: [{category: Anime}, {tagsAny: [keep]}]",
+            "Movies",
+            "keep",
+            true ;
+            "any of"
+        )]
+        #[test_case(
+            "anyOf: [{category: Anime}, {tagsAny: [keep]}]",
+            "Movies",
+            "seen",
+            false ;
+            "any of none"
+        )]
+        #[test_case(
+            "allOf: [{anyOf: [{category: Anime}, {category: TV}]}, {tagsAny: [keep]}]",
+            "TV",
+            "keep",
+            true ;
+            "all of nested"
+        )]
+        #[test_case(
+            "allOf: [{anyOf: [{category: Anime}, {category: TV}]}, {tagsAny: [keep]}]",
+            "TV",
+            "seen",
+            false ;
+            "all of nested missing"
+        )]
+        #[test_case(
+            "anyOf: [{category: Anime}, {not: {tagsAny: [keep]}}]",
+            "Movies",
+            "seen",
+            true ;
+            "any of not"
+        )]
+        #[test_case(
+            "when: \"category == 'Anime' && !('keep' in tags)\"",
+            "Anime",
+            "seen",
+            true ;
+            "when"
+        )]
+        #[test_case(
+            "anyOf: [{when: \"'keep' in tags\"}, {category: Movies}]",
+            "Anime",
+            "keep",
+            true ;
+            "when nested"
+        )]
+        fn test_matches_groups(condition: &str, category: &str, tags: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                category: category.to_string(),
+                tags: qbittorrent::TagList::from(tags.to_string()),
+                ..Default::default()
+            };
+            let rule = rule(&format!("{}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("{dlspeed: '<1000'}", true ; "comparison")]
+        #[test_case("{dlspeed: '>=1000'}", false ; "comparison false")]
+        #[test_case("{f_l_piece_prio: true}", true ; "bool")]
+        #[test_case("{f_l_piece_prio: 'true'}", true ; "bool as string")]
+        #[test_case("{f_l_piece_prio: false}", false ; "bool mismatch")]
+        #[test_case("{download_path: /data/incomplete}", true ; "string")]
+        #[test_case("{ratio: '>1'}", true ; "known field")]
+        #[test_case("{seen_complete: 0}", false ; "missing field")]
+        fn test_matches_fields(fields: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                ratio: 1.5,
+                other_fields: serde_json::from_str(
+                    r#"{"dlspeed": 512, "f_l_piece_prio": true, "download_path": "/data/incomplete"}"#,
+                )
+                .unwrap(),
+                ..Default::default()
+            };
+            let rule = rule(&format!("fields: {}\nlimits: {{}}", fields));
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("Show.S01E02.1080p.mkv", true ; "episode")]
+        #[test_case("Show.S1E2.1080p.mkv", false ; "short episode")]
+        #[test_case("Movie.2024.1080p.mkv", false ; "movie")]
+        fn test_matches_name(name: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            let rule = rule("name: '*.S??E??.*'\nlimits: {}");
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(&["tracker.example.org"], "", true ; "tracker list")]
+        #[test_case(&["announce.tracker.example.org"], "", true ; "subdomain")]
+        #[test_case(&[], "https://tracker.example.org/announce", true ; "current tracker")]
+        #[test_case(&["example.org", "faketracker.example.org"], "", false ; "other hosts")]
+        #[test_case(&[], "", false ; "no trackers")]
+        fn test_matches_tracker(trackers: &[&str], current: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                tracker: current.to_string(),
+                trackers: trackers
+                    .iter()
+                    .map(|x| qbittorrent::Tracker {
+                        host: x.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            let rule = rule("tracker: tracker.example.org\nlimits: {}");
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case("trackerStatus: notWorking", true ; "status")]
+        #[test_case("trackerStatus: updating", false ; "other status")]
+        #[test_case("trackerMessage: '*unregistered*'", true ; "message")]
+        #[test_case("trackerMessage: unregistered", true ; "message word")]
+        #[test_case("trackerMessage: 'torrent unreg*'", true ; "message glob")]
+        #[test_case("trackerMessage: banned", false ; "other message")]
+        #[test_case("trackerCount: '>1'", true ; "count")]
+        #[test_case("trackerCount: '1..1'", false ; "single tracker")]
+        fn test_matches_tracker_status(condition: &str, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                trackers: vec![
+                    qbittorrent::Tracker {
+                        host: "tracker.example.org".to_string(),
+                        url: "https://tracker.example.org/announce".to_string(),
+                        status: qbittorrent::TrackerStatus::NotWorking,
+                        message: "Torrent Unregistered".to_string(),
+                    },
+                    qbittorrent::Tracker {
+                        host: "backup.example.org".to_string(),
+                        url: "https://backup.example.org/announce".to_string(),
+                        status: qbittorrent::TrackerStatus::Working,
+                        message: String::new(),
+                    },
+                ],
+                ..Default::default()
+            };
+            let rule = rule(&format!("{}\nlimits: {{}}", condition));
+            assert_eq!(rule.matches(&torrent, None), expected);
         }
     }
-}
 
-#[derive(Debug, PartialEq)]
-enum ComparisonOperator {
-    GreaterThan,
-    GreaterThanOrEqual,
-    LessThan,
-    LessThanOrEqual,
-}
+    mod direction {
+        use super::*;
+        use test_case::test_case;
 
-impl fmt::Display for ComparisonOperator {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let symbol = match self {
-            ComparisonOperator::GreaterThan => ">",
-            ComparisonOperator::GreaterThanOrEqual => ">=",
-            ComparisonOperator::LessThan => "<",
-            ComparisonOperator::LessThanOrEqual => "<=",
-        };
-        write!(f, "{}", symbol)
+        #[test_case(2.0, Some(1.0), Some(1.0) ; "lower")]
+        #[test_case(1.0, Some(2.0), Some(1.0) ; "higher")]
+        #[test_case(-1.0, Some(5.0), Some(5.0) ; "from unlimited")]
+        #[test_case(5.0, Some(-1.0), Some(5.0) ; "to unlimited")]
+        #[test_case(-2.0, Some(5.0), Some(5.0) ; "from global")]
+        #[test_case(5.0, None, Some(5.0) ; "unset")]
+        fn test_guard_tighten(current: f64, target: Option<f64>, expected: Option<f64>) {
+            assert_eq!(
+                Direction::Tighten.guard(current, None, target, -1.0),
+                expected
+            );
+        }
+
+        #[test_case(2.0, Some(1.0), Some(2.0) ; "lower")]
+        #[test_case(1.0, Some(2.0), Some(2.0) ; "higher")]
+        #[test_case(-1.0, Some(5.0), Some(-1.0) ; "from unlimited")]
+        #[test_case(5.0, Some(-1.0), Some(-1.0) ; "to unlimited")]
+        #[test_case(5.0, None, Some(5.0) ; "unset")]
+        fn test_guard_loosen(current: f64, target: Option<f64>, expected: Option<f64>) {
+            assert_eq!(
+                Direction::Loosen.guard(current, None, target, -1.0),
+                expected
+            );
+        }
+
+        #[test_case(Some(-2.0), None, Some(-2.0) ; "unset keeps global")]
+        #[test_case(Some(5.0), None, Some(5.0) ; "unset keeps own")]
+        #[test_case(Some(-2.0), Some(9.0), Some(-2.0) ; "looser keeps global")]
+        #[test_case(Some(-2.0), Some(1.0), Some(1.0) ; "tighter")]
+        fn test_guard_own(own: Option<f64>, target: Option<f64>, expected: Option<f64>) {
+            // The global limit of 5 is in effect unless the torrent has its own limit.
+            assert_eq!(Direction::Tighten.guard(5.0, own, target, -1.0), expected);
+        }
+
+        #[test]
+        fn test_resolved_limits_keeps_global() {
+            // Only the ratio is set, so the torrent keeps following the global seeding time.
+            let torrent = qbittorrent::Torrent {
+                max_ratio: 3.0,
+                ratio_limit: Some(-2.0),
+                max_seeding_time: 600,
+                seeding_time_limit: Some(-2),
+                ..Default::default()
+            };
+            let rule: Rule =
+                serde_yaml::from_str("onlyTighten: true\nlimits: {ratio: 2.0}").unwrap();
+            let limits = rule.resolved_limits(&torrent).unwrap();
+            assert_eq!(limits.ratio, Some(2.0));
+            assert_eq!(limits.minutes, Some(-2));
+        }
+
+        #[test_case(-1, Some(100) ; "from unlimited")]
+        #[test_case(50 * 1024, None ; "looser")]
+        #[test_case(500 * 1024, Some(100) ; "tighter")]
+        fn test_target_upload_limit(up_limit: i64, expected: Option<u32>) {
+            let torrent = qbittorrent::Torrent {
+                up_limit,
+                ..Default::default()
+            };
+            let rule: Rule =
+                serde_yaml::from_str("onlyTighten: true\nlimits: {uploadKiBps: 100}").unwrap();
+            assert_eq!(
+                target_limits(&rule, &torrent).and_then(|x| x.upload_ki_bps),
+                expected
+            );
+        }
+
+        #[test]
+        fn test_target_limits() {
+            let torrent = qbittorrent::Torrent {
+                max_ratio: 3.0,
+                max_seeding_time: 100,
+                ..Default::default()
+            };
+            let rule: Rule =
+                serde_yaml::from_str("onlyTighten: true\nlimits: {ratio: 2.0, minutes: 200}")
+                    .unwrap();
+            let expected = RuleLimits {
+                ratio: Some(2.0),
+                minutes: Some(100),
+                inactive_minutes: None,
+                upload_ki_bps: None,
+                download_ki_bps: None,
+            };
+            assert_eq!(target_limits(&rule, &torrent), Some(expected));
+        }
+
+        #[test_case("global", Some(-2.0), false ; "already global")]
+        #[test_case("global", Some(3.0), true ; "own limit")]
+        #[test_case("global", None, false ; "unknown own limit")]
+        #[test_case("unlimited", Some(-2.0), true ; "global to unlimited")]
+        #[test_case("3", Some(-2.0), false ; "global in effect")]
+        fn test_target_limits_global(ratio: &str, ratio_limit: Option<f64>, expected: bool) {
+            // The global ratio limit of 3 is in effect unless the torrent has its own limit.
+            let torrent = qbittorrent::Torrent {
+                max_ratio: 3.0,
+                ratio_limit,
+                ..Default::default()
+            };
+            let rule: Rule =
+                serde_yaml::from_str(&format!("limits: {{ratio: {}}}", ratio)).unwrap();
+            assert_eq!(target_limits(&rule, &torrent).is_some(), expected);
+        }
     }
-}
 
-impl<'de, T: FromStr> serde::Deserialize<'de> for Comparison<T> {
-    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
-        use serde::de::Error;
-        let s = String::deserialize(d)?;
-        let Some(pos) = s.find(|c| c != '>' && c != '<' && c != '=') else {
-            return Err(Error::invalid_value(
-                Unexpected::Str(&s),
-                &"a number prefixed with '>', '>=', '<' or '<='",
-            ));
-        };
-        let (prefix, value) = s.split_at(pos);
-        let operator = match prefix {
-            "<" => ComparisonOperator::LessThan,
-            "<=" => ComparisonOperator::LessThanOrEqual,
-            ">" => ComparisonOperator::GreaterThan,
-            ">=" => ComparisonOperator::GreaterThanOrEqual,
-            prefix => {
-                return Err(Error::invalid_value(
-                    Unexpected::Other(&format!("prefix \"{}\"", &prefix)),
-                    &"prefix '>', '>=', '<' or '<='",
-                ))
-            }
-        };
-        let value = match value.parse::<T>() {
-            Ok(value) => value,
-            Err(_) => {
-                return Err(Error::invalid_value(
-                    Unexpected::Str(value),
-                    &"a suitable number",
-                ));
+    mod tiers {
+        use super::*;
+        use test_case::test_case;
+
+        fn rule() -> Rule {
+            serde_yaml::from_str(
+                "tiers:\n\
+                - {after: 60, limits: {ratio: -1}}\n\
+                - {after: 7d, limits: {ratio: 2.0}}\n\
+                - {after: 30d, limits: {ratio: 0, minutes: 0}}",
+            )
+            .unwrap()
+        }
+
+        #[test_case(0, None ; "before first")]
+        #[test_case(60, Some(-1.0) ; "first")]
+        #[test_case(10079, Some(-1.0) ; "before second")]
+        #[test_case(10080, Some(2.0) ; "second")]
+        #[test_case(100000, Some(0.0) ; "last")]
+        fn test_limits_for(minutes: usize, expected: Option<f64>) {
+            let torrent = qbittorrent::Torrent {
+                seeding_time: minutes * 60,
+                ..Default::default()
+            };
+            let rule = rule();
+            assert_eq!(rule.matches(&torrent, None), expected.is_some());
+            assert_eq!(rule.limits_for(&torrent).and_then(|x| x.ratio), expected);
+        }
+
+        #[test]
+        fn test_error_after() {
+            let error =
+                serde_yaml::from_str::<Rule>("tiers: [{after: 7x, limits: {}}]").unwrap_err();
+            assert!(error
+                .to_string()
+                .contains("expected minutes or a duration such as 30d"));
+        }
+
+        #[test_case("limits: {}\ntiers: [{after: 0, limits: {}}]", "rule #1 has both limits and tiers" ; "both")]
+        #[test_case("category: Alien", "rule #1 has no limits, tiers or actions" ; "neither")]
+        #[test_case("action: delete\nlimits: {}", "rule #1 deletes torrents but also has limits or actions" ; "delete with limits")]
+        #[test_case("actions: {addTags: [a, b], removeTags: [b]}", "rule #1 both adds and removes tag \"b\"" ; "same tag")]
+        #[test_case("action: delete\nactions: {setCategory: old}", "rule #1 deletes torrents but also has limits or actions" ; "delete with actions")]
+        #[test_case("tiers: [{after: 10, limits: {}}, {after: 10, limits: {}}]", "rule #1 has tiers that are not in ascending order" ; "order")]
+        #[test_case("actions: {exec: /usr/local/bin/hook}", "rule #1 only has exec, which needs limits or other actions to run" ; "only exec")]
+        #[test_case("actions: {addTags: [done], exec: []}", "rule #1 has an empty exec command" ; "empty exec")]
+        #[test_case("action: delete\ncontinue: true", "rule #1 deletes torrents but also has continue" ; "delete with continue")]
+        #[test_case("actions: {retire: retired}", "rule #1 retires torrents without setting limits" ; "retire without limits")]
+        #[test_case("limits: {}\nactions: {retire: 'a,b'}", "rule #1 retires torrents with tag \"a,b\", which is not a valid tag" ; "retire tag")]
+        #[test_case("limits: {}\ninterval: 0", "rule #1 has interval 0, which would never evaluate it" ; "interval")]
+        #[test_case("limits: {}\nmaxPerRun: 0", "rule #1 has maxPerRun 0, which would never change torrents" ; "max per run")]
+        fn test_validate(rule: &str, expected: &str) {
+            let mut config: Config =
+                serde_yaml::from_str("server: {address: http://localhost}\nrules: []").unwrap();
+            config.rules = RuleList(vec![serde_yaml::from_str(rule).unwrap()]);
+            match config.validate() {
+                Err(ConfigError::Invalid(error)) => assert_eq!(error, expected),
+                result => panic!("Unexpected result: {:?}", result),
             }
-        };
-        Ok(Self { operator, value })
+        }
+
+        #[test_case(false, true ; "forced dry run")]
+        #[test_case(true, false ; "allowed")]
+        fn test_apply_defaults_destructive(allow_destructive: bool, dry_run: bool) {
+            let mut config: Config = serde_yaml::from_str(&format!(
+                "server: {{address: http://localhost}}\n\
+                allowDestructive: {}\n\
+                rules: [{{category: Alien, action: deleteWithData}}]",
+                allow_destructive
+            ))
+            .unwrap();
+            config.validate().unwrap();
+            config.apply_defaults();
+            assert_eq!(config.rules.0[0].dry_run == Some(true), dry_run);
+        }
+
+        #[test_case("{action: delete}", true ; "delete")]
+        #[test_case("{actions: {setLocation: /archive}}", true ; "set location")]
+        #[test_case("{actions: {autoTmm: true}}", true ; "enable auto tmm")]
+        #[test_case("{actions: {autoTmm: false}}", false ; "disable auto tmm")]
+        #[test_case("{actions: {addTags: [a]}}", false ; "add tags")]
+        #[test_case("{limits: {ratio: 2.0}, actions: {pause: true}}", true ; "pause")]
+        fn test_is_destructive(rule: &str, expected: bool) {
+            let rule: Rule = serde_yaml::from_str(rule).unwrap();
+            assert_eq!(rule.is_destructive(), expected);
+        }
     }
-}
 
-impl Config {
-    pub fn load(path: &Path) -> Result<Self, ConfigError> {
-        let yaml = Self::load_file(path).map_err(ConfigError::Io)?;
-        let config: Self = serde_yaml::from_str(&yaml).map_err(ConfigError::Deserialization)?;
-        Ok(config)
+    mod age {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case(">30d", 31, true ; "older")]
+        #[test_case(">30d", 29, false ; "newer")]
+        #[test_case("<1w", 3, true ; "weeks")]
+        fn test_matches(condition: &str, days: i64, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                added_on: crate::clock::now() - days * 24 * 60 * 60,
+                ..Default::default()
+            };
+            let rule: Rule =
+                serde_yaml::from_str(&format!("age: '{}'\nlimits: {{}}", condition)).unwrap();
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test_case(">30d", Some(31), true ; "completed long ago")]
+        #[test_case(">30d", Some(29), false ; "completed recently")]
+        #[test_case("<1d", None, false ; "unfinished")]
+        fn test_matches_completed(condition: &str, days: Option<i64>, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                completion_on: days.map_or(-1, |x| crate::clock::now() - x * 24 * 60 * 60),
+                ..Default::default()
+            };
+            let rule: Rule =
+                serde_yaml::from_str(&format!("completedAge: '{}'\nlimits: {{}}", condition))
+                    .unwrap();
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
     }
 
-    fn load_file(path: &Path) -> Result<String, io::Error> {
-        let mut file = File::open(path)?;
-        let mut file_content = String::new();
-        file.read_to_string(&mut file_content)?;
-        Ok(file_content)
+    mod seeding_time_source {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case(None, false ; "default")]
+        #[test_case(Some(SeedingTimeSource::SeedingTime), false ; "seeding time")]
+        #[test_case(Some(SeedingTimeSource::Completion), true ; "completion")]
+        fn test_matches(source: Option<SeedingTimeSource>, expected: bool) {
+            let torrent = qbittorrent::Torrent {
+                completion_on: crate::clock::now() - 7200,
+                seeding_time: 1800,
+                ..Default::default()
+            };
+            let mut rule: Rule = serde_yaml::from_str("seedingTime: '>60'\nlimits: {}").unwrap();
+            rule.seeding_time_source = source;
+            assert_eq!(rule.matches(&torrent, None), expected);
+        }
+
+        #[test]
+        fn test_apply_defaults() {
+            let mut config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                seedingTimeSource: completion\n\
+                rules:\n\
+                - {limits: {}}\n\
+                - {seedingTimeSource: seedingTime, limits: {}}",
+            )
+            .unwrap();
+            config.apply_defaults();
+            let sources: Vec<_> = config.rules.iter().map(|x| x.seeding_time_source).collect();
+            assert_eq!(
+                sources,
+                vec![
+                    Some(SeedingTimeSource::Completion),
+                    Some(SeedingTimeSource::SeedingTime)
+                ]
+            );
+        }
     }
-}
 
-#[derive(Deserialize, PartialEq, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct Rule {
-    category: Option<String>,
-    seeding_time: Option<Comparison<usize>>,
-    tags: Option<qbittorrent::TagList>,
-    pub limits: RuleLimits,
-}
+    mod tracker_presets {
+        use super::*;
+        use test_case::test_case;
 
-impl Rule {
-    fn matches(&self, torrent: &qbittorrent::Torrent) -> bool {
-        if let Some(category) = &self.category {
-            if category != &torrent.category {
-                return false;
-            }
+        fn presets() -> TrackerPresets {
+            serde_yaml::from_str(
+                "example.org: {ratio: 1.0}\n\
+                tracker.example.org: {ratio: 2.0, minutes: 4320}",
+            )
+            .unwrap()
         }
-        if let Some(seeding_time) = &self.seeding_time {
-            if !seeding_time.compare(torrent.seeding_time / 60) {
-                return false;
-            }
+
+        #[test_case("example.org", Some(1.0) ; "exact")]
+        #[test_case("announce.example.org", Some(1.0) ; "subdomain")]
+        #[test_case("tracker.example.org", Some(2.0) ; "most specific")]
+        #[test_case("badexample.org", None ; "suffix")]
+        #[test_case("example.com", None ; "unknown")]
+        fn test_get(host: &str, expected: Option<f64>) {
+            assert_eq!(presets().get(host).and_then(|x| x.ratio), expected);
         }
-        if let Some(tags) = &self.tags {
-            if tags != &torrent.tags {
-                return false;
+
+        #[test]
+        fn test_rule() {
+            let mut rule: Rule = serde_yaml::from_str("limits: trackerPolicy").unwrap();
+            if let Some(Limits::TrackerPolicy(rule_presets)) = &mut rule.limits {
+                *rule_presets = presets();
             }
+            let torrent = |tracker: &str| qbittorrent::Torrent {
+                tracker: tracker.to_string(),
+                max_ratio: -1.0,
+                max_seeding_time: -1,
+                ..Default::default()
+            };
+            let known = torrent("https://tracker.example.org:443/announce?passkey=abc");
+            assert!(rule.matches(&known, None));
+            assert_eq!(
+                target_limits(&rule, &known),
+                Some(RuleLimits {
+                    ratio: Some(2.0),
+                    minutes: Some(4320),
+                    inactive_minutes: None,
+                    upload_ki_bps: None,
+                    download_ki_bps: None,
+                })
+            );
+            assert!(!rule.matches(&torrent("udp://tracker.example.com:1337/announce"), None));
+            assert!(!rule.matches(&torrent(""), None));
         }
-        true
-    }
 
-    pub fn needs_update(&self, torrent: &qbittorrent::Torrent) -> bool {
-        if let Some(ratio) = &self.limits.ratio {
-            if &torrent.max_ratio != ratio {
-                log::debug!("Torrent {} has incorrect ratio", torrent.name);
-                return true;
-            }
+        #[test]
+        fn test_error_keyword() {
+            let error = serde_yaml::from_str::<Rule>("limits: trackerPolcy").unwrap_err();
+            assert!(error
+                .to_string()
+                .contains("expected share limits or trackerPolicy"));
         }
-        if let Some(minutes) = &self.limits.minutes {
-            if &torrent.max_seeding_time != minutes {
-                log::debug!("Torrent {} has incorrect max seeding time", torrent.name);
-                return true;
-            }
+
+        #[test_case("limits: {ratio: abc}", "expected a ratio, 'unlimited' or 'global'" ; "ratio")]
+        #[test_case("limits: {minutes: 5x}", "expected minutes, a duration such as 30d" ; "minutes")]
+        fn test_error_limits(rule: &str, expected: &str) {
+            let error = serde_yaml::from_str::<Rule>(rule).unwrap_err().to_string();
+            assert!(error.contains(expected), "{}", error);
+        }
+
+        #[test]
+        fn test_validate_without_presets() {
+            let config: Config = serde_yaml::from_str(
+                "server: {address: http://localhost}\n\
+                rules: [{category: Alien, limits: trackerPolicy}]",
+            )
+            .unwrap();
+            assert!(matches!(
+                config.validate(),
+                Err(ConfigError::Invalid(message))
+                    if message == "rule #1 uses trackerPolicy without trackerPresets"
+            ));
         }
-        false
     }
-}
 
-impl fmt::Display for Rule {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut conditions = Vec::<String>::new();
-        if let Some(category) = &self.category {
-            conditions.push(format!("category = {}", category));
+    mod pattern {
+        use super::*;
+        use serde_test::{assert_de_tokens_error, Token};
+
+        #[test]
+        fn test_error_invalid() {
+            assert_de_tokens_error::<Pattern>(
+                &[Token::Str("[abc")],
+                "invalid value: string \"[abc\", \
+                expected a glob pattern using '*', '?' or '[...]'",
+            );
         }
-        if let Some(seeding_time) = &self.seeding_time {
-            conditions.push(format!(
-                "seeding time {} {} minutes",
-                seeding_time.operator, seeding_time.value
-            ));
+    }
+
+    mod template {
+        use super::*;
+        use serde_test::{assert_de_tokens_error, Token};
+        use test_case::test_case;
+
+        #[test_case("{name} -> {new_ratio}", "Okarun -> 2" ; "fields")]
+        #[test_case("{{{rule}}}", "{alien}" ; "escaped braces")]
+        #[test_case("plain", "plain" ; "no fields")]
+        fn test_render(template: &str, expected: &str) {
+            let template: Template = template.parse().unwrap();
+            let rendered = template.render(|field| {
+                match field {
+                    TemplateField::Name => "Okarun",
+                    TemplateField::NewRatio => "2",
+                    TemplateField::Rule => "alien",
+                    _ => "",
+                }
+                .to_string()
+            });
+            assert_eq!(rendered, expected);
         }
-        if let Some(tags) = &self.tags {
-            conditions.push(format!("tags = {}", tags));
+
+        #[test_case("{unknown}" ; "unknown field")]
+        #[test_case("{name" ; "unclosed")]
+        #[test_case("name}" ; "unopened")]
+        fn test_error_invalid(template: &'static str) {
+            assert_de_tokens_error::<Template>(
+                &[Token::Str(template)],
+                &format!(
+                    "invalid value: string {:?}, expected a template with placeholders such as \
+                    {{name}}, {{hash}}, {{rule}}, {{category}}, {{old_ratio}}, {{new_ratio}}, \
+                    {{old_minutes}} or {{new_minutes}}",
+                    template
+                ),
+            );
         }
-        let ratio = match self.limits.ratio {
-            Some(ratio) => Cow::from(ratio.to_string()),
-            None => Cow::from(crate::UNLIMITED),
-        };
-        let minutes = match self.limits.minutes {
-            Some(minutes) => Cow::from(minutes.to_string()),
-            None => Cow::from(crate::UNLIMITED),
-        };
-        write!(f, "{} => {} ratio and {} minutes", conditions.join(", "), ratio, minutes)
     }
-}
 
-#[derive(Deserialize, PartialEq, Debug)]
-pub struct RuleLimits {
-    pub ratio: Option<qbittorrent::Ratio>,
-    pub minutes: Option<qbittorrent::MaxSeedingTime>,
-}
+    mod bytes {
+        use super::*;
+        use test_case::test_case;
 
-#[derive(Deserialize, PartialEq, Debug)]
-pub struct RuleList(Vec<Rule>);
+        #[test_case("512", Ok(512) ; "bytes")]
+        #[test_case("50GB", Ok(50_000_000_000) ; "gigabytes")]
+        #[test_case("1.5 TiB", Ok(1_649_267_441_664) ; "tebibytes")]
+        #[test_case("10kb", Ok(10_000) ; "lowercase")]
+        #[test_case("10XB", Err(()) ; "unknown unit")]
+        #[test_case("GB", Err(()) ; "no number")]
+        #[test_case("20000000TB", Err(()) ; "overflow")]
+        #[test_case("18446744073709551616", Err(()) ; "overflow without unit")]
+        fn test_parse(s: &str, expected: Result<u64, ()>) {
+            assert_eq!(s.parse::<Bytes>(), expected.map(Bytes));
+        }
 
-impl RuleList {
-    pub fn find(&self, torrent: &qbittorrent::Torrent) -> Option<&Rule> {
-        self.0.iter().find(|&rule| rule.matches(torrent))
+        #[test_case(999, "999B" ; "bytes")]
+        #[test_case(50_000_000_000, "50GB" ; "gigabytes")]
+        #[test_case(1_500_000, "1.5MB" ; "fraction")]
+        fn test_display(bytes: u64, expected: &str) {
+            assert_eq!(Bytes(bytes).to_string(), expected);
+        }
     }
 
-    pub fn iter(&self) -> std::slice::Iter<'_, Rule> {
-        self.0.iter()
+    mod time_span {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case("90s", Ok(90) ; "seconds")]
+        #[test_case("30d", Ok(2_592_000) ; "days")]
+        #[test_case("2w", Ok(1_209_600) ; "weeks")]
+        #[test_case("12", Err(()) ; "no unit")]
+        #[test_case("1y", Err(()) ; "unknown unit")]
+        #[test_case("30500000000000000w", Err(()) ; "overflow")]
+        #[test_case("18446744073709551616s", Err(()) ; "number overflow")]
+        fn test_parse(s: &str, expected: Result<usize, ()>) {
+            assert_eq!(s.parse::<TimeSpan>(), expected.map(TimeSpan));
+        }
+
+        #[test_case(2_592_000, "30d" ; "days")]
+        #[test_case(5400, "90m" ; "minutes")]
+        #[test_case(0, "0s" ; "zero")]
+        fn test_display(seconds: usize, expected: &str) {
+            assert_eq!(TimeSpan(seconds).to_string(), expected);
+        }
     }
 
-    pub fn len(&self) -> usize {
-        self.0.len()
+    mod rule_limits {
+        use super::*;
+        use test_case::test_case;
+
+        #[test_case("{ratio: 2.5, minutes: 1440}", Some(2.5), Some(1440) ; "numbers")]
+        #[test_case("{minutes: 30d}", None, Some(43200) ; "duration")]
+        #[test_case("{minutes: '1440m'}", None, Some(1440) ; "minutes unit")]
+        #[test_case("{ratio: unlimited, minutes: unlimited}", Some(-1.0), Some(-1) ; "unlimited")]
+        #[test_case("{ratio: global, minutes: global}", Some(-2.0), Some(-2) ; "global")]
+        #[test_case("{}", None, None ; "unset")]
+        fn test_deserialize(
+            yaml: &str,
+            ratio: Option<qbittorrent::Ratio>,
+            minutes: Option<qbittorrent::MaxSeedingTime>,
+        ) {
+            let limits: RuleLimits = serde_yaml::from_str(yaml).unwrap();
+            assert_eq!(limits.ratio, ratio);
+            assert_eq!(limits.minutes, minutes);
+        }
+
+        #[test_case("{uploadKiBps: 512}", Some(512) ; "limited")]
+        #[test_case("{uploadKiBps: 0}", Some(0) ; "unlimited")]
+        #[test_case("{ratio: 2.0}", None ; "unset")]
+        fn test_deserialize_upload(yaml: &str, expected: Option<u32>) {
+            let limits: RuleLimits = serde_yaml::from_str(yaml).unwrap();
+            assert_eq!(limits.upload_ki_bps, expected);
+        }
+
+        #[test]
+        fn test_deserialize_download() {
+            let limits: RuleLimits = serde_yaml::from_str("{downloadKiBps: 1024}").unwrap();
+            assert_eq!(limits.download_ki_bps, Some(1024));
+            assert_eq!(limits.upload_ki_bps, None);
+        }
+
+        #[test_case("{ratio: 2.0, minutes: 60}", "2 ratio and 60 minutes" ; "share limits")]
+        #[test_case(
+            "{ratio: 2.0, inactiveMinutes: 30, uploadKiBps: 100}",
+            "2 ratio, global minutes, 30 inactive minutes and 100 KiB/s upload" ;
+            "upload"
+        )]
+        #[test_case(
+            "{uploadKiBps: 0, downloadKiBps: 50}",
+            "global ratio, global minutes, unlimited upload and 50 KiB/s download" ;
+            "download"
+        )]
+        #[test_case(
+            "{ratio: unlimited, minutes: global}",
+            "unlimited ratio and global minutes" ;
+            "keywords"
+        )]
+        fn test_display(yaml: &str, expected: &str) {
+            let limits: RuleLimits = serde_yaml::from_str(yaml).unwrap();
+            assert_eq!(limits.to_string(), expected);
+        }
+
+        #[test_case("{ratio: lots}" ; "ratio")]
+        #[test_case("{minutes: 90s}" ; "partial minute")]
+        #[test_case("{inactiveMinutes: forever}" ; "inactive")]
+        fn test_deserialize_invalid(yaml: &str) {
+            assert!(serde_yaml::from_str::<RuleLimits>(yaml).is_err());
+        }
+
+        // qBittorrent stores ratio limits in single precision and may report them that way.
+        #[test_case(1.05, 1.05, None, false ; "exact")]
+        #[test_case(1.05, 1.0499999523162842, None, false ; "single precision")]
+        #[test_case(0.1, 0.10000000149011612, None, false ; "single precision above")]
+        #[test_case(1.05, 1.0500000000000003, None, false ; "double precision")]
+        #[test_case(1234.56, 1234.56005859375, None, false ; "large ratio")]
+        #[test_case(1.05, 1.06, None, true ; "two decimals")]
+        #[test_case(1.0, 1.001, None, true ; "three decimals")]
+        #[test_case(-1.0, -1.0, None, false ; "unlimited")]
+        #[test_case(-1.0, 1.05, None, true ; "limited to unlimited")]
+        #[test_case(-2.0, 1.05, Some(-2.0), false ; "global")]
+        #[test_case(-2.0, 1.05, Some(1.0499999523162842), true ; "own limit to global")]
+        fn test_ratio_differs(
+            target: qbittorrent::Ratio,
+            current: qbittorrent::Ratio,
+            own: Option<qbittorrent::Ratio>,
+            expected: bool,
+        ) {
+            assert_eq!(limit_differs(target, current, own, GLOBAL_RATIO), expected);
+        }
+
+        #[test_case("{ratio: 1.0499999523162842, minutes: 60}", true ; "rounded ratio")]
+        #[test_case("{ratio: 1.06, minutes: 60}", false ; "other ratio")]
+        #[test_case("{ratio: 1.05}", false ; "unset minutes")]
+        #[test_case("{ratio: 1.05, minutes: 61}", false ; "other minutes")]
+        fn test_same_as(yaml: &str, expected: bool) {
+            let limits: RuleLimits = serde_yaml::from_str("{ratio: 1.05, minutes: 60}").unwrap();
+            let reported: RuleLimits = serde_yaml::from_str(yaml).unwrap();
+            assert_eq!(limits.same_as(&reported), expected);
+        }
     }
-}
 
-#[derive(Deserialize, PartialEq, Debug, Default)]
-pub struct ServerConfig {
-    pub address: String,
-    pub username: Option<String>,
-    pub password: Option<String>,
-}
+    mod minutes {
+        use super::*;
+        use test_case::test_case;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        #[test_case("10080", Ok(10080) ; "bare minutes")]
+        #[test_case("2w", Ok(20160) ; "weeks")]
+        #[test_case("36h", Ok(2160) ; "hours")]
+        #[test_case("1440m", Ok(1440) ; "minutes")]
+        #[test_case("90s", Err(()) ; "partial minute")]
+        #[test_case("1y", Err(()) ; "unknown unit")]
+        fn test_parse(s: &str, expected: Result<usize, ()>) {
+            assert_eq!(s.parse::<Minutes>(), expected.map(Minutes));
+        }
+    }
 
     mod comparison {
         use super::*;
@@ -223,10 +4423,7 @@ mod tests {
         #[test_case(222, false ; "equal")]
         #[test_case(333, true ; "greater")]
         fn test_compare_gt(value: usize, expected: bool) {
-            let comparison = Comparison::<usize> {
-                operator: ComparisonOperator::GreaterThan,
-                value: 222,
-            };
+            let comparison = Comparison::<usize>::Bound(ComparisonOperator::GreaterThan, 222);
             assert_eq!(comparison.compare(value), expected);
         }
 
@@ -234,10 +4431,8 @@ mod tests {
         #[test_case(333, true ; "equal")]
         #[test_case(444, true ; "greater")]
         fn test_compare_gte(value: usize, expected: bool) {
-            let comparison = Comparison::<usize> {
-                operator: ComparisonOperator::GreaterThanOrEqual,
-                value: 333,
-            };
+            let comparison =
+                Comparison::<usize>::Bound(ComparisonOperator::GreaterThanOrEqual, 333);
             assert_eq!(comparison.compare(value), expected);
         }
 
@@ -245,10 +4440,7 @@ mod tests {
         #[test_case(444, false ; "equal")]
         #[test_case(555, false ; "more")]
         fn test_compare_lt(value: usize, expected: bool) {
-            let comparison = Comparison::<usize> {
-                operator: ComparisonOperator::LessThan,
-                value: 444,
-            };
+            let comparison = Comparison::<usize>::Bound(ComparisonOperator::LessThan, 444);
             assert_eq!(comparison.compare(value), expected);
         }
 
@@ -256,10 +4448,16 @@ mod tests {
         #[test_case(555, true ; "equal")]
         #[test_case(666, false ; "more")]
         fn test_compare_lte(value: usize, expected: bool) {
-            let comparison = Comparison::<usize> {
-                operator: ComparisonOperator::LessThanOrEqual,
-                value: 555,
-            };
+            let comparison = Comparison::<usize>::Bound(ComparisonOperator::LessThanOrEqual, 555);
+            assert_eq!(comparison.compare(value), expected);
+        }
+
+        #[test_case(9, false ; "below")]
+        #[test_case(10, true ; "lower end")]
+        #[test_case(20, true ; "upper end")]
+        #[test_case(21, false ; "above")]
+        fn test_compare_between(value: usize, expected: bool) {
+            let comparison = Comparison::<usize>::Between(10, 20);
             assert_eq!(comparison.compare(value), expected);
         }
 
@@ -300,39 +4498,52 @@ mod tests {
                 );
             }
 
+            #[test]
+            fn test_range() {
+                let comparison = Comparison::<usize>::Between(10, 20);
+                assert_de_tokens(&comparison, &[Token::Str("10..20")]);
+                assert_de_tokens(
+                    &comparison,
+                    &[
+                        Token::Seq { len: Some(2) },
+                        Token::U64(10),
+                        Token::Str("20"),
+                        Token::SeqEnd,
+                    ],
+                );
+            }
+
+            #[test]
+            fn test_error_reversed_range() {
+                assert_de_tokens_error::<Comparison<usize>>(
+                    &[Token::Str("20..10")],
+                    "the lower end of the range is above the upper end",
+                );
+            }
+
             #[test]
             fn test_gt() {
-                let comparison = Comparison::<i32> {
-                    operator: ComparisonOperator::GreaterThan,
-                    value: 963,
-                };
+                let comparison = Comparison::<i32>::Bound(ComparisonOperator::GreaterThan, 963);
                 assert_de_tokens(&comparison, &[Token::Str(">963")]);
             }
 
             #[test]
             fn test_gte() {
-                let comparison = Comparison::<u32> {
-                    operator: ComparisonOperator::GreaterThanOrEqual,
-                    value: 1234,
-                };
+                let comparison =
+                    Comparison::<u32>::Bound(ComparisonOperator::GreaterThanOrEqual, 1234);
                 assert_de_tokens(&comparison, &[Token::Str(">=1234")]);
             }
 
             #[test]
             fn test_lt() {
-                let comparison = Comparison::<usize> {
-                    operator: ComparisonOperator::LessThan,
-                    value: 3,
-                };
+                let comparison = Comparison::<usize>::Bound(ComparisonOperator::LessThan, 3);
                 assert_de_tokens(&comparison, &[Token::Str("<3")]);
             }
 
             #[test]
             fn test_lte() {
-                let comparison = Comparison::<i64> {
-                    operator: ComparisonOperator::LessThanOrEqual,
-                    value: -50000,
-                };
+                let comparison =
+                    Comparison::<i64>::Bound(ComparisonOperator::LessThanOrEqual, -50000);
                 assert_de_tokens(&comparison, &[Token::Str("<=-50000")]);
             }
         }
@@ -0,0 +1,110 @@
+use crate::plan;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, PartialEq)]
+enum Answer {
+    Yes,
+    No,
+    Each,
+    All,
+}
+
+impl Answer {
+    fn parse(line: &str) -> Self {
+        match line.trim().to_lowercase().as_str() {
+            "y" | "yes" => Self::Yes,
+            "e" | "each" => Self::Each,
+            "a" | "all" => Self::All,
+            _ => Self::No,
+        }
+    }
+}
+
+/// Asks on the terminal before changes are applied.
+#[derive(Default)]
+pub struct Confirmer {
+    /// Set once the user chooses to apply everything without asking again.
+    apply_all: bool,
+}
+
+impl Confirmer {
+    /// Lists the changes and returns the ones the user chose to apply.
+    pub fn select<'a, 'b>(
+        &mut self,
+        changes: &[&'b plan::Change<'a>],
+    ) -> io::Result<Vec<&'b plan::Change<'a>>> {
+        self.select_with(&mut io::stdin().lock(), &mut io::stdout(), changes)
+    }
+
+    fn select_with<'a, 'b>(
+        &mut self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+        changes: &[&'b plan::Change<'a>],
+    ) -> io::Result<Vec<&'b plan::Change<'a>>> {
+        if self.apply_all || changes.is_empty() {
+            return Ok(changes.to_vec());
+        }
+        writeln!(output, "{} changes pending:", changes.len())?;
+        for change in changes {
+            writeln!(output, "  {}", change)?;
+        }
+        let answer = ask(
+            input,
+            output,
+            "Apply? [y]es, [N]o, [e]ach separately, [a]ll from now on: ",
+        )?;
+        match answer {
+            Answer::Yes => Ok(changes.to_vec()),
+            Answer::No => Ok(Vec::new()),
+            Answer::All => {
+                self.apply_all = true;
+                Ok(changes.to_vec())
+            }
+            Answer::Each => {
+                let mut selected = Vec::new();
+                for &change in changes {
+                    if ask(input, output, &format!("Apply {}? [y/N]: ", change))? == Answer::Yes {
+                        selected.push(change);
+                    }
+                }
+                Ok(selected)
+            }
+        }
+    }
+}
+
+fn ask(input: &mut impl BufRead, output: &mut impl Write, question: &str) -> io::Result<Answer> {
+    write!(output, "{}", question)?;
+    output.flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(Answer::parse(&line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent;
+    use std::io::Cursor;
+    use test_case::test_case;
+
+    #[test_case("y\n", &["a", "b"], false ; "yes")]
+    #[test_case("\n", &[], false ; "default no")]
+    #[test_case("", &[], false ; "end of input")]
+    #[test_case("e\nn\ny\n", &["b"], false ; "each")]
+    #[test_case("a\n", &["a", "b"], true ; "all")]
+    fn test_select(input: &str, expected: &[&str], apply_all: bool) {
+        let torrent = qbittorrent::Torrent::default();
+        let a = plan::Change::new("a", &torrent, None);
+        let b = plan::Change::new("b", &torrent, None);
+        let changes = [&a, &b];
+        let mut confirmer = Confirmer::default();
+        let selected = confirmer
+            .select_with(&mut Cursor::new(input), &mut Vec::new(), &changes)
+            .unwrap();
+        let hashes: Vec<&str> = selected.iter().map(|x| x.hash).collect();
+        assert_eq!(hashes, expected);
+        assert_eq!(confirmer.apply_all, apply_all);
+    }
+}
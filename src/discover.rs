@@ -0,0 +1,186 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// WebUI ports probed in addition to the one in the local qBittorrent configuration.
+const DEFAULT_PORTS: [u16; 3] = [8080, 8081, 8090];
+const HOSTS: [&str; 2] = ["127.0.0.1", "[::1]"];
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// WebUI settings read from a local qBittorrent configuration file.
+#[derive(Debug, Default, PartialEq)]
+struct WebUiSettings {
+    port: Option<u16>,
+    https: bool,
+}
+
+impl WebUiSettings {
+    fn parse(ini: &str) -> Self {
+        let mut settings = Self::default();
+        let mut preferences = false;
+        for line in ini.lines().map(str::trim) {
+            if line.starts_with('[') {
+                preferences = line == "[Preferences]";
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if !preferences {
+                continue;
+            }
+            match key.trim() {
+                "WebUI\\Port" => settings.port = value.trim().parse().ok(),
+                "WebUI\\HTTPS\\Enabled" => settings.https = value.trim() == "true",
+                _ => {}
+            }
+        }
+        settings
+    }
+}
+
+/// Locations of the qBittorrent configuration file on Linux (including Flatpak), macOS and
+/// Windows.
+fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(config_home) = env::var_os("XDG_CONFIG_HOME") {
+        paths.push(PathBuf::from(config_home).join("qBittorrent/qBittorrent.conf"));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        paths.push(home.join(".config/qBittorrent/qBittorrent.conf"));
+        paths.push(
+            home.join(".var/app/org.qbittorrent.qBittorrent/config/qBittorrent/qBittorrent.conf"),
+        );
+        paths.push(home.join("Library/Preferences/qBittorrent/qBittorrent.ini"));
+    }
+    if let Some(app_data) = env::var_os("APPDATA") {
+        paths.push(PathBuf::from(app_data).join("qBittorrent/qBittorrent.ini"));
+    }
+    paths
+}
+
+/// WebUI base URLs to probe, starting with the ones from the local configuration.
+fn candidates() -> Vec<String> {
+    let mut schemes_ports: Vec<(&str, u16)> = Vec::new();
+    for path in config_paths() {
+        let Ok(ini) = fs::read_to_string(&path) else {
+            continue;
+        };
+        log::info!("Read qBittorrent configuration from {}", path.display());
+        let settings = WebUiSettings::parse(&ini);
+        if let Some(port) = settings.port {
+            let scheme = if settings.https { "https" } else { "http" };
+            schemes_ports.push((scheme, port));
+        }
+    }
+    for port in DEFAULT_PORTS {
+        schemes_ports.push(("http", port));
+    }
+    let mut candidates = Vec::new();
+    for (scheme, port) in schemes_ports {
+        for host in HOSTS {
+            let url = format!("{}://{}:{}/", scheme, host, port);
+            if !candidates.contains(&url) {
+                candidates.push(url);
+            }
+        }
+    }
+    candidates
+}
+
+/// A running WebUI.
+struct Instance {
+    address: String,
+    /// Whether the API could be used without logging in.
+    open: bool,
+}
+
+async fn probe(client: &reqwest::Client, address: &str) -> Option<Instance> {
+    let response = client
+        .get(format!("{}api/v2/app/webapiVersion", address))
+        .send()
+        .await
+        .ok()?;
+    let open = match response.status() {
+        reqwest::StatusCode::OK => true,
+        reqwest::StatusCode::FORBIDDEN => false,
+        _ => return None,
+    };
+    // Other web servers return 200 for arbitrary paths, but not a version number.
+    if open
+        && !response
+            .text()
+            .await
+            .ok()?
+            .starts_with(|c: char| c.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(Instance {
+        address: address.to_string(),
+        open,
+    })
+}
+
+/// Probes the local addresses for a qBittorrent WebUI and prints a server block for it.
+pub async fn discover() -> bool {
+    let client = match reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        // Local WebUIs with HTTPS commonly use self-signed certificates.
+        .danger_accept_invalid_certs(true)
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            log::error!("HTTP client error: {}", error);
+            return false;
+        }
+    };
+    for address in candidates() {
+        log::debug!("Probing {}", address);
+        let Some(instance) = probe(&client, &address).await else {
+            continue;
+        };
+        log::info!("Found qBittorrent at {}", instance.address);
+        println!("server:");
+        println!("  address: {}", instance.address.trim_end_matches('/'));
+        if instance.open {
+            println!("  # The WebUI does not require logging in from this machine.");
+        } else {
+            println!("  username: admin");
+            println!("  password: changeme");
+        }
+        return true;
+    }
+    log::error!("Could not find qBittorrent on this machine");
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let ini = "[BitTorrent]\n\
+            Session\\Port=6881\n\
+            \n\
+            [Preferences]\n\
+            WebUI\\Address=*\n\
+            WebUI\\HTTPS\\Enabled=true\n\
+            WebUI\\Port=8123\n";
+        assert_eq!(
+            WebUiSettings::parse(ini),
+            WebUiSettings {
+                port: Some(8123),
+                https: true
+            }
+        );
+        assert_eq!(
+            WebUiSettings::parse("[Other]\nWebUI\\Port=8123"),
+            WebUiSettings::default()
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{config, plan};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time;
+
+/// How long to wait for the command before killing it.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Runs the rule's command for the change. Failures are logged rather than returned, since the
+/// change itself has already been applied.
+pub async fn run(command: &config::ExecCommand, change: &plan::Change<'_>) {
+    let Some(mut command) = build(command, change) else {
+        return;
+    };
+    let name = &change.torrent.name;
+    match time::timeout(TIMEOUT, command.status()).await {
+        Ok(Ok(status)) if status.success() => log::debug!("Ran command for {}", name),
+        Ok(Ok(status)) => log::warn!("Command for {} exited with {}", name, status),
+        Ok(Err(error)) => log::warn!("Could not run command for {}: {}", name, error),
+        Err(_) => log::warn!(
+            "Command for {} did not finish within {} seconds: killed",
+            name,
+            TIMEOUT.as_secs()
+        ),
+    }
+}
+
+/// The command with the torrent described in `JEANNE_` environment variables.
+fn build(command: &config::ExecCommand, change: &plan::Change) -> Option<Command> {
+    let (program, args) = command.split()?;
+    let torrent = change.torrent;
+    let rule = change
+        .rule
+        .map(|(i, rule)| rule.name(i).into_owned())
+        .unwrap_or_default();
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .env("JEANNE_RULE", rule)
+        .env("JEANNE_HASH", change.hash)
+        .env("JEANNE_NAME", &torrent.name)
+        .env("JEANNE_CATEGORY", &torrent.category)
+        .env(
+            "JEANNE_TAGS",
+            torrent.tags.iter().cloned().collect::<Vec<_>>().join(","),
+        )
+        .env("JEANNE_SAVE_PATH", &torrent.save_path)
+        .env("JEANNE_CONTENT_PATH", &torrent.content_path)
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent;
+    use test_case::test_case;
+
+    #[test_case("/bin/true", &[] ; "program")]
+    #[test_case("[/bin/echo, a, b]", &["a", "b"] ; "arguments")]
+    fn test_build(yaml: &str, expected: &[&str]) {
+        let command: config::ExecCommand = serde_yaml::from_str(yaml).unwrap();
+        let torrent = qbittorrent::Torrent {
+            name: "Alien".to_string(),
+            category: "movies".to_string(),
+            save_path: "/data".to_string(),
+            ..Default::default()
+        };
+        let change = plan::Change::new("abc", &torrent, None);
+        let command = build(&command, &change).unwrap();
+        let command = command.as_std();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, expected);
+        let env: Vec<_> = command
+            .get_envs()
+            .filter_map(|(key, value)| Some((key.to_str()?, value?.to_str()?)))
+            .collect();
+        assert!(env.contains(&("JEANNE_HASH", "abc")));
+        assert!(env.contains(&("JEANNE_NAME", "Alien")));
+        assert!(env.contains(&("JEANNE_CATEGORY", "movies")));
+        assert!(env.contains(&("JEANNE_SAVE_PATH", "/data")));
+    }
+}
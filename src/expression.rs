@@ -0,0 +1,620 @@
+use crate::config::{Bytes, TimeSpan};
+use crate::qbittorrent;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Boolean expression evaluated against a torrent, such as
+/// `seeding_time > 7d && (category == "tv" || "archive" in tags)`.
+#[derive(Debug)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn matches(&self, torrent: &qbittorrent::Torrent) -> bool {
+        matches!(self.root.evaluate(torrent), Value::Bool(true))
+    }
+
+    /// Whether the expression refers to the field.
+    pub fn uses(&self, field: &str) -> bool {
+        self.root.uses(field)
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let (root, kind) = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {}", token));
+        }
+        if kind != Type::Bool {
+            return Err(format!("expected a true/false expression, got a {}", kind));
+        }
+        Ok(Self {
+            source: s.trim().to_string(),
+            root,
+        })
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let s = String::deserialize(d)?;
+        s.parse()
+            .map_err(|error| Error::custom(format!("invalid expression '{}': {}", s, error)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Bool,
+    List,
+    Number,
+    String,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool => write!(f, "true/false value"),
+            Self::List => write!(f, "list"),
+            Self::Number => write!(f, "number"),
+            Self::String => write!(f, "string"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    List(Vec<String>),
+    Number(f64),
+    String(String),
+}
+
+/// Torrent data available in expressions. Times are in seconds and amounts of data in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Age,
+    AutoTmm,
+    Availability,
+    Category,
+    Completed,
+    CompletedAge,
+    Downloaded,
+    InactiveTime,
+    Leechers,
+    Name,
+    Private,
+    Progress,
+    Ratio,
+    SeedingTime,
+    Size,
+    State,
+    Tags,
+    Tracker,
+    Trackers,
+    Uploaded,
+    UpSpeed,
+}
+
+impl Field {
+    const ALL: [Self; 21] = [
+        Self::Age,
+        Self::AutoTmm,
+        Self::Availability,
+        Self::Category,
+        Self::Completed,
+        Self::CompletedAge,
+        Self::Downloaded,
+        Self::InactiveTime,
+        Self::Leechers,
+        Self::Name,
+        Self::Private,
+        Self::Progress,
+        Self::Ratio,
+        Self::SeedingTime,
+        Self::Size,
+        Self::State,
+        Self::Tags,
+        Self::Tracker,
+        Self::Trackers,
+        Self::Uploaded,
+        Self::UpSpeed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Age => "age",
+            Self::AutoTmm => "auto_tmm",
+            Self::Availability => "availability",
+            Self::Category => "category",
+            Self::Completed => "completed",
+            Self::CompletedAge => "completed_age",
+            Self::Downloaded => "downloaded",
+            Self::InactiveTime => "inactive_time",
+            Self::Leechers => "leechers",
+            Self::Name => "name",
+            Self::Private => "private",
+            Self::Progress => "progress",
+            Self::Ratio => "ratio",
+            Self::SeedingTime => "seeding_time",
+            Self::Size => "size",
+            Self::State => "state",
+            Self::Tags => "tags",
+            Self::Tracker => "tracker",
+            Self::Trackers => "trackers",
+            Self::Uploaded => "uploaded",
+            Self::UpSpeed => "up_speed",
+        }
+    }
+
+    fn kind(self) -> Type {
+        match self {
+            Self::AutoTmm | Self::Completed | Self::Private => Type::Bool,
+            Self::Tags | Self::Trackers => Type::List,
+            Self::Category | Self::Name | Self::State | Self::Tracker => Type::String,
+            _ => Type::Number,
+        }
+    }
+
+    fn value(self, torrent: &qbittorrent::Torrent) -> Value {
+        match self {
+            Self::Age => Value::Number(torrent.age() as f64),
+            Self::AutoTmm => Value::Bool(torrent.auto_tmm),
+            Self::Availability => Value::Number(torrent.availability),
+            Self::Category => Value::String(torrent.category.clone()),
+            Self::Completed => Value::Bool(torrent.progress >= 1.0),
+            Self::CompletedAge => Value::Number(torrent.time_since_completion() as f64),
+            Self::Downloaded => Value::Number(torrent.downloaded as f64),
+            Self::InactiveTime => Value::Number(torrent.inactive_time() as f64),
+            Self::Leechers => Value::Number(torrent.num_incomplete as f64),
+            Self::Name => Value::String(torrent.name.clone()),
+            Self::Private => Value::Bool(torrent.private == Some(true)),
+            Self::Progress => Value::Number(torrent.progress),
+            Self::Ratio => Value::Number(torrent.ratio),
+            Self::SeedingTime => Value::Number(torrent.seeding_time as f64),
+            Self::Size => Value::Number(torrent.size as f64),
+            Self::State => Value::String(torrent.state.clone()),
+            Self::Tags => Value::List(torrent.tags.iter().cloned().collect()),
+            Self::Tracker => Value::String(torrent.tracker.clone()),
+            Self::Trackers => {
+                Value::List(torrent.trackers.iter().map(|x| x.host.clone()).collect())
+            }
+            Self::Uploaded => Value::Number(torrent.uploaded as f64),
+            Self::UpSpeed => Value::Number(torrent.upspeed as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Operator {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "==" => Some(Self::Equal),
+            "!=" => Some(Self::NotEqual),
+            ">" => Some(Self::Greater),
+            ">=" => Some(Self::GreaterOrEqual),
+            "<" => Some(Self::Less),
+            "<=" => Some(Self::LessOrEqual),
+            _ => None,
+        }
+    }
+
+    fn is_ordering(self) -> bool {
+        !matches!(self, Self::Equal | Self::NotEqual)
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Literal(Value),
+    Field(Field),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Box<Node>, Operator, Box<Node>),
+    In(Box<Node>, Box<Node>),
+    Matches(Box<Node>, glob::Pattern),
+}
+
+impl Node {
+    fn evaluate(&self, torrent: &qbittorrent::Torrent) -> Value {
+        match self {
+            Self::Literal(value) => value.clone(),
+            Self::Field(field) => field.value(torrent),
+            Self::Not(node) => Value::Bool(!node.test(torrent)),
+            Self::And(left, right) => Value::Bool(left.test(torrent) && right.test(torrent)),
+            Self::Or(left, right) => Value::Bool(left.test(torrent) || right.test(torrent)),
+            Self::Compare(left, operator, right) => {
+                let ordering = match (left.evaluate(torrent), right.evaluate(torrent)) {
+                    (Value::Number(a), Value::Number(b)) => a.partial_cmp(&b),
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(&b)),
+                    (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(&b)),
+                    _ => None,
+                };
+                let Some(ordering) = ordering else {
+                    return Value::Bool(false);
+                };
+                Value::Bool(match operator {
+                    Operator::Equal => ordering.is_eq(),
+                    Operator::NotEqual => ordering.is_ne(),
+                    Operator::Greater => ordering.is_gt(),
+                    Operator::GreaterOrEqual => ordering.is_ge(),
+                    Operator::Less => ordering.is_lt(),
+                    Operator::LessOrEqual => ordering.is_le(),
+                })
+            }
+            Self::In(left, right) => {
+                Value::Bool(match (left.evaluate(torrent), right.evaluate(torrent)) {
+                    (Value::String(item), Value::List(list)) => list.contains(&item),
+                    (Value::String(needle), Value::String(haystack)) => haystack.contains(&needle),
+                    _ => false,
+                })
+            }
+            Self::Matches(node, pattern) => Value::Bool(match node.evaluate(torrent) {
+                Value::String(value) => pattern.matches(&value),
+                _ => false,
+            }),
+        }
+    }
+
+    fn test(&self, torrent: &qbittorrent::Torrent) -> bool {
+        self.evaluate(torrent) == Value::Bool(true)
+    }
+
+    fn uses(&self, name: &str) -> bool {
+        match self {
+            Self::Literal(_) => false,
+            Self::Field(field) => field.name() == name,
+            Self::Not(node) | Self::Matches(node, _) => node.uses(name),
+            Self::And(left, right)
+            | Self::Or(left, right)
+            | Self::Compare(left, _, right)
+            | Self::In(left, right) => left.uses(name) || right.uses(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Number(f64),
+    String(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Identifier(name) => write!(f, "'{}'", name),
+            Self::Number(number) => write!(f, "number {}", number),
+            Self::String(string) => write!(f, "string \"{}\"", string),
+            Self::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 14] = [
+    "&&", "||", "==", "!=", ">=", "<=", ">", "<", "!", "(", ")", "[", "]", ",",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(symbol) = SYMBOLS.iter().find(|x| rest.starts_with(**x)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if c == '"' || c == '\'' {
+            let mut string = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, x)) if x == c => break i + 2,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, x)) => string.push(x),
+                        None => return Err("unterminated string".to_string()),
+                    },
+                    Some((_, x)) => string.push(x),
+                    None => return Err("unterminated string".to_string()),
+                }
+            };
+            tokens.push(Token::String(string));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|x: char| !x.is_ascii_alphanumeric() && x != '.')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..end])?));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|x: char| !x.is_ascii_alphanumeric() && x != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Identifier(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Parses a number with an optional time unit (`7d`) or data unit (`50GB`).
+fn parse_number(s: &str) -> Result<f64, String> {
+    if let Ok(number) = s.parse() {
+        return Ok(number);
+    }
+    if let Ok(time) = s.parse::<TimeSpan>() {
+        return Ok(time.seconds() as f64);
+    }
+    if let Ok(bytes) = s.parse::<Bytes>() {
+        return Ok(bytes.bytes() as f64);
+    }
+    Err(format!("invalid number '{}'", s))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or("unexpected end of expression")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// Consumes the token if it is next.
+    fn accept(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(x) if x == symbol => Ok(()),
+            token => Err(format!("expected '{}', got {}", symbol, token)),
+        }
+    }
+
+    fn or(&mut self) -> Result<(Node, Type), String> {
+        let mut left = self.and()?;
+        while self.accept(&Token::Symbol("||")) {
+            let right = self.and()?;
+            left = (
+                Node::Or(
+                    Box::new(boolean(left, "||")?),
+                    Box::new(boolean(right, "||")?),
+                ),
+                Type::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<(Node, Type), String> {
+        let mut left = self.unary()?;
+        while self.accept(&Token::Symbol("&&")) {
+            let right = self.unary()?;
+            left = (
+                Node::And(
+                    Box::new(boolean(left, "&&")?),
+                    Box::new(boolean(right, "&&")?),
+                ),
+                Type::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<(Node, Type), String> {
+        if self.accept(&Token::Symbol("!")) {
+            let node = boolean(self.unary()?, "!")?;
+            return Ok((Node::Not(Box::new(node)), Type::Bool));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<(Node, Type), String> {
+        let (left, left_kind) = self.primary()?;
+        let operator = match self.peek() {
+            Some(Token::Symbol(symbol)) => Operator::from_symbol(symbol),
+            Some(Token::Identifier(name)) if name == "in" => {
+                self.position += 1;
+                let (right, right_kind) = self.primary()?;
+                if left_kind != Type::String || !matches!(right_kind, Type::List | Type::String) {
+                    return Err(format!(
+                        "'in' needs a string on the left and a list or string on the right, got {} and {}",
+                        left_kind, right_kind
+                    ));
+                }
+                return Ok((Node::In(Box::new(left), Box::new(right)), Type::Bool));
+            }
+            Some(Token::Identifier(name)) if name == "matches" => {
+                self.position += 1;
+                let pattern = match self.next()? {
+                    Token::String(pattern) => glob::Pattern::new(&pattern)
+                        .map_err(|_| format!("invalid glob pattern \"{}\"", pattern))?,
+                    token => return Err(format!("expected a glob pattern, got {}", token)),
+                };
+                if left_kind != Type::String {
+                    return Err(format!("'matches' needs a string, got a {}", left_kind));
+                }
+                return Ok((Node::Matches(Box::new(left), pattern), Type::Bool));
+            }
+            _ => None,
+        };
+        let Some(operator) = operator else {
+            return Ok((left, left_kind));
+        };
+        self.position += 1;
+        let (right, right_kind) = self.primary()?;
+        if left_kind != right_kind || left_kind == Type::List {
+            return Err(format!("can't compare a {} to a {}", left_kind, right_kind));
+        }
+        if operator.is_ordering() && left_kind != Type::Number {
+            return Err(format!("can't order a {}", left_kind));
+        }
+        Ok((
+            Node::Compare(Box::new(left), operator, Box::new(right)),
+            Type::Bool,
+        ))
+    }
+
+    fn primary(&mut self) -> Result<(Node, Type), String> {
+        match self.next()? {
+            Token::Symbol("(") => {
+                let node = self.or()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Symbol("[") => {
+                let mut items = Vec::new();
+                if !self.accept(&Token::Symbol("]")) {
+                    loop {
+                        match self.next()? {
+                            Token::String(item) => items.push(item),
+                            token => return Err(format!("expected a string, got {}", token)),
+                        }
+                        if self.accept(&Token::Symbol("]")) {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok((Node::Literal(Value::List(items)), Type::List))
+            }
+            Token::Number(number) => Ok((Node::Literal(Value::Number(number)), Type::Number)),
+            Token::String(string) => Ok((Node::Literal(Value::String(string)), Type::String)),
+            Token::Identifier(name) => match name.as_str() {
+                "true" => Ok((Node::Literal(Value::Bool(true)), Type::Bool)),
+                "false" => Ok((Node::Literal(Value::Bool(false)), Type::Bool)),
+                _ => {
+                    let field = Field::ALL
+                        .into_iter()
+                        .find(|x| x.name() == name)
+                        .ok_or_else(|| format!("unknown field '{}'", name))?;
+                    Ok((Node::Field(field), field.kind()))
+                }
+            },
+            token => Err(format!("unexpected {}", token)),
+        }
+    }
+}
+
+/// Checks that the operand of a logical operator is true or false.
+fn boolean((node, kind): (Node, Type), operator: &str) -> Result<Node, String> {
+    if kind != Type::Bool {
+        return Err(format!(
+            "'{}' needs true/false values, got a {}",
+            operator, kind
+        ));
+    }
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn torrent() -> qbittorrent::Torrent {
+        qbittorrent::Torrent {
+            category: "tv".to_string(),
+            name: "Show.S01E02.1080p.mkv".to_string(),
+            ratio: 1.5,
+            seeding_time: 8 * 24 * 60 * 60,
+            size: 2_000_000_000,
+            tags: qbittorrent::TagList::from("archive, seen".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test_case("seeding_time > 7d", true ; "time unit")]
+    #[test_case("seeding_time > 1w && ratio < 1", false ; "and")]
+    #[test_case("ratio >= 2 || category == \"tv\"", true ; "or")]
+    #[test_case("seeding_time > 7d && (category == 'movies' || \"archive\" in tags)", true ; "grouped")]
+    #[test_case("!(\"keep\" in tags)", true ; "not")]
+    #[test_case("size > 1.5GB && size < 2GiB", true ; "data units")]
+    #[test_case("category in [\"tv\", \"anime\"]", true ; "in list")]
+    #[test_case("\"1080p\" in name", true ; "substring")]
+    #[test_case("name matches '*.S??E??.*'", true ; "glob")]
+    #[test_case("category != 'tv'", false ; "not equal")]
+    #[test_case("private == false && !completed", true ; "booleans")]
+    fn test_matches(expression: &str, expected: bool) {
+        let expression: Expression = expression.parse().unwrap();
+        assert_eq!(expression.matches(&torrent()), expected);
+    }
+
+    #[test_case("seeding_time >", "unexpected end of expression" ; "incomplete")]
+    #[test_case("seeding > 7d", "unknown field 'seeding'" ; "unknown field")]
+    #[test_case("ratio > 2x", "invalid number '2x'" ; "unknown unit")]
+    #[test_case("category > 'tv'", "can't order a string" ; "string ordering")]
+    #[test_case("ratio == 'high'", "can't compare a number to a string" ; "type mismatch")]
+    #[test_case("ratio", "expected a true/false expression, got a number" ; "not boolean")]
+    #[test_case("ratio && completed", "'&&' needs true/false values, got a number" ; "and number")]
+    #[test_case("(completed", "unexpected end of expression" ; "unclosed group")]
+    #[test_case("completed completed", "unexpected 'completed'" ; "trailing")]
+    #[test_case("name == 'tv", "unterminated string" ; "unterminated")]
+    #[test_case("ratio $ 2", "unexpected character '$'" ; "character")]
+    fn test_parse_error(expression: &str, expected: &str) {
+        assert_eq!(
+            expression.parse::<Expression>().unwrap_err(),
+            expected.to_string()
+        );
+    }
+
+    #[test]
+    fn test_uses() {
+        let expression: Expression = "private || 'x' in trackers".parse().unwrap();
+        assert!(expression.uses("private"));
+        assert!(expression.uses("trackers"));
+        assert!(!expression.uses("tracker"));
+    }
+}
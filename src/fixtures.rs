@@ -0,0 +1,131 @@
+use crate::{config, plan, qbittorrent};
+
+/// Hash under which the fixture torrent is evaluated.
+const FIXTURE_HASH: &str = "fixture";
+
+/// Evaluates the rules against the test case's torrent and describes any mismatch.
+pub fn check(config: &config::Config, case: &config::TestCase) -> Result<(), String> {
+    let torrents = qbittorrent::TorrentMap::from([(
+        FIXTURE_HASH.to_string(),
+        qbittorrent::Torrent::from(&case.torrent),
+    )]);
+    let plan = plan::Plan::new(config, &torrents);
+    let expect = &case.expect;
+    let Some(&(hash, torrent, matched)) = plan.evaluated.first() else {
+        return if expect.ignored {
+            Ok(())
+        } else {
+            Err("torrent was ignored".to_string())
+        };
+    };
+    if expect.ignored {
+        return Err("torrent was not ignored".to_string());
+    }
+
+    let rule = matched.map(|(i, rule)| rule.name(i));
+    if rule.as_deref() != expect.rule.as_deref() {
+        return Err(format!(
+            "expected rule {}, matched {}",
+            expect.rule.as_deref().unwrap_or("(none)"),
+            rule.as_deref().unwrap_or("(none)")
+        ));
+    }
+    if let Some(expected) = &expect.limits {
+        let limits = match (matched, &config.default_action) {
+            (Some(matched), _) => {
+                let layers =
+                    config
+                        .rules
+                        .layers(matched, hash, torrent, config.managed_tag.as_deref());
+                plan::layered_limits(&layers, torrent)
+            }
+            (None, config::DefaultAction::Limits(limits)) => Some(limits.clone()),
+            (None, _) => None,
+        };
+        if limits.as_ref() != Some(expected) {
+            return Err(format!(
+                "expected limits {}, got {}",
+                expected,
+                limits.map_or("none".to_string(), |x| x.to_string())
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Runs every test case in the configuration and prints the results. Returns whether all passed.
+pub fn run(config: &config::Config) -> bool {
+    let mut failed = 0;
+    for case in &config.tests {
+        match check(config, case) {
+            Ok(()) => println!("ok      {}", case.name),
+            Err(reason) => {
+                println!("FAILED  {}: {}", case.name, reason);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} passed, {} failed", config.tests.len() - failed, failed);
+    failed == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const CONFIG: &str = "
+server: {address: http://localhost}
+ignore: {categories: [Private]}
+rules:
+  - id: alien
+    category: Alien
+    limits: {ratio: 20.0}
+  - category: Ghost
+    onlyTighten: true
+    limits: {ratio: 5.0}
+";
+
+    #[test_case("{category: Alien}", "{rule: alien}", Ok(()) ; "rule")]
+    #[test_case(
+        "{category: Alien}",
+        "{rule: alien, limits: {ratio: 20.0}}",
+        Ok(()) ;
+        "limits"
+    )]
+    #[test_case(
+        "{category: Ghost, maxRatio: 2.0}",
+        "{rule: rule-2, limits: {ratio: 5.0}}",
+        Err("expected limits 5 ratio and global minutes, got 2 ratio and global minutes") ;
+        "guarded limits"
+    )]
+    #[test_case("{}", "{}", Ok(()) ; "unmatched")]
+    #[test_case(
+        "{category: Alien}",
+        "{}",
+        Err("expected rule (none), matched alien") ;
+        "unexpected rule"
+    )]
+    #[test_case("{category: Private}", "{ignored: true}", Ok(()) ; "ignored")]
+    #[test_case(
+        "{category: Private}",
+        "{rule: alien}",
+        Err("torrent was ignored") ;
+        "unexpectedly ignored"
+    )]
+    #[test_case(
+        "{category: Alien}",
+        "{ignored: true}",
+        Err("torrent was not ignored") ;
+        "not ignored"
+    )]
+    fn test_check(torrent: &str, expect: &str, expected: Result<(), &str>) {
+        let config: config::Config = serde_yaml::from_str(CONFIG).unwrap();
+        let case: config::TestCase = serde_yaml::from_str(&format!(
+            "{{name: test, torrent: {}, expect: {}}}",
+            torrent, expect
+        ))
+        .unwrap();
+        assert_eq!(check(&config, &case), expected.map_err(str::to_string));
+    }
+}
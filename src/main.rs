@@ -1,83 +1,635 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use simple_logger::SimpleLogger;
-use std::borrow::Cow;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::time::Duration;
+use std::time::Instant;
 use tokio::{task, time};
 
+mod clock;
 mod config;
+mod confirm;
+mod discover;
+mod exec;
+mod expression;
+mod fixtures;
+mod notify;
+mod plan;
+mod polling;
 mod qbittorrent;
+mod schedule;
+mod stats;
+mod status;
+mod templates;
+mod timings;
+mod tunnel;
 
 const UNLIMITED: &str = "unlimited";
 const GLOBAL: &str = "global";
+/// Prefix of the tags used to annotate torrents with the rule that manages them.
+const RULE_TAG_PREFIX: &str = "jeanne:";
+/// How many times a change is retried after rate-limited responses before giving up on the cycle.
+const MAX_RATE_LIMIT_RETRIES: usize = 5;
 
 #[derive(Parser)]
-#[command(name = "jeanne", version)]
+#[command(
+    name = "jeanne",
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    #[arg(env = "JEANNE_CONFIG")]
+    #[arg(env = "JEANNE_CONFIG", required_unless_present = "discover")]
     /// Path to the configuration Yaml file.
-    config: PathBuf,
+    config: Option<PathBuf>,
+
+    #[arg(long, exclusive = true)]
+    /// Look for qBittorrent on this machine and print the server configuration for it.
+    discover: bool,
+
+    #[arg(long)]
+    /// Evaluate the rules once and print the changes without applying them.
+    dry_run: bool,
+
+    #[arg(long)]
+    /// Log how long each phase of every cycle took and the latencies of the requests.
+    timings: bool,
+
+    #[arg(long, conflicts_with = "dry_run")]
+    /// List the changes of every cycle and ask for confirmation before applying them.
+    confirm: bool,
+
+    #[arg(long, value_enum, default_value_t = DiffFormat::Table)]
+    /// Output format for the changes printed in dry-run mode.
+    format: DiffFormat,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Summarize the statistics recorded in the state directory.
+    Stats {
+        #[arg(env = "JEANNE_CONFIG")]
+        /// Path to the configuration Yaml file.
+        config: PathBuf,
+
+        #[arg(long, default_value_t = 30)]
+        /// Number of days to summarize.
+        days: u32,
+    },
+    /// Check the rules against the example torrents in the configuration's tests section.
+    Test {
+        #[arg(env = "JEANNE_CONFIG")]
+        /// Path to the configuration Yaml file.
+        config: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DiffFormat {
+    Table,
+    Json,
+}
+
+/// Syncs the torrents and fetches the extra torrent details that the rules need.
+async fn sync(
+    config: &config::Config,
+    client: &mut qbittorrent::Client,
+    cycle: u64,
+) -> Result<qbittorrent::SyncSummary, qbittorrent::ClientError> {
+    let summary = client.update().await?;
+    if config.rules.use_trackers() {
+        client
+            .update_trackers(config.rules.refresh_tracker_statuses(cycle))
+            .await?;
+    }
+    if config.rules.use_file_counts() {
+        client.update_file_counts().await?;
+    }
+    if config.rules.use_private_flags() {
+        client.update_private_flags().await?;
+    }
+    Ok(summary)
 }
 
-async fn handle_torrent(
+async fn dry_run(
     config: &config::Config,
+    client: &mut qbittorrent::Client,
+    format: DiffFormat,
+) -> Result<(), qbittorrent::ClientError> {
+    sync(config, client, 0).await?;
+    let plan = plan::Plan::new(config, &client.torrents);
+    for line in plan.report(&config.rules).lines() {
+        log::info!("{}", line);
+    }
+    let diff = plan.diff();
+    match format {
+        DiffFormat::Table => print!("{}", plan::diff_table(&diff)),
+        DiffFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&diff).expect("Could not serialize changes")
+        ),
+    }
+    Ok(())
+}
+
+/// Retries the request while the server is rate limiting, up to `MAX_RATE_LIMIT_RETRIES` times.
+async fn retry_rate_limited<T, F, Fut>(mut request: F) -> Result<T, qbittorrent::ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, qbittorrent::ClientError>>,
+{
+    let mut retries = 0;
+    loop {
+        match request().await {
+            Err(qbittorrent::ClientError::RateLimited(delay))
+                if retries < MAX_RATE_LIMIT_RETRIES =>
+            {
+                log::warn!(
+                    "Rate limited by server: pausing for {} seconds",
+                    delay.as_secs()
+                );
+                time::sleep(delay).await;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Sets the limits and returns the hashes that don't have them afterwards. The API responds with
+/// 200 even if some of the hashes weren't updated, so the limits are read back.
+async fn set_and_verify<'a>(
     client: &qbittorrent::Client,
-    hash: &str,
-    torrent: &qbittorrent::Torrent,
-) -> Option<Result<(), qbittorrent::ClientError>> {
-    if let Some(rule) = config.rules.find(torrent) {
-        if rule.needs_update(torrent) {
-            log::info!(
-                "Applying matched rule to {}; ratio: {} => {}; total minutes: {} => {}",
-                torrent.name,
-                if torrent.max_ratio == -1.0 {
-                    Cow::from(UNLIMITED)
-                } else {
-                    Cow::from(torrent.max_ratio.to_string())
-                },
-                match rule.limits.ratio {
-                    Some(ratio) => Cow::from(ratio.to_string()),
-                    None => Cow::from(GLOBAL),
-                },
-                if torrent.max_seeding_time == -1 {
-                    Cow::from(UNLIMITED)
-                } else {
-                    Cow::from(torrent.max_seeding_time.to_string())
-                },
-                match rule.limits.minutes {
-                    Some(minutes) => Cow::from(minutes.to_string()),
-                    None => Cow::from(GLOBAL),
-                },
-            );
-            return Some(client.apply_rule_limits(hash, &rule.limits).await);
+    hashes: &[&'a str],
+    limits: &config::RuleLimits,
+) -> Result<Vec<&'a str>, qbittorrent::ClientError> {
+    retry_rate_limited(move || client.set_share_limits(hashes, limits)).await?;
+    if let Some(upload_ki_bps) = limits.upload_ki_bps {
+        retry_rate_limited(move || client.set_upload_limit(hashes, upload_ki_bps)).await?;
+    }
+    if let Some(download_ki_bps) = limits.download_ki_bps {
+        retry_rate_limited(move || client.set_download_limit(hashes, download_ki_bps)).await?;
+    }
+    let current = match retry_rate_limited(move || client.share_limits(hashes)).await {
+        Ok(current) => current,
+        Err(error @ qbittorrent::ClientError::RateLimited(_)) => return Err(error),
+        Err(error) => {
+            log::warn!("Could not verify share limits: {}", error);
+            return Ok(Vec::new());
         }
-    } else if torrent.is_limited() {
-        log::info!(
-            "Torrent {} is limited despite not being matched: setting to global limits",
-            torrent.name
+    };
+    let expected = limits.as_reported();
+    Ok(hashes
+        .iter()
+        .copied()
+        .filter(|hash| !current.get(*hash).is_some_and(|x| x.same_as(&expected)))
+        .collect())
+}
+
+/// Sets the share limits of the changes, batching torrents with the same target limits into a
+/// single request. Torrents that didn't receive the limits are retried individually; the ones
+/// that still fail are returned with the reason.
+async fn apply_limits<'a>(
+    client: &qbittorrent::Client,
+    changes: &[&plan::Change<'a>],
+) -> Result<HashMap<&'a str, String>, qbittorrent::ClientError> {
+    let mut failed = HashMap::new();
+    for batch in plan::batches(changes) {
+        log::debug!(
+            "Setting {} on {} torrents",
+            batch.limits,
+            batch.hashes.len()
         );
-        return Some(client.apply_global_limits(hash).await);
+        let retry = match set_and_verify(client, &batch.hashes, &batch.limits).await {
+            Ok(unverified) => unverified,
+            Err(error @ qbittorrent::ClientError::RateLimited(_)) => return Err(error),
+            Err(error) => {
+                log::warn!(
+                    "Couldn't set share limits of {} torrents: {}",
+                    batch.hashes.len(),
+                    error
+                );
+                batch.hashes.clone()
+            }
+        };
+        for hash in retry {
+            log::debug!("Retrying share limits of {}", hash);
+            match set_and_verify(client, &[hash], &batch.limits).await {
+                Ok(unverified) if unverified.is_empty() => {}
+                Ok(_) => {
+                    failed.insert(hash, "did not receive the new share limits".to_string());
+                }
+                Err(error @ qbittorrent::ClientError::RateLimited(_)) => return Err(error),
+                Err(error) => {
+                    failed.insert(hash, error.to_string());
+                }
+            }
+        }
     }
-    None
+    Ok(failed)
 }
 
-async fn run(
+/// Deletes the torrent or updates its category, location, super seeding, queue position,
+/// automatic management, sequential download, tags and trackers, rechecks or pauses it, and
+/// reports the change and runs the rule's command.
+/// Share limits are set beforehand by `apply_limits`. Each request is retried on its own while
+/// the server is rate limiting, as moving in the queue and toggling sequential download aren't
+/// idempotent and must not be repeated by retrying the whole change.
+async fn apply(
     config: &config::Config,
-    client: &mut qbittorrent::Client,
+    client: &qbittorrent::Client,
+    notifier: &notify::Notifier,
+    change: &plan::Change<'_>,
 ) -> Result<(), qbittorrent::ClientError> {
-    client.update().await?;
-    for (hash, torrent) in &client.torrents {
-        if let Some(result) = handle_torrent(config, client, hash, torrent).await {
-            match result {
-                Ok(()) => log::debug!("Successfully updated {}", hash),
-                Err(error) => log::warn!("Couldn't update {}: {:?}", hash, error),
+    let hashes = [change.hash];
+    let hashes = &hashes;
+    if let Some(action) = change.action {
+        let delete_files = action == config::Action::DeleteWithData;
+        retry_rate_limited(move || client.delete(hashes, delete_files)).await?;
+    }
+    if let Some(category) = change.category {
+        retry_rate_limited(move || client.set_category(hashes, category)).await?;
+    }
+    if let Some(location) = change.location {
+        retry_rate_limited(move || client.set_location(hashes, location)).await?;
+    }
+    if let Some(super_seeding) = change.super_seeding {
+        retry_rate_limited(move || client.set_super_seeding(hashes, super_seeding)).await?;
+    }
+    if change.recheck {
+        retry_rate_limited(move || client.recheck(hashes)).await?;
+    }
+    if let Some(queue) = change.queue {
+        retry_rate_limited(move || client.move_in_queue(hashes, queue)).await?;
+    }
+    if let Some(auto_tmm) = change.auto_tmm {
+        retry_rate_limited(move || client.set_auto_management(hashes, auto_tmm)).await?;
+    }
+    if !change.add_tags.is_empty() {
+        retry_rate_limited(move || client.add_tags(change.hash, &change.add_tags)).await?;
+    }
+    if !change.remove_tags.is_empty() {
+        retry_rate_limited(move || client.remove_tags(change.hash, &change.remove_tags)).await?;
+    }
+    if !change.add_trackers.is_empty() {
+        retry_rate_limited(move || client.add_trackers(change.hash, &change.add_trackers)).await?;
+    }
+    if !change.remove_trackers.is_empty() {
+        retry_rate_limited(move || client.remove_trackers(change.hash, &change.remove_trackers))
+            .await?;
+    }
+    if change.pause {
+        retry_rate_limited(move || client.pause(hashes)).await?;
+    }
+    if change.sequential_download.is_some() {
+        // The change is only planned when the target differs from the current state.
+        retry_rate_limited(move || client.toggle_sequential_download(hashes)).await?;
+    }
+    match change.rule {
+        Some((_, rule)) => {
+            let message = change.message(config.log_template.as_ref());
+            log::info!("{}", message);
+            if let Some(notify) = &rule.notify {
+                let event = notify::RuleEvent::new(change);
+                notifier.notify_rule(notify, &message, &event).await;
             }
-        };
+            if let Some(command) = change.exec {
+                exec::run(command, change).await;
+            }
+        }
+        None => {
+            match (&change.limits, &config.default_action) {
+                (Some(limits), config::DefaultAction::Limits(_)) => log::info!(
+                    "Torrent {} is not matched by any rule: set to the default limits of {}",
+                    change.torrent.name,
+                    limits
+                ),
+                (Some(_), _) => log::info!(
+                    "Torrent {} is limited despite not being matched: set to global limits",
+                    change.torrent.name
+                ),
+                (None, _) => {}
+            }
+            if !change.remove_tags.is_empty() {
+                log::info!(
+                    "Removed rule tags from unmatched torrent {}",
+                    change.torrent.name
+                );
+            }
+        }
     }
     Ok(())
 }
 
+/// State carried between cycles.
+#[derive(Default)]
+struct RunState {
+    /// Whether the change limit was exceeded on the previous cycle, to only alert once.
+    over_limit: bool,
+    /// Whether `maxChangesPerRun` held back changes on the previous cycle, to only alert once.
+    over_run_limit: bool,
+    startup: bool,
+    stats: Option<stats::Stats>,
+    status: Option<status::StatusFile>,
+    /// Asks before applying changes when running with `--confirm`.
+    confirmer: Option<confirm::Confirmer>,
+    /// Phase durations of the current cycle.
+    timings: timings::Timings,
+    /// Dry-run changes that have been logged by hash, to only log each change once.
+    dry_run_logged: HashMap<String, String>,
+    /// Start of the previous cycle, from which on scheduled rules are due.
+    last_cycle: Option<i64>,
+    /// Number of cycles that have synced successfully since startup.
+    cycle: u64,
+    /// Times until which rules with a `cooldown` don't change a torrent again, by rule index and
+    /// hash.
+    cooldowns: HashMap<(usize, String), i64>,
+    /// Rules that matched the torrents, reused for torrents that haven't changed since.
+    matches: plan::MatchCache,
+}
+
+impl RunState {
+    fn record_error(&mut self, message: String) {
+        if let Some(status) = &mut self.status {
+            status.record_error(message);
+        }
+    }
+
+    fn write_status(&mut self) {
+        if let Some(status) = &mut self.status {
+            if let Err(error) = status.write() {
+                log::warn!("Could not write state file: {}", error);
+            }
+        }
+    }
+}
+
+async fn run(
+    config: &config::Config,
+    client: &mut qbittorrent::Client,
+    notifier: &notify::Notifier,
+    state: &mut RunState,
+) -> Result<qbittorrent::SyncSummary, qbittorrent::ClientError> {
+    state.timings = timings::Timings::default();
+    let now = clock::now();
+    let start = Instant::now();
+    let summary = sync(config, client, state.cycle).await?;
+    state
+        .timings
+        .record("sync", start.elapsed().saturating_sub(summary.parse_time));
+    state.timings.record("parse", summary.parse_time);
+    if summary.added > 0 && !summary.full_update {
+        log::debug!("{} new torrents added", summary.added);
+    }
+    let start = Instant::now();
+    let changed = client.take_changed();
+    let plan = plan::Plan::incremental(config, &client.torrents, &mut state.matches, &changed);
+    state.timings.record("match", start.elapsed());
+    if state.startup {
+        let report = plan.report(&config.rules);
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+        if config.notifications.startup_report {
+            notifier.notify(&report).await;
+        }
+        state.startup = false;
+    }
+    if let Some(stats) = &mut state.stats {
+        if let Err(error) = stats.record_uploads(&plan) {
+            log::warn!("Could not record statistics: {}", error);
+        }
+    }
+    if let Some(status) = &mut state.status {
+        status.start_cycle(&plan, &config.rules);
+    }
+    let (mut dry_run, mut pending): (Vec<_>, Vec<_>) = plan.changes.iter().partition(|x| x.dry_run);
+    plan::drop_logged(&mut dry_run, &mut state.dry_run_logged);
+    for change in dry_run {
+        if change.action.is_some() {
+            log::warn!("Dry run: would apply matched rule to {}", change);
+        } else {
+            log::info!("Dry run: would apply matched rule to {}", change);
+        }
+    }
+    state.cooldowns.retain(|_, until| *until > now);
+    let cooling_down = plan::hold_cooling_down(&mut pending, &state.cooldowns, now);
+    if cooling_down > 0 {
+        log::debug!(
+            "Holding back {} changes of rules that recently changed the same torrents",
+            cooling_down
+        );
+    }
+    // On the first cycle, only rules scheduled for the current minute are due.
+    let last_cycle = state.last_cycle.replace(now).unwrap_or(now - 60);
+    let held = plan::hold_not_due(&mut pending, state.cycle, last_cycle, now);
+    if held > 0 {
+        log::debug!("Holding back {} changes until their rules' next run", held);
+    }
+    state.cycle += 1;
+    for ((index, rule), deferred) in plan::cap_per_rule(&mut pending) {
+        log::info!(
+            "Rule #{} changes at most {} torrents per cycle: {} changes left for later cycles",
+            index + 1,
+            rule.max_per_run.unwrap_or_default(),
+            deferred
+        );
+    }
+    if let Some(max_changes) = config.max_changes_per_cycle {
+        if pending.len() > max_changes {
+            let message = format!(
+                "Cycle would change {} torrents, more than the maximum of {}: not applying changes",
+                pending.len(),
+                max_changes
+            );
+            log::warn!("{}", message);
+            for change in &pending {
+                log::warn!("Would change {}", change);
+            }
+            if !state.over_limit {
+                notifier.notify(&message).await;
+                state.over_limit = true;
+            }
+            state.record_error(message);
+            state.write_status();
+            return Ok(summary);
+        }
+    }
+    state.over_limit = false;
+    if let Some(max_changes) = config.max_changes_per_run {
+        let deferred = plan::cap_changes(&mut pending, max_changes);
+        if deferred.is_empty() {
+            state.over_run_limit = false;
+        } else {
+            let message = format!(
+                "Cycle would change {} torrents, more than the maximum of {}: \
+                {} changes left for later cycles",
+                pending.len() + deferred.len(),
+                max_changes,
+                deferred.len()
+            );
+            log::warn!("{}", message);
+            for change in &deferred {
+                log::warn!("Left for later: {}", change);
+            }
+            if !state.over_run_limit {
+                notifier.notify(&message).await;
+                state.over_run_limit = true;
+            }
+        }
+    }
+    let deferred = plan::cap_deletions(&mut pending, config.max_deletions_per_cycle);
+    if deferred > 0 {
+        log::warn!(
+            "Deleting at most {} torrents per cycle: {} deletions left for later cycles",
+            config.max_deletions_per_cycle,
+            deferred
+        );
+    }
+    let changes = match &mut state.confirmer {
+        Some(confirmer) => match task::block_in_place(|| confirmer.select(&pending)) {
+            Ok(changes) => changes,
+            Err(error) => {
+                log::warn!("Could not read confirmation: {}", error);
+                Vec::new()
+            }
+        },
+        None => pending,
+    };
+    let client = &*client;
+    let start = Instant::now();
+    match apply_limits(client, &changes).await {
+        Ok(failed) => {
+            for (hash, reason) in &failed {
+                log::warn!("Couldn't update {}: {}", hash, reason);
+                state.record_error(format!("Couldn't update {}: {}", hash, reason));
+            }
+            for change in changes {
+                if failed.contains_key(change.hash) {
+                    continue;
+                }
+                match apply(config, client, notifier, change).await {
+                    Ok(()) => {
+                        log::debug!("Successfully updated {}", change.hash);
+                        if let Some(stats) = &state.stats {
+                            if let Err(error) = stats.record_change(change) {
+                                log::warn!("Could not record statistics: {}", error);
+                            }
+                        }
+                        if let Some(status) = &mut state.status {
+                            status.record_change(change);
+                        }
+                        if let Some((index, rule)) = change.rule {
+                            if let Some(cooldown) = rule.cooldown {
+                                state.cooldowns.insert(
+                                    (index, change.hash.to_string()),
+                                    now.saturating_add_unsigned(cooldown.seconds() as u64),
+                                );
+                            }
+                        }
+                    }
+                    Err(error @ qbittorrent::ClientError::RateLimited(_)) => {
+                        let message = format!("{}: skipping the remaining changes", error);
+                        log::warn!("{}", message);
+                        state.record_error(message);
+                        break;
+                    }
+                    Err(error) => {
+                        log::warn!("Couldn't update {}: {:?}", change.hash, error);
+                        state.record_error(format!("Couldn't update {}: {}", change.hash, error));
+                    }
+                }
+            }
+        }
+        Err(error) => {
+            let message = format!("{}: skipping changes", error);
+            log::warn!("{}", message);
+            state.record_error(message);
+        }
+    }
+    state.timings.record("apply", start.elapsed());
+    state.write_status();
+    Ok(summary)
+}
+
+fn load_config(path: &Path) -> Result<config::Config, ExitCode> {
+    log::debug!("Using configuration at {}", path.display());
+    match config::Config::load(path) {
+        Ok(config) => Ok(config),
+        Err(config::ConfigError::Deserialization(error)) => {
+            log::error!("Could not parse configuration file: {}", error);
+            Err(ExitCode::FAILURE)
+        }
+        Err(config::ConfigError::Invalid(error)) => {
+            log::error!("Invalid configuration: {}", error);
+            Err(ExitCode::FAILURE)
+        }
+        Err(config::ConfigError::Io(error)) => {
+            log::error!("Could not load configuration file: {}", error);
+            Err(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn open_stats(config: &config::Config) -> Result<Option<stats::Stats>, ExitCode> {
+    let Some(state_dir) = &config.state_dir else {
+        return Ok(None);
+    };
+    match stats::Stats::open(state_dir) {
+        Ok(stats) => Ok(Some(stats)),
+        Err(error) => {
+            log::error!("Could not open statistics database: {}", error);
+            Err(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn print_stats(path: &Path, days: u32) -> ExitCode {
+    let config = match load_config(path) {
+        Ok(config) => config,
+        Err(exit_code) => return exit_code,
+    };
+    let stats = match open_stats(&config) {
+        Ok(Some(stats)) => stats,
+        Ok(None) => {
+            log::error!("Configuration does not set stateDir");
+            return ExitCode::FAILURE;
+        }
+        Err(exit_code) => return exit_code,
+    };
+    match stats.report(days) {
+        Ok(report) => {
+            print!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            log::error!("Could not read statistics: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_tests(path: &Path) -> ExitCode {
+    let config = match load_config(path) {
+        Ok(config) => config,
+        Err(exit_code) => return exit_code,
+    };
+    if config.tests.is_empty() {
+        log::error!("Configuration does not contain any tests");
+        return ExitCode::FAILURE;
+    }
+    if fixtures::run(&config) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
 #[tokio::main]
 async fn main() -> ExitCode {
     SimpleLogger::new()
@@ -88,23 +640,73 @@ async fn main() -> ExitCode {
 
     let cli = Cli::parse();
 
-    log::debug!("Using configuration at {}", cli.config.display());
-    let mut config = match config::Config::load(&cli.config) {
+    if cli.discover {
+        return if discover::discover().await {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+    }
+
+    match &cli.command {
+        Some(Command::Stats { config, days }) => return print_stats(config, *days),
+        Some(Command::Test { config }) => return run_tests(config),
+        None => {}
+    }
+
+    if cli.confirm && !std::io::stdin().is_terminal() {
+        log::error!("--confirm requires an interactive terminal");
+        return ExitCode::FAILURE;
+    }
+
+    let Some(config_path) = &cli.config else {
+        unreachable!(
+            "clap requires the configuration path unless discovering or running a subcommand"
+        );
+    };
+    let mut config = match load_config(config_path) {
         Ok(config) => config,
-        Err(config::ConfigError::Deserialization(error)) => {
-            log::error!("Could not parse configuration file: {}", error);
-            return ExitCode::FAILURE;
-        }
-        Err(config::ConfigError::Io(error)) => {
-            log::error!("Could not load configuration file: {}", error);
-            return ExitCode::FAILURE;
-        }
+        Err(exit_code) => return exit_code,
     };
     log::info!("Loaded configuration with {} rules", &config.rules.len());
     for (i, rule) in config.rules.iter().enumerate() {
-        log::info!("Rule #{}: {}", i + 1, rule);
+        if rule.is_enabled() {
+            log::info!("Rule #{}: {}", i + 1, rule);
+        } else {
+            log::info!("Rule #{} is disabled: {}", i + 1, rule);
+        }
+    }
+    if !config.ignore.is_empty() {
+        log::info!("Ignoring torrents with {}", config.ignore);
     }
 
+    let notifier = match notify::Notifier::new(&config.notifications) {
+        Ok(notifier) => notifier,
+        Err(error) => {
+            log::error!("HTTP client error: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut tunnel = match config.tunnel.take() {
+        Some(tunnel_config) => {
+            let local_port = url::Url::parse(&config.server.address)
+                .ok()
+                .and_then(|x| x.port_or_known_default());
+            let Some(local_port) = local_port else {
+                log::error!("Configuration did not contain a valid base URL");
+                return ExitCode::FAILURE;
+            };
+            let mut tunnel = tunnel::Tunnel::new(tunnel_config, local_port);
+            if let Err(error) = tunnel.ensure().await {
+                log::error!("Could not open SSH tunnel: {}", error);
+                return ExitCode::FAILURE;
+            }
+            Some(tunnel)
+        }
+        None => None,
+    };
+
     let mut client = match qbittorrent::Client::new(std::mem::take(&mut config.server)) {
         Ok(client) => client,
         Err(error) => {
@@ -132,32 +734,84 @@ async fn main() -> ExitCode {
         }
     };
 
+    if cli.dry_run {
+        return match dry_run(&config, &mut client, cli.format).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                log::error!("Could not evaluate rules: {:?}", error);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let stats = match open_stats(&config) {
+        Ok(stats) => stats,
+        Err(exit_code) => return exit_code,
+    };
+
     let forever = task::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(60));
+        let mut poller = polling::Poller::new(config.polling.clone());
+        let mut state = RunState {
+            startup: true,
+            stats,
+            status: config.state_file.clone().map(status::StatusFile::new),
+            confirmer: cli.confirm.then(confirm::Confirmer::default),
+            ..Default::default()
+        };
 
         loop {
-            interval.tick().await;
-            if let Err(error) = run(&config, &mut client).await {
-                match error {
-                    qbittorrent::ClientError::Authentication => {
-                        log::warn!("No permission to access server");
-                        match client.login().await {
-                            Ok(()) => log::info!("Reauthenticated"),
-                            Err(error) => {
-                                log::error!("{}", error);
-                                return ExitCode::FAILURE;
-                            }
-                        };
-                    }
-                    qbittorrent::ClientError::InvalidUrl => {
-                        log::error!("Configuration did not contain a valid base URL")
-                    }
-                    qbittorrent::ClientError::Reqwest(reqwest_error) => {
-                        log::error!("HTTP client error: {}", reqwest_error)
-                    }
-                    _ => log::warn!("Unknown error while updating"),
+            if let Some(tunnel) = &mut tunnel {
+                if let Err(error) = tunnel.ensure().await {
+                    log::error!("Could not open SSH tunnel: {}", error);
+                }
+            }
+            if client.session_expiring() {
+                match client.login().await {
+                    Ok(()) => log::debug!("Renewed session"),
+                    Err(error) => log::warn!("Could not renew session: {}", error),
+                }
+            }
+            let mut retry_after = None;
+            let summary = match run(&config, &mut client, &notifier, &mut state).await {
+                Ok(summary) => Some(summary),
+                Err(error) => {
+                    state.record_error(error.to_string());
+                    state.write_status();
+                    match error {
+                        qbittorrent::ClientError::Authentication => {
+                            log::warn!("No permission to access server");
+                            match client.login().await {
+                                Ok(()) => log::info!("Reauthenticated"),
+                                Err(error) => {
+                                    log::error!("{}", error);
+                                    return ExitCode::FAILURE;
+                                }
+                            };
+                        }
+                        qbittorrent::ClientError::InvalidUrl => {
+                            log::error!("Configuration did not contain a valid base URL")
+                        }
+                        qbittorrent::ClientError::Reqwest(reqwest_error) => {
+                            log::error!("HTTP client error: {}", reqwest_error)
+                        }
+                        qbittorrent::ClientError::RateLimited(delay) => {
+                            log::warn!(
+                                "Rate limited by server: pausing for {} seconds",
+                                delay.as_secs()
+                            );
+                            retry_after = Some(delay);
+                        }
+                        _ => log::warn!("Unknown error while updating"),
+                    };
+                    None
                 }
             };
+            let latencies = client.take_latencies();
+            if cli.timings {
+                log::info!("{}", state.timings.report(latencies));
+            }
+            let interval = poller.next(summary.as_ref(), Instant::now());
+            time::sleep(retry_after.map_or(interval, |x| x.max(interval))).await;
         }
     });
 
@@ -0,0 +1,212 @@
+use crate::{config, plan, qbittorrent};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+#[derive(Debug)]
+pub enum NotificationError {
+    Request(reqwest::Error),
+    Status(reqwest::StatusCode),
+}
+
+impl fmt::Display for NotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Request(reqwest_error) => write!(f, "HTTP client error: {}", reqwest_error),
+            Self::Status(status) => write!(f, "Server responded with {}", status),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TelegramMessage<'a> {
+    chat_id: &'a str,
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct WebhookMessage<'a> {
+    message: &'a str,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    event: Option<&'a RuleEvent<'a>>,
+}
+
+/// Details of a rule being applied to a torrent, which webhooks receive along with the message.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleEvent<'a> {
+    rule: String,
+    hash: &'a str,
+    name: &'a str,
+    category: &'a str,
+    old_limits: EventLimits,
+    /// `None` if the change doesn't set limits.
+    new_limits: Option<EventLimits>,
+}
+
+impl<'a> RuleEvent<'a> {
+    pub fn new(change: &'a plan::Change) -> Self {
+        let torrent = change.torrent;
+        Self {
+            rule: change
+                .rule
+                .map(|(i, rule)| rule.name(i).into_owned())
+                .unwrap_or_default(),
+            hash: change.hash,
+            name: &torrent.name,
+            category: &torrent.category,
+            old_limits: EventLimits {
+                ratio: torrent.max_ratio,
+                minutes: torrent.max_seeding_time,
+                inactive_minutes: torrent.max_inactive_seeding_time,
+            },
+            new_limits: change.limits.as_ref().map(|limits| EventLimits {
+                ratio: limits.ratio.unwrap_or(-2.0),
+                minutes: limits.minutes.unwrap_or(-2),
+                inactive_minutes: limits.inactive_minutes.unwrap_or(-2),
+            }),
+        }
+    }
+}
+
+/// Share limits as qBittorrent writes them, with -1 for unlimited and -2 for the global limit.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct EventLimits {
+    ratio: qbittorrent::Ratio,
+    minutes: qbittorrent::MaxSeedingTime,
+    inactive_minutes: qbittorrent::MaxSeedingTime,
+}
+
+pub struct Notifier {
+    channels: BTreeMap<String, config::Channel>,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(config: &config::NotificationConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder().build()?;
+        Ok(Self {
+            channels: config.channels.clone(),
+            client,
+        })
+    }
+
+    /// Sends the message to every configured channel. Failures are logged and otherwise ignored.
+    pub async fn notify(&self, message: &str) {
+        for (name, channel) in &self.channels {
+            self.notify_channel(name, channel, message, None).await;
+        }
+    }
+
+    /// Sends the message to the channels selected by a rule's notification setting. Webhooks also
+    /// receive the details of the change.
+    pub async fn notify_rule(
+        &self,
+        setting: &config::Notify,
+        message: &str,
+        event: &RuleEvent<'_>,
+    ) {
+        let names = match setting {
+            config::Notify::Enabled(true) => self.channels.keys().cloned().collect(),
+            config::Notify::Enabled(false) => Vec::new(),
+            config::Notify::Channels(names) => names.clone(),
+            config::Notify::Target(url) if config::is_webhook_url(url) => {
+                let channel = config::Channel::Webhook { url: url.clone() };
+                self.notify_channel(url, &channel, message, Some(event))
+                    .await;
+                return;
+            }
+            config::Notify::Target(name) => vec![name.clone()],
+        };
+        for name in names {
+            if let Some(channel) = self.channels.get(&name) {
+                self.notify_channel(&name, channel, message, Some(event))
+                    .await;
+            }
+        }
+    }
+
+    async fn notify_channel(
+        &self,
+        name: &str,
+        channel: &config::Channel,
+        message: &str,
+        event: Option<&RuleEvent<'_>>,
+    ) {
+        match self.send(channel, message, event).await {
+            Ok(()) => log::debug!("Sent notification to {}", name),
+            Err(error) => log::warn!("Could not send notification to {}: {}", name, error),
+        }
+    }
+
+    async fn send(
+        &self,
+        channel: &config::Channel,
+        message: &str,
+        event: Option<&RuleEvent<'_>>,
+    ) -> Result<(), NotificationError> {
+        let request = match channel {
+            config::Channel::Telegram { bot_token, chat_id } => self
+                .client
+                .post(format!("{}/bot{}/sendMessage", TELEGRAM_API, bot_token))
+                .json(&TelegramMessage {
+                    chat_id,
+                    text: message,
+                }),
+            config::Channel::Webhook { url } => self
+                .client
+                .post(url)
+                .json(&WebhookMessage { message, event }),
+        };
+        let response = request.send().await.map_err(NotificationError::Request)?;
+        if !response.status().is_success() {
+            return Err(NotificationError::Status(response.status()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_message() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {id: alien, category: Alien, limits: {ratio: 2.0}, notify: https://localhost/hook}",
+        )
+        .unwrap();
+        let torrent = qbittorrent::Torrent {
+            name: "Alien".to_string(),
+            category: "Alien".to_string(),
+            max_ratio: -1.0,
+            max_seeding_time: -1,
+            max_inactive_seeding_time: -2,
+            ..Default::default()
+        };
+        let torrents = qbittorrent::TorrentMap::from([("abc".to_string(), torrent)]);
+        let plan = plan::Plan::new(&config, &torrents);
+        let event = RuleEvent::new(&plan.changes[0]);
+        let message = WebhookMessage {
+            message: "Applied",
+            event: Some(&event),
+        };
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "message": "Applied",
+                "rule": "alien",
+                "hash": "abc",
+                "name": "Alien",
+                "category": "Alien",
+                "oldLimits": {"ratio": -1.0, "minutes": -1, "inactiveMinutes": -2},
+                "newLimits": {"ratio": 2.0, "minutes": -2, "inactiveMinutes": -2},
+            })
+        );
+    }
+}
@@ -0,0 +1,1792 @@
+use crate::config;
+use crate::qbittorrent;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Write};
+
+/// Index and rule that a torrent matched.
+pub type MatchedRule<'a> = (usize, &'a config::Rule);
+
+/// A pending modification to a single torrent.
+pub struct Change<'a> {
+    pub hash: &'a str,
+    pub torrent: &'a qbittorrent::Torrent,
+    /// Matched rule, or `None` if the torrent didn't match any rule.
+    pub rule: Option<MatchedRule<'a>>,
+    /// Limits to set; unset share limits fall back to the global limits.
+    pub limits: Option<config::RuleLimits>,
+    /// Action to take on the torrent instead of setting limits.
+    pub action: Option<config::Action>,
+    /// Category to move the torrent to.
+    pub category: Option<&'a str>,
+    /// Directory to move the torrent's data to.
+    pub location: Option<&'a str>,
+    /// Whether to enable or disable super seeding.
+    pub super_seeding: Option<bool>,
+    /// Whether to recheck the torrent's data.
+    pub recheck: bool,
+    /// Where to move the torrent in the queue.
+    pub queue: Option<config::QueueMove>,
+    /// Whether to enable or disable Automatic Torrent Management.
+    pub auto_tmm: Option<bool>,
+    /// Whether to download the torrent in sequential order.
+    pub sequential_download: Option<bool>,
+    /// Whether to pause the torrent.
+    pub pause: bool,
+    /// Whether the matched rule only logs its changes.
+    pub dry_run: bool,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    /// Announce URLs to add to the torrent.
+    pub add_trackers: Vec<&'a str>,
+    /// Announce URLs to remove from the torrent.
+    pub remove_trackers: Vec<&'a str>,
+    /// Command to run once the change is applied.
+    pub exec: Option<&'a config::ExecCommand>,
+}
+
+fn current_limit<T: PartialEq + ToString>(value: T, unlimited: T) -> Cow<'static, str> {
+    if value == unlimited {
+        Cow::from(crate::UNLIMITED)
+    } else {
+        Cow::from(value.to_string())
+    }
+}
+
+impl<'a> Change<'a> {
+    /// Message logged when the change is applied, formatted with the template if one is set.
+    pub fn message(&self, template: Option<&config::Template>) -> String {
+        let Some(template) = template else {
+            return format!("Applying matched rule to {}", self);
+        };
+        let torrent = self.torrent;
+        let limits = self.limits.clone().unwrap_or_default();
+        template.render(|field| match field {
+            config::TemplateField::Category => torrent.category.clone(),
+            config::TemplateField::Hash => self.hash.to_string(),
+            config::TemplateField::Name => torrent.name.clone(),
+            config::TemplateField::NewMinutes => config::format_limit(limits.minutes).into_owned(),
+            config::TemplateField::NewRatio => config::format_limit(limits.ratio).into_owned(),
+            config::TemplateField::OldMinutes => {
+                current_limit(torrent.max_seeding_time, -1).into_owned()
+            }
+            config::TemplateField::OldRatio => current_limit(torrent.max_ratio, -1.0).into_owned(),
+            config::TemplateField::Rule => match self.rule {
+                Some((i, rule)) => rule.name(i).into_owned(),
+                None => String::new(),
+            },
+        })
+    }
+
+    pub fn new(
+        hash: &'a str,
+        torrent: &'a qbittorrent::Torrent,
+        rule: Option<MatchedRule<'a>>,
+    ) -> Self {
+        Self {
+            hash,
+            torrent,
+            rule,
+            limits: None,
+            action: None,
+            category: None,
+            location: None,
+            super_seeding: None,
+            recheck: false,
+            queue: None,
+            auto_tmm: None,
+            sequential_download: None,
+            pause: false,
+            dry_run: rule.is_some_and(|(_, rule)| rule.dry_run == Some(true)),
+            add_tags: Vec::new(),
+            remove_tags: Vec::new(),
+            add_trackers: Vec::new(),
+            remove_trackers: Vec::new(),
+            exec: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.limits.is_none()
+            && self.action.is_none()
+            && self.category.is_none()
+            && self.location.is_none()
+            && self.super_seeding.is_none()
+            && !self.recheck
+            && self.queue.is_none()
+            && self.auto_tmm.is_none()
+            && self.sequential_download.is_none()
+            && !self.pause
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+            && self.add_trackers.is_empty()
+            && self.remove_trackers.is_empty()
+    }
+
+    /// Adds the rule tag for `rule_tag` and removes any other rule tags from the torrent.
+    fn annotate(&mut self, rule_tag: Option<String>) {
+        self.remove_tags = self
+            .torrent
+            .tags
+            .iter()
+            .filter(|&x| x.starts_with(crate::RULE_TAG_PREFIX) && Some(x) != rule_tag.as_ref())
+            .cloned()
+            .collect();
+        if let Some(rule_tag) = rule_tag {
+            if !self.torrent.tags.contains(&rule_tag) {
+                self.add_tags.push(rule_tag);
+            }
+        }
+    }
+
+    /// Adds the tags that the torrent doesn't have yet.
+    fn add_missing_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if !self.torrent.tags.contains(tag) && !self.add_tags.contains(tag) {
+                self.add_tags.push(tag.clone());
+            }
+        }
+    }
+
+    /// Removes the tags that the torrent has.
+    fn remove_present_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if self.torrent.tags.contains(tag) && !self.remove_tags.contains(tag) {
+                self.remove_tags.push(tag.clone());
+            }
+        }
+    }
+
+    /// Individual field modifications, for reviewing changes without applying them.
+    pub fn diff(&self) -> Vec<DiffEntry> {
+        let torrent = self.torrent;
+        let current_ratio = current_limit(torrent.max_ratio, -1.0);
+        let current_minutes = current_limit(torrent.max_seeding_time, -1);
+        let entry = |field, current: Cow<str>, target: Cow<str>| DiffEntry {
+            hash: self.hash.to_string(),
+            name: torrent.name.clone(),
+            field,
+            current: current.into_owned(),
+            target: target.into_owned(),
+            rule: self.rule.map(|(i, rule)| rule.name(i).into_owned()),
+        };
+        let mut entries = Vec::new();
+        if let Some(action) = self.action {
+            entries.push(entry(
+                "action",
+                Cow::from("-"),
+                Cow::from(action.to_string()),
+            ));
+        }
+        if let Some(limits) = &self.limits {
+            // Rules only set the limits they define, while unmatched torrents are reset.
+            let reset = self.rule.is_none();
+            if limits.ratio.map_or(reset, |x| {
+                config::limit_differs(x, torrent.max_ratio, torrent.ratio_limit, -2.0)
+            }) {
+                entries.push(entry(
+                    "ratio",
+                    current_ratio,
+                    config::format_limit(limits.ratio),
+                ));
+            }
+            if limits.minutes.map_or(reset, |x| {
+                config::limit_differs(x, torrent.max_seeding_time, torrent.seeding_time_limit, -2)
+            }) {
+                entries.push(entry(
+                    "minutes",
+                    current_minutes,
+                    config::format_limit(limits.minutes),
+                ));
+            }
+            // Only qBittorrent 4.6 and later report inactive limits, which are global otherwise.
+            let current_inactive = torrent.max_inactive_seeding_time;
+            if limits
+                .inactive_minutes
+                .map_or(reset && current_inactive != -2, |x| {
+                    config::limit_differs(
+                        x,
+                        current_inactive,
+                        torrent.inactive_seeding_time_limit,
+                        -2,
+                    )
+                })
+            {
+                entries.push(entry(
+                    "inactive minutes",
+                    current_limit(current_inactive, -1),
+                    config::format_limit(limits.inactive_minutes),
+                ));
+            }
+            if let Some(upload_ki_bps) = limits.upload_ki_bps {
+                if upload_ki_bps != torrent.upload_limit() {
+                    entries.push(entry(
+                        "upload limit",
+                        config::format_speed_limit(torrent.upload_limit()),
+                        config::format_speed_limit(upload_ki_bps),
+                    ));
+                }
+            }
+            if let Some(download_ki_bps) = limits.download_ki_bps {
+                if download_ki_bps != torrent.download_limit() {
+                    entries.push(entry(
+                        "download limit",
+                        config::format_speed_limit(torrent.download_limit()),
+                        config::format_speed_limit(download_ki_bps),
+                    ));
+                }
+            }
+        }
+        if let Some(category) = self.category {
+            entries.push(entry(
+                "category",
+                Cow::from(torrent.category.as_str()),
+                Cow::from(category),
+            ));
+        }
+        if let Some(location) = self.location {
+            entries.push(entry(
+                "location",
+                Cow::from(torrent.save_path.as_str()),
+                Cow::from(location),
+            ));
+        }
+        if let Some(super_seeding) = self.super_seeding {
+            entries.push(entry(
+                "super seeding",
+                Cow::from(torrent.super_seeding.to_string()),
+                Cow::from(super_seeding.to_string()),
+            ));
+        }
+        if self.recheck {
+            entries.push(entry("action", Cow::from("-"), Cow::from("recheck")));
+        }
+        if let Some(queue) = self.queue {
+            entries.push(entry(
+                "queue position",
+                Cow::from(torrent.priority.to_string()),
+                Cow::from(format!("move {}", queue)),
+            ));
+        }
+        if let Some(auto_tmm) = self.auto_tmm {
+            entries.push(entry(
+                "auto tmm",
+                Cow::from(torrent.auto_tmm.to_string()),
+                Cow::from(auto_tmm.to_string()),
+            ));
+        }
+        if let Some(sequential_download) = self.sequential_download {
+            entries.push(entry(
+                "sequential download",
+                Cow::from(torrent.seq_dl.to_string()),
+                Cow::from(sequential_download.to_string()),
+            ));
+        }
+        if self.pause {
+            entries.push(entry("action", Cow::from("-"), Cow::from("pause")));
+        }
+        if !self.add_tags.is_empty() || !self.remove_tags.is_empty() {
+            let tags: Vec<&str> = torrent
+                .tags
+                .iter()
+                .filter(|&x| !self.remove_tags.contains(x))
+                .chain(&self.add_tags)
+                .map(String::as_str)
+                .collect();
+            entries.push(entry(
+                "tags",
+                Cow::from(torrent.tags.to_string()),
+                Cow::from(format!("[{}]", tags.join(", "))),
+            ));
+        }
+        if !self.add_trackers.is_empty() || !self.remove_trackers.is_empty() {
+            let current: Vec<&str> = torrent.trackers.iter().map(|x| x.url.as_str()).collect();
+            let trackers: Vec<&str> = current
+                .iter()
+                .filter(|&x| !self.remove_trackers.contains(x))
+                .chain(&self.add_trackers)
+                .copied()
+                .collect();
+            entries.push(entry(
+                "trackers",
+                Cow::from(format!("[{}]", current.join(", "))),
+                Cow::from(format!("[{}]", trackers.join(", "))),
+            ));
+        }
+        entries
+    }
+}
+
+impl fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let torrent = self.torrent;
+        write!(f, "{}", torrent.name)?;
+        match self.action {
+            Some(config::Action::Delete) => write!(f, "; deleting")?,
+            Some(config::Action::DeleteWithData) => write!(f, "; deleting with data")?,
+            None => {}
+        }
+        if let Some(limits) = &self.limits {
+            // Only unmatched torrents are reset, as rules don't plan changes without limits.
+            if *limits == config::RuleLimits::default() {
+                write!(f, "; reset to global limits")?;
+            } else {
+                write!(
+                    f,
+                    "; ratio: {} => {}; total minutes: {} => {}",
+                    current_limit(torrent.max_ratio, -1.0),
+                    config::format_limit(limits.ratio),
+                    current_limit(torrent.max_seeding_time, -1),
+                    config::format_limit(limits.minutes),
+                )?;
+                if let Some(inactive_minutes) = limits.inactive_minutes {
+                    write!(
+                        f,
+                        "; inactive minutes: {} => {}",
+                        current_limit(torrent.max_inactive_seeding_time, -1),
+                        config::format_limit(Some(inactive_minutes))
+                    )?;
+                }
+                if let Some(upload_ki_bps) = limits.upload_ki_bps {
+                    write!(
+                        f,
+                        "; upload limit: {} => {}",
+                        config::format_speed_limit(torrent.upload_limit()),
+                        config::format_speed_limit(upload_ki_bps)
+                    )?;
+                }
+                if let Some(download_ki_bps) = limits.download_ki_bps {
+                    write!(
+                        f,
+                        "; download limit: {} => {}",
+                        config::format_speed_limit(torrent.download_limit()),
+                        config::format_speed_limit(download_ki_bps)
+                    )?;
+                }
+            }
+        }
+        if let Some(category) = self.category {
+            write!(f, "; category: {} => {}", torrent.category, category)?;
+        }
+        if let Some(location) = self.location {
+            write!(f, "; location: {} => {}", torrent.save_path, location)?;
+        }
+        if let Some(super_seeding) = self.super_seeding {
+            write!(
+                f,
+                "; super seeding: {} => {}",
+                torrent.super_seeding, super_seeding
+            )?;
+        }
+        if self.recheck {
+            write!(f, "; rechecking")?;
+        }
+        if let Some(queue) = self.queue {
+            write!(f, "; moving {} from position {}", queue, torrent.priority)?;
+        }
+        if let Some(auto_tmm) = self.auto_tmm {
+            write!(f, "; auto tmm: {} => {}", torrent.auto_tmm, auto_tmm)?;
+        }
+        if let Some(sequential_download) = self.sequential_download {
+            write!(
+                f,
+                "; sequential download: {} => {}",
+                torrent.seq_dl, sequential_download
+            )?;
+        }
+        if self.pause {
+            write!(f, "; pausing")?;
+        }
+        if !self.add_tags.is_empty() {
+            write!(f, "; adding tags: {}", self.add_tags.join(", "))?;
+        }
+        if !self.remove_tags.is_empty() {
+            write!(f, "; removing tags: {}", self.remove_tags.join(", "))?;
+        }
+        if !self.add_trackers.is_empty() {
+            write!(f, "; adding trackers: {}", self.add_trackers.join(", "))?;
+        }
+        if !self.remove_trackers.is_empty() {
+            write!(
+                f,
+                "; removing trackers: {}",
+                self.remove_trackers.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Single field modification of a torrent.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct DiffEntry {
+    pub hash: String,
+    pub name: String,
+    pub field: &'static str,
+    pub current: String,
+    pub target: String,
+    /// Matched rule, or `None` if the torrent is reset to global limits.
+    pub rule: Option<String>,
+}
+
+/// Formats the entries as a plain text table with aligned columns.
+pub fn diff_table(entries: &[DiffEntry]) -> String {
+    let header = ["HASH", "NAME", "FIELD", "CURRENT", "TARGET", "RULE"];
+    let rows: Vec<[&str; 6]> = entries
+        .iter()
+        .map(|x| {
+            [
+                x.hash.as_str(),
+                x.name.as_str(),
+                x.field,
+                x.current.as_str(),
+                x.target.as_str(),
+                x.rule.as_deref().unwrap_or("-"),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(|x| x.chars().count());
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(column, width)| format!("{:width$}", column, width = width))
+            .collect();
+        let _ = writeln!(table, "{}", line.join("  ").trim_end());
+    }
+    table
+}
+
+/// Torrents that are set to the same share limits with a single request.
+#[derive(Debug, PartialEq)]
+pub struct Batch<'a> {
+    pub limits: config::RuleLimits,
+    pub hashes: Vec<&'a str>,
+}
+
+/// Groups the changes that modify share limits by their target limits.
+pub fn batches<'a>(changes: &[&Change<'a>]) -> Vec<Batch<'a>> {
+    let mut batches: Vec<Batch> = Vec::new();
+    for change in changes {
+        let Some(limits) = &change.limits else {
+            continue;
+        };
+        match batches.iter_mut().find(|batch| &batch.limits == limits) {
+            Some(batch) => batch.hashes.push(change.hash),
+            None => batches.push(Batch {
+                limits: limits.clone(),
+                hashes: vec![change.hash],
+            }),
+        }
+    }
+    batches
+}
+
+/// Keeps the first `max` changes and returns the ones left for later cycles.
+pub fn cap_changes<'a, 'b>(changes: &mut Vec<&'b Change<'a>>, max: usize) -> Vec<&'b Change<'a>> {
+    changes.split_off(max.min(changes.len()))
+}
+
+/// Keeps at most `max` of the changes that delete torrents and returns how many were left for
+/// later cycles. Other changes are kept.
+pub fn cap_deletions<'a, 'b>(changes: &mut Vec<&'b Change<'a>>, max: usize) -> usize {
+    let mut deletions = 0;
+    changes.retain(|change| {
+        if change.action.is_none() {
+            return true;
+        }
+        deletions += 1;
+        deletions <= max
+    });
+    deletions.saturating_sub(max)
+}
+
+/// Drops the dry-run changes that were already logged as they are, and returns how many were
+/// dropped. `logged` maps hashes to the changes last logged for them; torrents without a dry-run
+/// change in the cycle are forgotten, so their changes are logged again if they come back.
+pub fn drop_logged(changes: &mut Vec<&Change>, logged: &mut HashMap<String, String>) -> usize {
+    let before = changes.len();
+    let previous = std::mem::take(logged);
+    changes.retain(|change| {
+        let rendered = change.to_string();
+        let new = previous.get(change.hash) != Some(&rendered);
+        logged.insert(change.hash.to_string(), rendered);
+        new
+    });
+    before - changes.len()
+}
+
+/// Holds back the changes of rules that aren't due in the cycle, and returns how many were held
+/// back. See `Rule::is_due`.
+pub fn hold_not_due(changes: &mut Vec<&Change>, cycle: u64, from: i64, to: i64) -> usize {
+    let before = changes.len();
+    changes.retain(|change| {
+        change
+            .rule
+            .is_none_or(|(_, rule)| rule.is_due(cycle, from, to))
+    });
+    before - changes.len()
+}
+
+/// Holds back the changes of rules that changed the same torrent within their `cooldown`, and
+/// returns how many were held back. `cooldowns` maps rule indices and hashes to the time until
+/// which the rule is cooling down for the torrent.
+pub fn hold_cooling_down(
+    changes: &mut Vec<&Change>,
+    cooldowns: &HashMap<(usize, String), i64>,
+    now: i64,
+) -> usize {
+    let before = changes.len();
+    changes.retain(|change| {
+        change.rule.is_none_or(|(index, _)| {
+            cooldowns
+                .get(&(index, change.hash.to_string()))
+                .is_none_or(|until| *until <= now)
+        })
+    });
+    before - changes.len()
+}
+
+/// Keeps at most `maxPerRun` changes of each rule that sets it and returns the rules that had
+/// changes left for later cycles, with the number of those changes.
+pub fn cap_per_rule<'a, 'b>(changes: &mut Vec<&'b Change<'a>>) -> Vec<(MatchedRule<'a>, usize)> {
+    let mut counts: BTreeMap<usize, (&'a config::Rule, usize, usize)> = BTreeMap::new();
+    changes.retain(|change| {
+        let Some((index, rule)) = change.rule else {
+            return true;
+        };
+        let Some(max) = rule.max_per_run else {
+            return true;
+        };
+        let (_, count, _) = counts.entry(index).or_insert((rule, 0, max));
+        *count += 1;
+        *count <= max
+    });
+    counts
+        .into_iter()
+        .filter(|(_, (_, count, max))| count > max)
+        .map(|(index, (rule, count, max))| ((index, rule), count - max))
+        .collect()
+}
+
+/// Share limits of the layered rules, each filling in the limits that the earlier ones don't set.
+pub fn layered_limits(
+    layers: &[MatchedRule],
+    torrent: &qbittorrent::Torrent,
+) -> Option<config::RuleLimits> {
+    layers
+        .iter()
+        .filter_map(|(_, rule)| rule.resolved_limits(torrent))
+        .reduce(config::RuleLimits::or)
+}
+
+/// First value of an action that the layered rules set.
+fn layered<'a, T>(
+    layers: &[MatchedRule<'a>],
+    action: impl Fn(&'a config::Actions) -> Option<T>,
+) -> Option<T> {
+    layers.iter().find_map(|(_, rule)| action(&rule.actions))
+}
+
+/// First non-empty list of an action that the layered rules set.
+fn layered_list<'a, T>(
+    layers: &[MatchedRule<'a>],
+    action: impl Fn(&'a config::Actions) -> &'a [T],
+) -> &'a [T] {
+    layered(layers, |x| Some(action(x)).filter(|x| !x.is_empty())).unwrap_or_default()
+}
+
+/// Matches the cross-seeds of torrents matched by a rule with `applyToCrossSeeds` to the same
+/// rule. If several such rules match torrents with the same content, the first rule admitting
+/// the cross-seed wins. Cross-seeds keep an earlier rule or a rule pinning their hash.
+fn apply_to_cross_seeds<'a>(
+    evaluated: &mut [(&'a str, &'a qbittorrent::Torrent, Option<MatchedRule<'a>>)],
+) {
+    let mut rules: HashMap<&str, Vec<MatchedRule>> = HashMap::new();
+    for &(_, torrent, matched) in evaluated.iter() {
+        let Some((index, rule)) = matched else {
+            continue;
+        };
+        if rule.apply_to_cross_seeds != Some(true) || torrent.cross_seeds == 0 {
+            continue;
+        }
+        let rules = rules.entry(&torrent.content_path).or_default();
+        if let Err(position) = rules.binary_search_by_key(&index, |x| x.0) {
+            rules.insert(position, (index, rule));
+        }
+    }
+    for (hash, torrent, matched) in evaluated.iter_mut() {
+        let Some(rules) = rules.get(torrent.content_path.as_str()) else {
+            continue;
+        };
+        if matched.is_some_and(|(_, rule)| rule.pins(hash)) {
+            continue;
+        }
+        let before = matched.map_or(usize::MAX, |(index, _)| index);
+        if let Some(&rule) = rules
+            .iter()
+            .take_while(|(index, _)| *index < before)
+            .find(|(_, rule)| rule.admits(hash))
+        {
+            *matched = Some(rule);
+        }
+    }
+}
+
+/// Outcome of evaluating the rules against every known torrent.
+pub struct Plan<'a> {
+    pub changes: Vec<Change<'a>>,
+    /// Every torrent that wasn't ignored, with the rule that matched it.
+    pub evaluated: Vec<(&'a str, &'a qbittorrent::Torrent, Option<MatchedRule<'a>>)>,
+    pub ignored: usize,
+    pub matches: Vec<usize>,
+    pub total: usize,
+    pub unmatched: usize,
+}
+
+/// Rules that matched the torrents on earlier cycles, by hash, to avoid matching torrents that
+/// haven't changed against every rule again.
+#[derive(Default)]
+pub struct MatchCache {
+    matched: HashMap<String, Option<usize>>,
+}
+
+impl<'a> Plan<'a> {
+    pub fn new(config: &'a config::Config, torrents: &'a qbittorrent::TorrentMap) -> Self {
+        Self::build(config, torrents, |hash, torrent| {
+            config
+                .rules
+                .find(hash, torrent, config.managed_tag.as_deref())
+        })
+    }
+
+    /// Plans the changes like `new`, but torrents that aren't in `changed` keep the rule that
+    /// matched them on an earlier cycle, unless a rule up to that one can match differently as
+    /// time passes. Layered rules are still matched on every cycle.
+    pub fn incremental(
+        config: &'a config::Config,
+        torrents: &'a qbittorrent::TorrentMap,
+        cache: &mut MatchCache,
+        changed: &HashSet<String>,
+    ) -> Self {
+        cache.matched.retain(|hash, _| torrents.contains_key(hash));
+        let clock = config.rules.first_using_clock();
+        let mut reused = 0;
+        let plan = Self::build(config, torrents, |hash, torrent| {
+            if !changed.contains(hash) {
+                if let Some(&index) = cache.matched.get(hash) {
+                    if clock.is_none_or(|clock| index.is_some_and(|i| i < clock)) {
+                        reused += 1;
+                        return index.and_then(|i| Some((i, config.rules.get(i)?)));
+                    }
+                }
+            }
+            let matched = config
+                .rules
+                .find(hash, torrent, config.managed_tag.as_deref());
+            cache
+                .matched
+                .insert(hash.to_string(), matched.map(|(i, _)| i));
+            matched
+        });
+        log::trace!("Reused the matches of {} unchanged torrents", reused);
+        plan
+    }
+
+    fn build(
+        config: &'a config::Config,
+        torrents: &'a qbittorrent::TorrentMap,
+        mut find: impl FnMut(&str, &qbittorrent::Torrent) -> Option<MatchedRule<'a>>,
+    ) -> Self {
+        let mut plan = Self {
+            changes: Vec::new(),
+            evaluated: Vec::with_capacity(torrents.len()),
+            ignored: 0,
+            matches: vec![0; config.rules.len()],
+            total: torrents.len(),
+            unmatched: 0,
+        };
+        for (hash, torrent) in torrents {
+            if config.ignore.matches(hash, torrent) {
+                log::trace!("Ignoring {}", torrent.name);
+                plan.ignored += 1;
+                continue;
+            }
+            plan.evaluated.push((hash, torrent, find(hash, torrent)));
+        }
+        apply_to_cross_seeds(&mut plan.evaluated);
+        let last_position = torrents.values().map(|x| x.priority).max().unwrap_or(0);
+        for &(hash, torrent, matched) in &plan.evaluated {
+            let mut change = Change::new(hash, torrent, matched);
+            let mut add_tags: &[String] = &[];
+            let mut remove_tags: &[String] = &[];
+            if let Some((index, rule)) = matched {
+                plan.matches[index] += 1;
+                if rule.action.is_some() {
+                    // Deleted torrents need neither limits nor rule tags.
+                    change.action = rule.action;
+                    plan.changes.push(change);
+                    continue;
+                }
+                let layers = config.rules.layers(
+                    (index, rule),
+                    hash,
+                    torrent,
+                    config.managed_tag.as_deref(),
+                );
+                // Layered rules can add destructive actions, so any of them being in dry-run
+                // mode holds back the whole change.
+                change.dry_run = layers.iter().any(|(_, rule)| rule.dry_run == Some(true));
+                change.limits =
+                    layered_limits(&layers, torrent).filter(|x| x.differs_from(torrent));
+                change.category = layered(&layers, |x| x.set_category.as_deref())
+                    .filter(|&x| x != torrent.category);
+                change.location = layered(&layers, |x| x.set_location.as_deref()).filter(|&x| {
+                    x.trim_end_matches('/') != torrent.save_path.trim_end_matches('/')
+                });
+                change.super_seeding =
+                    layered(&layers, |x| x.super_seeding).filter(|&x| x != torrent.super_seeding);
+                // Torrents that are already being checked don't need another recheck.
+                change.recheck = layers.iter().any(|(_, rule)| rule.actions.recheck)
+                    && !torrent.state.starts_with("checking");
+                // Unqueued torrents can't be moved, and ones already at the end don't move further.
+                change.queue = layered(&layers, |x| x.queue).filter(|&x| {
+                    let end = match x {
+                        config::QueueMove::Top | config::QueueMove::Up => 1,
+                        config::QueueMove::Bottom | config::QueueMove::Down => last_position,
+                    };
+                    torrent.priority > 0 && torrent.priority != end
+                });
+                change.auto_tmm =
+                    layered(&layers, |x| x.auto_tmm).filter(|&x| x != torrent.auto_tmm);
+                change.sequential_download =
+                    layered(&layers, |x| x.sequential_download).filter(|&x| x != torrent.seq_dl);
+                change.pause = layers.iter().any(|(_, rule)| rule.actions.pause)
+                    && !qbittorrent::is_paused(&torrent.state);
+                change.add_trackers = layered_list(&layers, |x| &x.add_trackers)
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|&url| !torrent.trackers.iter().any(|x| x.url == url))
+                    .collect();
+                let remove_trackers = layered_list(&layers, |x| &x.remove_trackers);
+                change.remove_trackers = torrent
+                    .trackers
+                    .iter()
+                    .map(|x| x.url.as_str())
+                    .filter(|&url| remove_trackers.iter().any(|x| x.matches_ignore_case(url)))
+                    .collect();
+                change.exec = layered(&layers, |x| x.exec.as_ref());
+                add_tags = layered_list(&layers, |x| &x.add_tags);
+                remove_tags = layered_list(&layers, |x| &x.remove_tags);
+            } else {
+                plan.unmatched += 1;
+                change.limits = match &config.default_action {
+                    config::DefaultAction::ResetToGlobal => {
+                        torrent.is_limited().then(config::RuleLimits::default)
+                    }
+                    config::DefaultAction::LeaveAlone => None,
+                    config::DefaultAction::Limits(limits) => {
+                        Some(limits.clone()).filter(|x| x.differs_from(torrent))
+                    }
+                };
+            }
+            if config.rule_tags {
+                change.annotate(
+                    matched.map(|(i, rule)| format!("{}{}", crate::RULE_TAG_PREFIX, rule.name(i))),
+                );
+            }
+            change.add_missing_tags(add_tags);
+            change.remove_present_tags(remove_tags);
+            if let Some(tag) = &config.managed_tag {
+                match matched {
+                    Some(_) => change.add_missing_tags(std::slice::from_ref(tag)),
+                    None => change.remove_present_tags(std::slice::from_ref(tag)),
+                }
+            }
+            if !change.is_empty() {
+                plan.changes.push(change);
+            }
+        }
+        plan
+    }
+
+    pub fn diff(&self) -> Vec<DiffEntry> {
+        let mut entries: Vec<DiffEntry> = self.changes.iter().flat_map(Change::diff).collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name).then(a.hash.cmp(&b.hash)));
+        entries
+    }
+
+    fn changes_for(&self, index: Option<usize>) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.rule.map(|(i, _)| i) == index)
+            .count()
+    }
+
+    /// Summary of how many torrents each rule matches and how many of them will be changed.
+    pub fn report(&self, rules: &config::RuleList) -> String {
+        let mut report = format!(
+            "Evaluated {} torrents ({} ignored), {} to be changed",
+            self.total,
+            self.ignored,
+            self.changes.len()
+        );
+        for (i, rule) in rules.iter().enumerate() {
+            if !rule.is_enabled() {
+                let _ = write!(report, "\nRule #{} ({}): disabled", i + 1, rule);
+                continue;
+            }
+            let _ = write!(
+                report,
+                "\nRule #{} ({}): {} matched, {} to be changed",
+                i + 1,
+                rule,
+                self.matches[i],
+                self.changes_for(Some(i)),
+            );
+        }
+        let _ = write!(
+            report,
+            "\nNo matching rule: {} torrents, {} to be changed",
+            self.unmatched,
+            self.changes_for(None),
+        );
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn torrent(category: &str, max_ratio: qbittorrent::Ratio) -> qbittorrent::Torrent {
+        qbittorrent::Torrent {
+            category: category.to_string(),
+            max_inactive_seeding_time: -2,
+            max_ratio,
+            max_seeding_time: -1,
+            ..Default::default()
+        }
+    }
+
+    fn tagged(category: &str, tags: &str) -> qbittorrent::Torrent {
+        qbittorrent::Torrent {
+            tags: qbittorrent::TagList::from(tags.to_string()),
+            ..torrent(category, -1.0)
+        }
+    }
+
+    #[test]
+    fn test_batches() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Alien, limits: {ratio: 2.0}}\n\
+            - {category: Ghost, limits: {ratio: 2.0}}\n\
+            - {category: Turbo, limits: {ratio: 3.0}}",
+        )
+        .unwrap();
+        let torrents = [
+            ("a", torrent("Alien", 1.0)),
+            ("b", torrent("Ghost", 1.0)),
+            ("c", torrent("Turbo", 1.0)),
+            ("d", torrent("", 1.0)),
+            ("e", tagged("Alien", "")),
+        ];
+        let changes: Vec<Change> = torrents
+            .iter()
+            .map(|(hash, torrent)| {
+                let matched = config
+                    .rules
+                    .find(hash, torrent, config.managed_tag.as_deref());
+                let mut change = Change::new(hash, torrent, matched);
+                change.limits = match matched {
+                    Some(matched) => {
+                        layered_limits(&[matched], torrent).filter(|x| x.differs_from(torrent))
+                    }
+                    None => Some(config::RuleLimits::default()),
+                };
+                change
+            })
+            .collect();
+        let changes: Vec<&Change> = changes.iter().collect();
+        let ratio = |ratio| config::RuleLimits {
+            ratio: Some(ratio),
+            minutes: None,
+            inactive_minutes: None,
+            upload_ki_bps: None,
+            download_ki_bps: None,
+        };
+        assert_eq!(
+            batches(&changes),
+            vec![
+                Batch {
+                    limits: ratio(2.0),
+                    hashes: vec!["a", "b", "e"],
+                },
+                Batch {
+                    limits: ratio(3.0),
+                    hashes: vec!["c"],
+                },
+                Batch {
+                    limits: config::RuleLimits::default(),
+                    hashes: vec!["d"],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drop_logged() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules: [{category: Alien, dryRun: true, limits: {ratio: 2.0}}]",
+        )
+        .unwrap();
+        let mut torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", -1.0)),
+            ("b".to_string(), torrent("Alien", -1.0)),
+        ]);
+        let mut logged = HashMap::new();
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        assert_eq!(drop_logged(&mut changes, &mut logged), 0);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        assert_eq!(drop_logged(&mut changes, &mut logged), 2);
+        assert!(changes.is_empty());
+
+        // The change of a is different now, and b is no longer planned.
+        torrents.get_mut("a").unwrap().max_ratio = 1.0;
+        torrents.get_mut("b").unwrap().max_ratio = 2.0;
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        assert_eq!(drop_logged(&mut changes, &mut logged), 0);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(logged.keys().collect::<Vec<_>>(), ["a"]);
+
+        // b is logged again once it comes back.
+        torrents.get_mut("b").unwrap().max_ratio = -1.0;
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        assert_eq!(drop_logged(&mut changes, &mut logged), 1);
+        assert_eq!(changes.iter().map(|x| x.hash).collect::<Vec<_>>(), ["b"]);
+    }
+
+    #[test]
+    fn test_new_dry_run() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Alien, dryRun: true, limits: {ratio: 2.0}}\n\
+            - {category: Ghost, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", 1.0)),
+            ("b".to_string(), torrent("Ghost", 1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut dry_run: Vec<_> = plan.changes.iter().map(|x| (x.hash, x.dry_run)).collect();
+        dry_run.sort();
+        assert_eq!(dry_run, vec![("a", true), ("b", false)]);
+    }
+
+    #[test]
+    fn test_new() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            ignore: {categories: [Manual]}\n\
+            rules:\n\
+            - {category: Alien, limits: {ratio: 2.0}}\n\
+            - {category: Ghost, limits: {ratio: 3.0}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", 2.0)),
+            ("b".to_string(), torrent("Alien", 1.0)),
+            ("c".to_string(), torrent("Ghost", 1.0)),
+            ("d".to_string(), torrent("", 1.0)),
+            ("e".to_string(), torrent("", -1.0)),
+            ("f".to_string(), torrent("Manual", 1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        assert_eq!(plan.total, 6);
+        assert_eq!(plan.ignored, 1);
+        assert_eq!(plan.matches, vec![2, 1]);
+        assert_eq!(plan.unmatched, 2);
+        assert_eq!(plan.changes_for(Some(0)), 1);
+        assert_eq!(plan.changes_for(Some(1)), 1);
+        assert_eq!(plan.changes_for(None), 1);
+    }
+
+    #[test]
+    fn test_new_cross_seeds() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Private, applyToCrossSeeds: true, limits: {ratio: 5.0}}\n\
+            - {category: Public, limits: {ratio: 1.0}}",
+        )
+        .unwrap();
+        let cross_seed = |category, content_path: &str| qbittorrent::Torrent {
+            content_path: content_path.to_string(),
+            cross_seeds: 1,
+            ..torrent(category, 1.0)
+        };
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), cross_seed("Private", "/data/Show")),
+            ("b".to_string(), cross_seed("Public", "/data/Show")),
+            ("c".to_string(), cross_seed("Public", "/data/Movie")),
+            ("d".to_string(), cross_seed("", "/data/Movie")),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut matched: Vec<_> = plan
+            .evaluated
+            .iter()
+            .map(|(hash, _, matched)| (*hash, matched.map(|(i, _)| i)))
+            .collect();
+        matched.sort();
+        assert_eq!(
+            matched,
+            vec![("a", Some(0)), ("b", Some(0)), ("c", Some(1)), ("d", None)]
+        );
+        assert_eq!(plan.matches, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_new_cross_seeds_keep_own_rule() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Public, limits: {ratio: 1.0}}\n\
+            - {category: Private, applyToCrossSeeds: true, excludeHashes: [c], limits: {ratio: 5.0}}\n\
+            - {category: Pinned, hashes: [d], limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let cross_seed = |category| qbittorrent::Torrent {
+            content_path: "/data/Show".to_string(),
+            cross_seeds: 3,
+            ..torrent(category, 1.0)
+        };
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), cross_seed("Private")),
+            ("b".to_string(), cross_seed("Public")),
+            ("c".to_string(), cross_seed("")),
+            ("d".to_string(), cross_seed("")),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut matched: Vec<_> = plan
+            .evaluated
+            .iter()
+            .map(|(hash, _, matched)| (*hash, matched.map(|(i, _)| i)))
+            .collect();
+        matched.sort();
+        assert_eq!(
+            matched,
+            vec![("a", Some(1)), ("b", Some(0)), ("c", None), ("d", Some(2))]
+        );
+    }
+
+    #[test]
+    fn test_new_rule_tags() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            ruleTags: true\n\
+            rules:\n\
+            - {id: alien, category: Alien, limits: {}}\n\
+            - {category: Ghost, limits: {}}\n\
+            - {tags: [keep], limits: {}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), tagged("Alien", "")),
+            ("b".to_string(), tagged("Ghost", "jeanne:alien")),
+            ("c".to_string(), tagged("", "keep, jeanne:rule-3")),
+            ("d".to_string(), tagged("", "jeanne:rule-3, other")),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| (x.hash, x.add_tags.clone(), x.remove_tags.clone()))
+            .collect();
+        changes.sort();
+        assert_eq!(
+            changes,
+            vec![
+                ("a", vec!["jeanne:alien".to_string()], vec![]),
+                (
+                    "b",
+                    vec!["jeanne:rule-2".to_string()],
+                    vec!["jeanne:alien".to_string()]
+                ),
+                ("d", vec![], vec!["jeanne:rule-3".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rule_tags_untagged() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            ruleTags: true\n\
+            rules:\n\
+            - {untagged: true, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let mut torrents = qbittorrent::TorrentMap::from([("a".to_string(), tagged("", ""))]);
+        let plan = Plan::new(&config, &torrents);
+        assert_eq!(plan.changes[0].add_tags, ["jeanne:rule-1"]);
+        // The next cycle sees the torrent with the rule tag and its new limits.
+        let torrent = torrents.get_mut("a").unwrap();
+        torrent.tags = qbittorrent::TagList::from("jeanne:rule-1".to_string());
+        torrent.max_ratio = 2.0;
+        let plan = Plan::new(&config, &torrents);
+        assert_eq!(plan.matches, [1]);
+        assert!(plan.changes.is_empty());
+    }
+
+    #[test]
+    fn test_managed_tag() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            managedTag: jeanne\n\
+            rules:\n\
+            - {category: Alien, limits: {}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), tagged("Alien", "")),
+            ("b".to_string(), tagged("Alien", "jeanne")),
+            ("c".to_string(), tagged("", "jeanne, other")),
+            ("d".to_string(), tagged("", "")),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| (x.hash, x.add_tags.clone(), x.remove_tags.clone()))
+            .collect();
+        changes.sort();
+        assert_eq!(
+            changes,
+            vec![
+                ("a", vec!["jeanne".to_string()], vec![]),
+                ("c", vec![], vec!["jeanne".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_managed_tag_stable() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            managedTag: jeanne\n\
+            rules:\n\
+            - {tags: [foo], limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let mut torrents = qbittorrent::TorrentMap::from([("a".to_string(), tagged("", "foo"))]);
+        let plan = Plan::new(&config, &torrents);
+        assert_eq!(plan.changes[0].add_tags, ["jeanne"]);
+        // The next cycle sees the torrent with the managed tag and its new limits.
+        let torrent = torrents.get_mut("a").unwrap();
+        torrent.tags = qbittorrent::TagList::from("foo, jeanne".to_string());
+        torrent.max_ratio = 2.0;
+        let plan = Plan::new(&config, &torrents);
+        assert_eq!(plan.matches, [1]);
+        assert!(plan.changes.is_empty());
+    }
+
+    #[test]
+    fn test_diff_inactive_minutes() {
+        let torrent = qbittorrent::Torrent {
+            max_inactive_seeding_time: 60,
+            ..torrent("Alien", 2.0)
+        };
+        let mut change = Change::new("abc", &torrent, None);
+        change.limits = Some(config::RuleLimits {
+            inactive_minutes: Some(120),
+            ..Default::default()
+        });
+        let fields: Vec<_> = change
+            .diff()
+            .into_iter()
+            .map(|x| (x.field, x.current, x.target))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("ratio", "2".to_string(), "global".to_string()),
+                ("minutes", "unlimited".to_string(), "global".to_string()),
+                ("inactive minutes", "60".to_string(), "120".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_speed_limits() {
+        let torrent = qbittorrent::Torrent {
+            up_limit: -1,
+            dl_limit: 2048,
+            ..torrent("Alien", 2.0)
+        };
+        let rule: config::Rule =
+            serde_yaml::from_str("limits: {ratio: 2.0, uploadKiBps: 512, downloadKiBps: 2}")
+                .unwrap();
+        let mut change = Change::new("abc", &torrent, Some((0, &rule)));
+        change.limits = layered_limits(&[(0, &rule)], &torrent);
+        let fields: Vec<_> = change
+            .diff()
+            .into_iter()
+            .map(|x| (x.field, x.current, x.target))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![(
+                "upload limit",
+                "unlimited".to_string(),
+                "512 KiB/s".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_incremental() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Alien, limits: {ratio: 2.0}}\n\
+            - {age: '>1d', limits: {ratio: 3.0}}",
+        )
+        .unwrap();
+        let new = qbittorrent::Torrent {
+            added_on: crate::clock::now(),
+            ..torrent("Ghost", -1.0)
+        };
+        let mut torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", -1.0)),
+            ("b".to_string(), new),
+        ]);
+        let mut cache = MatchCache::default();
+        let changed = HashSet::from(["a".to_string(), "b".to_string()]);
+        let plan = Plan::incremental(&config, &torrents, &mut cache, &changed);
+        assert_eq!(plan.matches, [1, 0]);
+        // Torrents that aren't reported as changed keep their earlier match, unless a rule up
+        // to it depends on the time.
+        torrents.get_mut("a").unwrap().category = "Ghost".to_string();
+        torrents.get_mut("b").unwrap().added_on = 0;
+        let plan = Plan::incremental(&config, &torrents, &mut cache, &HashSet::new());
+        assert_eq!(plan.matches, [1, 1]);
+        let changed = HashSet::from(["a".to_string()]);
+        let plan = Plan::incremental(&config, &torrents, &mut cache, &changed);
+        assert_eq!(plan.matches, [0, 2]);
+    }
+
+    #[test]
+    fn test_incremental_completed_age() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {completedAge: '>1d', limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let recent = qbittorrent::Torrent {
+            completion_on: crate::clock::now() - 3600,
+            ..torrent("Alien", -1.0)
+        };
+        let mut torrents = qbittorrent::TorrentMap::from([("a".to_string(), recent)]);
+        let mut cache = MatchCache::default();
+        let changed = HashSet::from(["a".to_string()]);
+        let plan = Plan::incremental(&config, &torrents, &mut cache, &changed);
+        assert_eq!(plan.matches, [0]);
+        // The torrent crosses the threshold without qBittorrent reporting it as changed.
+        torrents.get_mut("a").unwrap().completion_on = crate::clock::now() - 2 * 86_400;
+        let plan = Plan::incremental(&config, &torrents, &mut cache, &HashSet::new());
+        assert_eq!(plan.matches, [1]);
+    }
+
+    #[test]
+    fn test_incremental_limit_reached() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {limitReached: true, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        // Stopped with an inactive seeding time limit of an hour, inactive for half of it.
+        let stopped = qbittorrent::Torrent {
+            state: "stoppedUP".to_string(),
+            max_inactive_seeding_time: 60,
+            max_seeding_time: -1,
+            last_activity: crate::clock::now() - 1800,
+            ..torrent("Alien", -1.0)
+        };
+        let mut torrents = qbittorrent::TorrentMap::from([("a".to_string(), stopped)]);
+        let mut cache = MatchCache::default();
+        let changed = HashSet::from(["a".to_string()]);
+        let plan = Plan::incremental(&config, &torrents, &mut cache, &changed);
+        assert_eq!(plan.matches, [0]);
+        // The torrent reaches the limit without qBittorrent reporting it as changed.
+        torrents.get_mut("a").unwrap().last_activity = crate::clock::now() - 7200;
+        let plan = Plan::incremental(&config, &torrents, &mut cache, &HashSet::new());
+        assert_eq!(plan.matches, [1]);
+    }
+
+    #[test]
+    fn test_cap_changes() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Alien, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", -1.0)),
+            ("b".to_string(), torrent("Alien", -1.0)),
+            ("c".to_string(), torrent("Alien", -1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        assert_eq!(cap_changes(&mut changes, 2).len(), 1);
+        assert_eq!(changes.len(), 2);
+        assert!(cap_changes(&mut changes, 5).is_empty());
+        assert_eq!(changes.len(), 2);
+    }
+
+    #[test]
+    fn test_cap_deletions() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            allowDestructive: true\n\
+            rules:\n\
+            - {category: Alien, action: delete}\n\
+            - {category: Ghost, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", -1.0)),
+            ("b".to_string(), torrent("Alien", -1.0)),
+            ("c".to_string(), torrent("Alien", -1.0)),
+            ("d".to_string(), torrent("Ghost", -1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        assert_eq!(cap_deletions(&mut changes, 2), 1);
+        let deletions = changes.iter().filter(|x| x.action.is_some()).count();
+        assert_eq!(deletions, 2);
+        assert!(changes.iter().any(|x| x.hash == "d"));
+    }
+
+    #[test]
+    fn test_hold_not_due() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Alien, runSchedule: '0 23 * * *', limits: {ratio: 2.0}}\n\
+            - {category: Ghost, runSchedule: '0 3 * * *', limits: {ratio: 2.0}}\n\
+            - {category: Turbo, limits: {ratio: 2.0}}\n\
+            - {category: Heavy, interval: 2, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", -1.0)),
+            ("b".to_string(), torrent("Ghost", -1.0)),
+            ("c".to_string(), torrent("Turbo", -1.0)),
+            ("d".to_string(), torrent("Heavy", -1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        // Saturday 2026-10-17 from 22:30 to 23:30 UTC.
+        assert_eq!(
+            hold_not_due(&mut changes, 0, 1_792_276_200, 1_792_279_800),
+            1
+        );
+        let mut hashes: Vec<_> = changes.iter().map(|x| x.hash).collect();
+        hashes.sort();
+        assert_eq!(hashes, ["a", "c", "d"]);
+        assert_eq!(
+            hold_not_due(&mut changes, 3, 1_792_276_200, 1_792_279_800),
+            1
+        );
+        assert!(changes.iter().all(|x| x.hash != "d"));
+    }
+
+    #[test]
+    fn test_hold_cooling_down() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Alien, cooldown: 1h, limits: {ratio: 2.0}}\n\
+            - {category: Ghost, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", -1.0)),
+            ("b".to_string(), torrent("Alien", -1.0)),
+            ("c".to_string(), torrent("Ghost", -1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let cooldowns = HashMap::from([((0, "a".to_string()), 3600), ((0, "b".to_string()), 60)]);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        assert_eq!(hold_cooling_down(&mut changes, &cooldowns, 60), 1);
+        let mut hashes: Vec<_> = changes.iter().map(|x| x.hash).collect();
+        hashes.sort();
+        assert_eq!(hashes, ["b", "c"]);
+    }
+
+    #[test]
+    fn test_cap_per_rule() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: Alien, maxPerRun: 2, limits: {ratio: 2.0}}\n\
+            - {category: Ghost, limits: {ratio: 2.0}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", -1.0)),
+            ("b".to_string(), torrent("Alien", -1.0)),
+            ("c".to_string(), torrent("Alien", -1.0)),
+            ("d".to_string(), torrent("Ghost", -1.0)),
+            ("e".to_string(), torrent("Ghost", -1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<&Change> = plan.changes.iter().collect();
+        let deferred = cap_per_rule(&mut changes);
+        let deferred: Vec<_> = deferred.iter().map(|((i, _), n)| (*i, *n)).collect();
+        assert_eq!(deferred, [(0, 1)]);
+        let matched = |index| {
+            changes
+                .iter()
+                .filter(|x| x.rule.is_some_and(|x| x.0 == index))
+                .count()
+        };
+        assert_eq!(matched(0), 2);
+        assert_eq!(matched(1), 2);
+    }
+
+    #[test]
+    fn test_continue() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: tv, continue: true, limits: {ratio: 2.0}, actions: {addTags: [tv]}}\n\
+            - {category: Alien, limits: {ratio: 20.0}}\n\
+            - {limits: {ratio: 5.0, minutes: 600}, actions: {addTags: [old], setCategory: old}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), tagged("tv", "")),
+            ("b".to_string(), tagged("movies", "")),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| {
+                let limits = x.limits.clone().unwrap_or_default();
+                (
+                    x.hash,
+                    x.rule.map(|(i, _)| i),
+                    limits.ratio,
+                    limits.minutes,
+                    x.add_tags.clone(),
+                    x.category,
+                )
+            })
+            .collect();
+        changes.sort_by_key(|x| x.0);
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    "a",
+                    Some(0),
+                    Some(2.0),
+                    Some(600),
+                    vec!["tv".to_string()],
+                    Some("old")
+                ),
+                (
+                    "b",
+                    Some(2),
+                    Some(5.0),
+                    Some(600),
+                    vec!["old".to_string()],
+                    Some("old")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_continue_dry_run() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: tv, continue: true, limits: {ratio: 2.0}}\n\
+            - {category: tv, dryRun: true, actions: {pause: true}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([("a".to_string(), tagged("tv", ""))]);
+        let plan = Plan::new(&config, &torrents);
+        assert!(plan.changes[0].pause);
+        assert!(plan.changes[0].dry_run);
+    }
+
+    #[test_case("resetToGlobal", Some(None) ; "reset")]
+    #[test_case("leaveAlone", None ; "leave alone")]
+    #[test_case("{ratio: 2.0}", Some(Some(2.0)) ; "limits")]
+    #[test_case("{ratio: 1.0}", None ; "same limits")]
+    fn test_default_action(default_action: &str, expected: Option<Option<f64>>) {
+        let config: config::Config = serde_yaml::from_str(&format!(
+            "server: {{address: http://localhost}}\n\
+            defaultAction: {}\n\
+            rules: [{{category: Alien, limits: {{ratio: 2.0}}}}]",
+            default_action
+        ))
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([("a".to_string(), torrent("Ghost", 1.0))]);
+        let plan = Plan::new(&config, &torrents);
+        let limits = plan.changes.first().and_then(|x| x.limits.clone());
+        assert_eq!(limits.map(|x| x.ratio), expected);
+    }
+
+    #[test]
+    fn test_set_category() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: tv, actions: {setCategory: tv-archive}}\n\
+            - {category: tv-archive, actions: {setCategory: tv-archive}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("tv", -1.0)),
+            ("b".to_string(), torrent("tv-archive", -1.0)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        assert_eq!(plan.changes.len(), 1);
+        assert_eq!(
+            plan.diff(),
+            vec![DiffEntry {
+                hash: "a".to_string(),
+                name: String::new(),
+                field: "category",
+                current: "tv".to_string(),
+                target: "tv-archive".to_string(),
+                rule: Some("rule-1".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_add_tags() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            ruleTags: true\n\
+            rules:\n\
+            - {id: expired, category: tv, actions: {addTags: [expired, seen]}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), tagged("tv", "seen")),
+            (
+                "b".to_string(),
+                tagged("tv", "expired, jeanne:expired, seen"),
+            ),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| (x.hash, x.add_tags.clone()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![(
+                "a",
+                vec!["jeanne:expired".to_string(), "expired".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_remove_tags() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - {category: tv, actions: {addTags: [expired], removeTags: [expiring, new]}}",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), tagged("tv", "expiring, seen")),
+            ("b".to_string(), tagged("tv", "expired")),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| (x.hash, x.add_tags.clone(), x.remove_tags.clone()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![(
+                "a",
+                vec!["expired".to_string()],
+                vec!["expiring".to_string()]
+            )]
+        );
+    }
+
+    #[test_case("setLocation: /archive/tv", |x, done| x.save_path = if done { "/archive/tv/" } else { "/fast/tv" }.to_string(), "location: /fast/tv => /archive/tv" ; "set location")]
+    #[test_case("superSeeding: false", |x, done| x.super_seeding = !done, "super seeding: true => false" ; "super seeding")]
+    #[test_case("recheck: true", |x, done| x.state = if done { "checkingUP" } else { "error" }.to_string(), "rechecking" ; "recheck")]
+    #[test_case("queue: top", |x, done| x.priority = if done { 1 } else { 3 }, "moving to the top of the queue from position 3" ; "queue top")]
+    #[test_case("queue: up", |x, done| x.priority = if done { 1 } else { 3 }, "moving up in the queue from position 3" ; "queue up")]
+    #[test_case("queue: bottom", |x, done| x.priority = if done { 3 } else { 1 }, "moving to the bottom of the queue from position 1" ; "queue bottom")]
+    #[test_case("queue: down", |x, done| x.priority = if done { 3 } else { 1 }, "moving down in the queue from position 1" ; "queue down")]
+    #[test_case("autoTmm: true", |x, done| x.auto_tmm = done, "auto tmm: false => true" ; "auto tmm")]
+    #[test_case("sequentialDownload: true", |x, done| x.seq_dl = done, "sequential download: false => true" ; "sequential download")]
+    fn test_action(action: &str, setup: fn(&mut qbittorrent::Torrent, bool), expected: &str) {
+        let config: config::Config = serde_yaml::from_str(&format!(
+            "server: {{address: http://localhost}}\n\
+            allowDestructive: true\n\
+            rules:\n\
+            - {{category: tv, actions: {{{}}}}}",
+            action
+        ))
+        .unwrap();
+        let torrent = |done| {
+            let mut torrent = torrent("tv", -1.0);
+            setup(&mut torrent, done);
+            torrent
+        };
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent(false)),
+            ("b".to_string(), torrent(true)),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| (x.hash, x.to_string()))
+            .collect();
+        assert_eq!(changes, vec![("a", format!("; {}", expected))]);
+    }
+
+    #[test]
+    fn test_limits_and_actions() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            allowDestructive: true\n\
+            rules:\n\
+            - {category: tv, limits: {ratio: 2.0}, actions: {addTags: [done], pause: true}}",
+        )
+        .unwrap();
+        let in_state = |state: &str| qbittorrent::Torrent {
+            state: state.to_string(),
+            ..torrent("tv", -1.0)
+        };
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), in_state("uploading")),
+            ("b".to_string(), in_state("stoppedUP")),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let mut changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| (x.hash, x.limits.is_some(), x.add_tags.clone(), x.pause))
+            .collect();
+        changes.sort();
+        let done = vec!["done".to_string()];
+        assert_eq!(
+            changes,
+            vec![("a", true, done.clone(), true), ("b", true, done, false)]
+        );
+    }
+
+    #[test]
+    fn test_edit_trackers() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules:\n\
+            - category: tv\n  \
+              actions:\n    \
+                addTrackers: [https://backup.example/announce]\n    \
+                removeTrackers: ['*://DEAD.example/*']",
+        )
+        .unwrap();
+        let with_trackers = |urls: &[&str]| qbittorrent::Torrent {
+            trackers: urls
+                .iter()
+                .map(|&url| qbittorrent::Tracker {
+                    url: url.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..torrent("tv", -1.0)
+        };
+        let torrents = qbittorrent::TorrentMap::from([
+            (
+                "a".to_string(),
+                with_trackers(&["udp://dead.example/announce", "https://live.example/a"]),
+            ),
+            (
+                "b".to_string(),
+                with_trackers(&["https://backup.example/announce"]),
+            ),
+        ]);
+        let plan = Plan::new(&config, &torrents);
+        let changes: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|x| (x.hash, x.add_trackers.clone(), x.remove_trackers.clone()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![(
+                "a",
+                vec!["https://backup.example/announce"],
+                vec!["udp://dead.example/announce"]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_diff_table() {
+        let entries = vec![
+            DiffEntry {
+                hash: "abc".to_string(),
+                name: "Okarun".to_string(),
+                field: "ratio",
+                current: "1".to_string(),
+                target: "2".to_string(),
+                rule: Some("#1".to_string()),
+            },
+            DiffEntry {
+                hash: "def".to_string(),
+                name: "Momo".to_string(),
+                field: "minutes",
+                current: "unlimited".to_string(),
+                target: "global".to_string(),
+                rule: None,
+            },
+        ];
+        assert_eq!(
+            diff_table(&entries),
+            "HASH  NAME    FIELD    CURRENT    TARGET  RULE\n\
+            abc   Okarun  ratio    1          2       #1\n\
+            def   Momo    minutes  unlimited  global  -\n"
+        );
+    }
+}
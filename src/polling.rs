@@ -0,0 +1,191 @@
+use crate::config;
+use crate::qbittorrent;
+use std::time::{Duration, Instant};
+
+/// Decides how long to wait between syncs.
+pub struct Poller {
+    config: config::PollingConfig,
+    /// Use the new torrent interval until this point in time.
+    fast_until: Option<Instant>,
+    /// Number of consecutive syncs that contained no torrent changes.
+    idle: u32,
+    /// Current interval in seconds, backed off from the base interval while idle.
+    interval: u64,
+    /// Whether the latest sync was the immediate one after new torrents were added.
+    immediate: bool,
+}
+
+impl Poller {
+    pub fn new(config: config::PollingConfig) -> Self {
+        Self {
+            interval: config.interval,
+            config,
+            fast_until: None,
+            idle: 0,
+            immediate: false,
+        }
+    }
+
+    /// Delay until the next sync, given the result of the latest one. New torrents are synced
+    /// again right away, as qBittorrent fills in details such as their trackers shortly after
+    /// adding them; the immediate sync isn't repeated if it finds new torrents too.
+    pub fn next(&mut self, summary: Option<&qbittorrent::SyncSummary>, now: Instant) -> Duration {
+        if let Some(summary) = summary {
+            self.back_off(summary);
+        }
+        if let (Some(summary), Some(_)) = (summary, self.config.new_torrent_interval) {
+            if summary.added > 0 && !summary.full_update {
+                log::debug!("New torrents added: polling more frequently");
+                self.fast_until = Some(now + Duration::from_secs(self.config.new_torrent_duration));
+            }
+        }
+        let added = summary.is_some_and(|x| x.added > 0 && !x.full_update);
+        self.immediate = added && !self.immediate;
+        if self.immediate {
+            log::debug!("New torrents added: syncing again right away");
+            return Duration::ZERO;
+        }
+        match (self.config.new_torrent_interval, self.fast_until) {
+            (Some(interval), Some(fast_until)) if now < fast_until => Duration::from_secs(interval),
+            _ => Duration::from_secs(self.interval),
+        }
+    }
+
+    fn back_off(&mut self, summary: &qbittorrent::SyncSummary) {
+        let Some(max_interval) = self.config.max_interval else {
+            return;
+        };
+        if summary.full_update || summary.added + summary.updated + summary.removed > 0 {
+            if self.interval != self.config.interval {
+                log::debug!(
+                    "Torrents changed: polling every {} seconds",
+                    self.config.interval
+                );
+            }
+            self.idle = 0;
+            self.interval = self.config.interval;
+            return;
+        }
+        self.idle = self.idle.saturating_add(1);
+        if self.idle > self.config.idle_syncs && self.interval < max_interval {
+            self.interval = self.interval.saturating_mul(2).min(max_interval);
+            log::debug!(
+                "No torrent changes: polling every {} seconds",
+                self.interval
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poller(new_torrent_interval: Option<u64>) -> Poller {
+        Poller::new(config::PollingConfig {
+            interval: 60,
+            new_torrent_interval,
+            new_torrent_duration: 120,
+            max_interval: None,
+            idle_syncs: 3,
+        })
+    }
+
+    fn added(added: usize) -> qbittorrent::SyncSummary {
+        qbittorrent::SyncSummary {
+            added,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_next_base() {
+        let mut poller = poller(Some(5));
+        let now = Instant::now();
+        assert_eq!(poller.next(Some(&added(0)), now), Duration::from_secs(60));
+        assert_eq!(poller.next(None, now), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_next_new_torrents() {
+        let mut poller = poller(Some(5));
+        let now = Instant::now();
+        assert_eq!(poller.next(Some(&added(1)), now), Duration::ZERO);
+        assert_eq!(poller.next(Some(&added(0)), now), Duration::from_secs(5));
+        let later = now + Duration::from_secs(119);
+        assert_eq!(poller.next(Some(&added(0)), later), Duration::from_secs(5));
+        let expired = now + Duration::from_secs(120);
+        assert_eq!(
+            poller.next(Some(&added(0)), expired),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_next_full_update() {
+        let mut poller = poller(Some(5));
+        let summary = qbittorrent::SyncSummary {
+            added: 0,
+            full_update: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            poller.next(Some(&summary), Instant::now()),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_next_back_off() {
+        let mut poller = Poller::new(config::PollingConfig {
+            max_interval: Some(300),
+            idle_syncs: 2,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let idle = added(0);
+        let delays: Vec<u64> = (0..6)
+            .map(|_| poller.next(Some(&idle), now).as_secs())
+            .collect();
+        assert_eq!(delays, vec![60, 60, 120, 240, 300, 300]);
+        let updated = qbittorrent::SyncSummary {
+            updated: 1,
+            ..Default::default()
+        };
+        assert_eq!(poller.next(Some(&updated), now), Duration::from_secs(60));
+        assert_eq!(poller.next(Some(&idle), now), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_next_back_off_error() {
+        let mut poller = Poller::new(config::PollingConfig {
+            max_interval: Some(300),
+            idle_syncs: 0,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert_eq!(poller.next(Some(&added(0)), now), Duration::from_secs(120));
+        assert_eq!(poller.next(None, now), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_next_disabled() {
+        let mut poller = poller(None);
+        let now = Instant::now();
+        assert_eq!(poller.next(Some(&added(1)), now), Duration::ZERO);
+        assert_eq!(poller.next(Some(&added(0)), now), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_next_immediate() {
+        let mut poller = poller(None);
+        let now = Instant::now();
+        let delays: Vec<u64> = [1, 1, 1, 0, 1]
+            .map(|x| poller.next(Some(&added(x)), now).as_secs())
+            .to_vec();
+        assert_eq!(delays, vec![0, 60, 0, 60, 0]);
+        // An immediate sync that fails doesn't hold back the next one.
+        assert_eq!(poller.next(None, now), Duration::from_secs(60));
+        assert_eq!(poller.next(Some(&added(1)), now), Duration::ZERO);
+    }
+}
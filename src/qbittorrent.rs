@@ -1,8 +1,11 @@
-use crate::config;
+use crate::{clock, config};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use url::Url;
 
 const GLOBAL_LIMIT: &str = "-2";
@@ -16,6 +19,31 @@ fn value_or_global_limit<T: ToString>(value: Option<T>) -> Cow<'static, str> {
 
 const URL_FAILURE: &str = "Could not build URL";
 
+/// How long before the session timeout the session is renewed.
+const SESSION_RENEWAL_MARGIN: Duration = Duration::from_secs(60);
+
+/// How long to wait after a rate-limited response without a usable `Retry-After` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Delay requested by a `Retry-After` header. Only the delay in seconds is supported.
+fn parse_retry_after(value: Option<&str>) -> Duration {
+    value
+        .and_then(|x| x.trim().parse().ok())
+        .map_or(DEFAULT_RETRY_AFTER, Duration::from_secs)
+}
+
+/// Fails with `ClientError::RateLimited` if the server responded with 429 Too Many Requests.
+fn check_rate_limit(response: &reqwest::Response) -> Result<(), ClientError> {
+    if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Ok(());
+    }
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|x| x.to_str().ok());
+    Err(ClientError::RateLimited(parse_retry_after(retry_after)))
+}
+
 pub type Ratio = f64;
 pub type MaxSeedingTime = i32;
 
@@ -24,7 +52,21 @@ pub struct TagList(Vec<String>);
 
 impl From<String> for TagList {
     fn from(item: String) -> Self {
-        Self(item.split_terminator(',').map(|x| x.to_string()).collect())
+        Self(
+            item.split_terminator(',')
+                .map(|x| x.trim().to_string())
+                .collect(),
+        )
+    }
+}
+
+impl TagList {
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0.iter().any(|x| x == tag)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, String> {
+        self.0.iter()
     }
 }
 
@@ -34,7 +76,7 @@ impl fmt::Display for TagList {
     }
 }
 
-type TorrentMap = HashMap<String, Torrent>;
+pub type TorrentMap = HashMap<String, Torrent>;
 
 #[derive(Debug)]
 pub enum AuthenticationError {
@@ -60,20 +102,53 @@ pub enum ClientError {
     Authentication,
     BadRequest,
     InvalidUrl,
+    /// The server responded with something other than the expected JSON.
+    InvalidResponse(serde_json::Error),
+    /// The server responded with 429 Too Many Requests; retry after the delay.
+    RateLimited(Duration),
     Reqwest(reqwest::Error),
 }
 
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Authentication => write!(f, "No permission to access server"),
+            Self::BadRequest => write!(f, "Server rejected the request"),
+            Self::InvalidUrl => write!(f, "Configuration did not contain a valid base URL"),
+            Self::InvalidResponse(error) => write!(f, "Invalid response from server: {}", error),
+            Self::RateLimited(delay) => {
+                write!(f, "Rate limited by server for {} seconds", delay.as_secs())
+            }
+            Self::Reqwest(reqwest_error) => write!(f, "HTTP client error: {}", reqwest_error),
+        }
+    }
+}
+
 pub struct Client {
     base_url: Url,
     client: reqwest::Client,
+    /// Durations of the requests made since the latencies were last taken.
+    latencies: Mutex<Vec<Duration>>,
+    logged_in_at: Option<Instant>,
     password: Option<String>,
     rid: usize,
+    session_timeout: Duration,
+    /// Number of files of each torrent, fetched by `update_file_counts`.
+    file_counts: HashMap<String, usize>,
+    /// Private flag of each torrent, fetched by `update_private_flags`.
+    private_flags: HashMap<String, bool>,
+    /// Every tracker of each torrent, fetched by `update_trackers`.
+    trackers: HashMap<String, Vec<Tracker>>,
+    /// Torrents whose trackers were edited since their trackers were fetched.
+    edited_trackers: Mutex<HashSet<String>>,
+    /// Torrents whose data changed since the changes were last taken.
+    changed: HashSet<String>,
     pub torrents: TorrentMap,
     pub username: Option<String>,
 }
 
 impl Client {
-    pub async fn login(&self) -> Result<(), AuthenticationError> {
+    pub async fn login(&mut self) -> Result<(), AuthenticationError> {
         let username = self
             .username
             .as_ref()
@@ -84,12 +159,12 @@ impl Client {
             .ok_or(AuthenticationError::MissingCredentials)?;
         log::debug!("Logging in as {}", username);
         let url = self.base_url.join("api/v2/auth/login").expect(URL_FAILURE);
-        let response = self
+        let request = self
             .client
-            .clone()
             .post(url)
-            .form(&[("username", username), ("password", password)])
-            .send()
+            .form(&[("username", username), ("password", password)]);
+        let response = self
+            .send(request)
             .await
             .map_err(AuthenticationError::Request)?;
         if response.status() == reqwest::StatusCode::FORBIDDEN {
@@ -99,9 +174,17 @@ impl Client {
             return Err(AuthenticationError::Credentials);
         }
         log::info!("Logged in as {}", username);
+        self.logged_in_at = Some(Instant::now());
         Ok(())
     }
 
+    /// Whether the session is about to reach the session timeout and should be renewed.
+    pub fn session_expiring(&self) -> bool {
+        self.logged_in_at.is_some_and(|logged_in_at| {
+            logged_in_at.elapsed() >= self.session_timeout.saturating_sub(SESSION_RENEWAL_MARGIN)
+        })
+    }
+
     pub fn new(config: config::ServerConfig) -> Result<Self, ClientError> {
         let base_url = Url::parse(&config.address).map_err(|_| ClientError::InvalidUrl)?;
         if (base_url.scheme() != "http" && base_url.scheme() != "https")
@@ -110,72 +193,129 @@ impl Client {
             return Err(ClientError::InvalidUrl);
         }
 
-        let client = reqwest::Client::builder()
-            .cookie_store(true)
-            .referer(true)
-            .build()
-            .map_err(ClientError::Reqwest)?;
+        let mut builder = reqwest::Client::builder().cookie_store(true).referer(true);
+        if config.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(timeout) = config.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(max_idle) = config.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(interval) = config.tcp_keepalive {
+            builder = builder.tcp_keepalive(Duration::from_secs(interval));
+        }
+        if let (Some(address), Some(host)) = (config.resolve, base_url.host_str()) {
+            // The port is ignored by reqwest in favour of the one in the URL.
+            builder = builder.resolve(host, SocketAddr::new(address, 0));
+        }
+        let client = builder.build().map_err(ClientError::Reqwest)?;
         Ok(Self {
             base_url,
             client,
+            latencies: Mutex::default(),
+            logged_in_at: None,
             password: config.password,
             rid: 0,
+            session_timeout: Duration::from_secs(config.session_timeout),
+            file_counts: HashMap::new(),
+            private_flags: HashMap::new(),
+            trackers: HashMap::new(),
+            edited_trackers: Mutex::default(),
+            changed: HashSet::new(),
             torrents: HashMap::new(),
             username: config.username,
         })
     }
 
-    pub async fn update(&mut self) -> Result<(), ClientError> {
+    /// Sends the request and records how long it took.
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let start = Instant::now();
+        let response = request.send().await;
+        self.latencies
+            .lock()
+            .expect("Latency lock poisoned")
+            .push(start.elapsed());
+        response
+    }
+
+    /// Returns the durations of the requests made since the last call.
+    pub fn take_latencies(&self) -> Vec<Duration> {
+        std::mem::take(&mut *self.latencies.lock().expect("Latency lock poisoned"))
+    }
+
+    /// Returns the hashes of the torrents whose data changed since the last call, including
+    /// added torrents and every torrent after a full update.
+    pub fn take_changed(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub async fn update(&mut self) -> Result<SyncSummary, ClientError> {
         log::trace!("Syncing data");
         let url = self
             .base_url
             .join("api/v2/sync/maindata")
             .expect(URL_FAILURE);
-        let response = self
-            .client
-            .clone()
-            .get(url)
-            .query(&[("rid", self.rid)])
-            .send()
-            .await
-            .map_err(ClientError::Reqwest)?;
+        let request = self.client.get(url).query(&[("rid", self.rid)]);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
         if response.status() == reqwest::StatusCode::FORBIDDEN {
             return Err(ClientError::Authentication);
         }
-        let main_data = response
-            .json::<MainData>()
-            .await
-            .map_err(ClientError::Reqwest)?;
+        let body = response.bytes().await.map_err(ClientError::Reqwest)?;
+        let parse_start = Instant::now();
+        let main_data =
+            serde_json::from_slice::<MainData>(&body).map_err(ClientError::InvalidResponse)?;
+        let mut summary = SyncSummary::default();
         if main_data.full_update.is_some() {
             log::debug!("Received a full update from server");
+            summary.full_update = true;
+            let previous = std::mem::take(&mut self.torrents);
             self.torrents = main_data
                 .torrents
                 .into_iter()
                 .filter_map(|(k, v)| match Torrent::from_data(v) {
-                    Ok(torrent) => Some((k, torrent)),
+                    Ok(mut torrent) => {
+                        torrent.carry_limits_changed_on(previous.get(&k));
+                        Some((k, torrent))
+                    }
                     Err(error) => {
                         log::warn!("Unable to deserialize torrent: missing {}", error);
                         None
                     }
                 })
                 .collect();
+            self.changed.extend(self.torrents.keys().cloned());
         } else {
             if let Some(torrents_removed) = main_data.torrents_removed {
                 for hash in torrents_removed {
                     if self.torrents.remove(&hash).is_some() {
                         log::trace!("Removed torrent {}", hash);
+                        summary.removed += 1;
                     };
                 }
             }
             for (key, data) in main_data.torrents {
                 if let Some(torrent) = self.torrents.get_mut(&key) {
                     log::trace!("Updating {}", key);
+                    if data.tracker.is_some() {
+                        self.trackers.remove(&key);
+                    }
                     torrent.update(data);
+                    self.changed.insert(key);
+                    summary.updated += 1;
                 } else {
                     log::trace!("Inserting {}", key);
                     match Torrent::from_data(data) {
-                        Ok(torrent) => {
+                        Ok(mut torrent) => {
+                            torrent.carry_limits_changed_on(None);
+                            self.changed.insert(key.clone());
                             self.torrents.insert(key, torrent);
+                            summary.added += 1;
                         }
                         Err(field) => {
                             log::warn!("Could not load torrent {}: no {} field", key, field);
@@ -185,35 +325,214 @@ impl Client {
             }
         }
 
+        count_cross_seeds(&mut self.torrents, &mut self.changed);
         self.rid = main_data.rid;
+        summary.parse_time = parse_start.elapsed();
         log::trace!("Data synced");
-        Ok(())
+        Ok(summary)
     }
 
-    pub async fn apply_rule_limits(
-        &self,
-        hash: &str,
-        limits: &config::RuleLimits,
-    ) -> Result<(), ClientError> {
-        self.set_share_limits(hash, limits.ratio, limits.minutes)
+    pub async fn add_tags(&self, hash: &str, tags: &[String]) -> Result<(), ClientError> {
+        self.post_tags("api/v2/torrents/addTags", hash, tags).await
+    }
+
+    pub async fn remove_tags(&self, hash: &str, tags: &[String]) -> Result<(), ClientError> {
+        self.post_tags("api/v2/torrents/removeTags", hash, tags)
             .await
     }
 
-    pub async fn apply_global_limits(&self, hash: &str) -> Result<(), ClientError> {
-        self.set_share_limits(hash, None, None).await
+    async fn post_tags(&self, path: &str, hash: &str, tags: &[String]) -> Result<(), ClientError> {
+        let tags = tags.join(",");
+        let data = HashMap::from([("hashes", hash), ("tags", &tags)]);
+        let url = self.base_url.join(path).expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Fetches the trackers of the torrents that aren't cached yet and fills in
+    /// `Torrent::trackers`. The maindata sync only contains the tracker currently in use. With
+    /// `refresh`, the trackers of every torrent are fetched again to get their current status.
+    pub async fn update_trackers(&mut self, refresh: bool) -> Result<(), ClientError> {
+        let edited = std::mem::take(
+            &mut *self
+                .edited_trackers
+                .lock()
+                .expect("Edited trackers lock poisoned"),
+        );
+        self.trackers
+            .retain(|hash, _| self.torrents.contains_key(hash) && !edited.contains(hash));
+        let missing: Vec<String> = self
+            .torrents
+            .keys()
+            .filter(|hash| refresh || !self.trackers.contains_key(*hash))
+            .cloned()
+            .collect();
+        for hash in missing {
+            match self.fetch_trackers(&hash).await {
+                Ok(trackers) => {
+                    self.trackers.insert(hash, trackers);
+                }
+                Err(error @ ClientError::RateLimited(_)) => return Err(error),
+                Err(error) => log::warn!("Could not fetch trackers of {}: {}", hash, error),
+            }
+        }
+        for (hash, torrent) in self.torrents.iter_mut() {
+            if let Some(trackers) = self.trackers.get(hash) {
+                if torrent.trackers != *trackers {
+                    torrent.trackers.clone_from(trackers);
+                    self.changed.insert(hash.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the private flag of torrents for which the sync data didn't include it. Flags are
+    /// fetched once per torrent, as they can't change.
+    pub async fn update_private_flags(&mut self) -> Result<(), ClientError> {
+        self.private_flags
+            .retain(|hash, _| self.torrents.contains_key(hash));
+        let missing: Vec<String> = self
+            .torrents
+            .iter()
+            .filter(|(hash, torrent)| {
+                torrent.private.is_none() && !self.private_flags.contains_key(*hash)
+            })
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in missing {
+            match self.fetch_private_flag(&hash).await {
+                Ok(private) => {
+                    self.private_flags.insert(hash, private);
+                }
+                Err(error @ ClientError::RateLimited(_)) => return Err(error),
+                Err(error) => log::warn!("Could not fetch properties of {}: {}", hash, error),
+            }
+        }
+        for (hash, torrent) in self.torrents.iter_mut() {
+            if torrent.private.is_none() {
+                torrent.private = self.private_flags.get(hash).copied();
+                if torrent.private.is_some() {
+                    self.changed.insert(hash.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the number of files of the torrents. Counts are fetched once per torrent, as they
+    /// don't change once qBittorrent has the metadata.
+    pub async fn update_file_counts(&mut self) -> Result<(), ClientError> {
+        self.file_counts
+            .retain(|hash, _| self.torrents.contains_key(hash));
+        let missing: Vec<String> = self
+            .torrents
+            .keys()
+            .filter(|hash| !self.file_counts.contains_key(*hash))
+            .cloned()
+            .collect();
+        for hash in missing {
+            match self.fetch_file_count(&hash).await {
+                // Torrents added from magnet links have no files until the metadata arrives.
+                Ok(0) => {}
+                Ok(count) => {
+                    self.file_counts.insert(hash, count);
+                }
+                Err(error @ ClientError::RateLimited(_)) => return Err(error),
+                Err(error) => log::warn!("Could not fetch files of {}: {}", hash, error),
+            }
+        }
+        for (hash, torrent) in self.torrents.iter_mut() {
+            let file_count = self.file_counts.get(hash).copied();
+            if torrent.file_count != file_count {
+                torrent.file_count = file_count;
+                self.changed.insert(hash.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_file_count(&self, hash: &str) -> Result<usize, ClientError> {
+        let url = self
+            .base_url
+            .join("api/v2/torrents/files")
+            .expect(URL_FAILURE);
+        let request = self.client.get(url).query(&[("hash", hash)]);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err(ClientError::Authentication);
+        }
+        let body = response.bytes().await.map_err(ClientError::Reqwest)?;
+        let files = serde_json::from_slice::<Vec<serde::de::IgnoredAny>>(&body)
+            .map_err(ClientError::InvalidResponse)?;
+        Ok(files.len())
+    }
+
+    async fn fetch_private_flag(&self, hash: &str) -> Result<bool, ClientError> {
+        let url = self
+            .base_url
+            .join("api/v2/torrents/properties")
+            .expect(URL_FAILURE);
+        let request = self.client.get(url).query(&[("hash", hash)]);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err(ClientError::Authentication);
+        }
+        let body = response.bytes().await.map_err(ClientError::Reqwest)?;
+        let properties = serde_json::from_slice::<TorrentProperties>(&body)
+            .map_err(ClientError::InvalidResponse)?;
+        Ok(properties.is_private)
+    }
+
+    async fn fetch_trackers(&self, hash: &str) -> Result<Vec<Tracker>, ClientError> {
+        let url = self
+            .base_url
+            .join("api/v2/torrents/trackers")
+            .expect(URL_FAILURE);
+        let request = self.client.get(url).query(&[("hash", hash)]);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err(ClientError::Authentication);
+        }
+        let body = response.bytes().await.map_err(ClientError::Reqwest)?;
+        let trackers = serde_json::from_slice::<Vec<TrackerInfo>>(&body)
+            .map_err(ClientError::InvalidResponse)?;
+        // DHT, PeX and LSD are listed as pseudo-trackers that don't parse as URLs.
+        Ok(trackers
+            .into_iter()
+            .filter_map(|tracker| {
+                Some(Tracker {
+                    host: Url::parse(&tracker.url).ok()?.host_str()?.to_string(),
+                    url: tracker.url,
+                    status: TrackerStatus::from_code(tracker.status),
+                    message: tracker.msg,
+                })
+            })
+            .collect())
     }
 
-    async fn set_share_limits(
+    /// Sets the share limits of every torrent with a single request. Unset limits are set to the
+    /// global limits.
+    pub async fn set_share_limits(
         &self,
-        hash: &str,
-        ratio: Option<Ratio>,
-        minutes: Option<MaxSeedingTime>,
+        hashes: &[&str],
+        limits: &config::RuleLimits,
     ) -> Result<(), ClientError> {
-        let ratio = value_or_global_limit(ratio);
-        let minutes = value_or_global_limit(minutes);
+        let hashes = hashes.join("|");
+        let ratio = value_or_global_limit(limits.ratio);
+        let minutes = value_or_global_limit(limits.minutes);
+        let inactive_minutes = value_or_global_limit(limits.inactive_minutes);
         let data = HashMap::from([
-            ("hashes", hash),
-            ("inactiveSeedingTimeLimit", GLOBAL_LIMIT),
+            ("hashes", hashes.as_str()),
+            ("inactiveSeedingTimeLimit", &inactive_minutes),
             ("ratioLimit", &ratio),
             ("seedingTimeLimit", &minutes),
         ]);
@@ -221,19 +540,381 @@ impl Client {
             .base_url
             .join("api/v2/torrents/setShareLimits")
             .expect(URL_FAILURE);
-        let response = self
-            .client
-            .clone()
-            .post(url)
-            .form(&data)
-            .send()
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Removes the torrents from qBittorrent, along with their data if `delete_files` is set.
+    pub async fn delete(&self, hashes: &[&str], delete_files: bool) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let delete_files = delete_files.to_string();
+        let data = HashMap::from([
+            ("hashes", hashes.as_str()),
+            ("deleteFiles", delete_files.as_str()),
+        ]);
+        let url = self
+            .base_url
+            .join("api/v2/torrents/delete")
+            .expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Moves the torrents to the category, which has to exist.
+    pub async fn set_category(&self, hashes: &[&str], category: &str) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let data = HashMap::from([("hashes", hashes.as_str()), ("category", category)]);
+        let url = self
+            .base_url
+            .join("api/v2/torrents/setCategory")
+            .expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Moves the data of the torrents to the directory.
+    pub async fn set_location(&self, hashes: &[&str], location: &str) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let data = HashMap::from([("hashes", hashes.as_str()), ("location", location)]);
+        let url = self
+            .base_url
+            .join("api/v2/torrents/setLocation")
+            .expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Enables or disables super seeding on the torrents.
+    pub async fn set_super_seeding(&self, hashes: &[&str], value: bool) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let value = value.to_string();
+        let data = HashMap::from([("hashes", hashes.as_str()), ("value", value.as_str())]);
+        let url = self
+            .base_url
+            .join("api/v2/torrents/setSuperSeeding")
+            .expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Rechecks the data of the torrents.
+    pub async fn recheck(&self, hashes: &[&str]) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let data = HashMap::from([("hashes", hashes.as_str())]);
+        let url = self
+            .base_url
+            .join("api/v2/torrents/recheck")
+            .expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Moves the torrents in the queue, which requires queueing to be enabled.
+    pub async fn move_in_queue(
+        &self,
+        hashes: &[&str],
+        queue: config::QueueMove,
+    ) -> Result<(), ClientError> {
+        let path = match queue {
+            config::QueueMove::Top => "api/v2/torrents/topPrio",
+            config::QueueMove::Bottom => "api/v2/torrents/bottomPrio",
+            config::QueueMove::Up => "api/v2/torrents/increasePrio",
+            config::QueueMove::Down => "api/v2/torrents/decreasePrio",
+        };
+        let hashes = hashes.join("|");
+        let data = HashMap::from([("hashes", hashes.as_str())]);
+        let url = self.base_url.join(path).expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Enables or disables Automatic Torrent Management on the torrents.
+    pub async fn set_auto_management(
+        &self,
+        hashes: &[&str],
+        enable: bool,
+    ) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let enable = enable.to_string();
+        let data = HashMap::from([("hashes", hashes.as_str()), ("enable", enable.as_str())]);
+        let url = self
+            .base_url
+            .join("api/v2/torrents/setAutoManagement")
+            .expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Adds the announce URLs to the torrent.
+    pub async fn add_trackers(&self, hash: &str, urls: &[&str]) -> Result<(), ClientError> {
+        self.post_trackers("api/v2/torrents/addTrackers", hash, &urls.join("\n"))
+            .await
+    }
+
+    /// Removes the announce URLs from the torrent.
+    pub async fn remove_trackers(&self, hash: &str, urls: &[&str]) -> Result<(), ClientError> {
+        self.post_trackers("api/v2/torrents/removeTrackers", hash, &urls.join("|"))
             .await
-            .map_err(ClientError::Reqwest)?;
+    }
+
+    async fn post_trackers(&self, path: &str, hash: &str, urls: &str) -> Result<(), ClientError> {
+        self.edited_trackers
+            .lock()
+            .expect("Edited trackers lock poisoned")
+            .insert(hash.to_string());
+        let data = HashMap::from([("hash", hash), ("urls", urls)]);
+        let url = self.base_url.join(path).expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
         if response.status() == reqwest::StatusCode::OK {
             return Ok(());
         }
         Err(ClientError::BadRequest)
     }
+
+    /// Toggles sequential downloading of the torrents, so the current state has to be known.
+    pub async fn toggle_sequential_download(&self, hashes: &[&str]) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let data = HashMap::from([("hashes", hashes.as_str())]);
+        let url = self
+            .base_url
+            .join("api/v2/torrents/toggleSequentialDownload")
+            .expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Pauses the torrents. qBittorrent 5.0 renamed pausing to stopping, so the old endpoint is
+    /// only used if the new one doesn't exist.
+    pub async fn pause(&self, hashes: &[&str]) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let data = HashMap::from([("hashes", hashes.as_str())]);
+        for path in ["api/v2/torrents/stop", "api/v2/torrents/pause"] {
+            let url = self.base_url.join(path).expect(URL_FAILURE);
+            let request = self.client.post(url).form(&data);
+            let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+            check_rate_limit(&response)?;
+            match response.status() {
+                reqwest::StatusCode::OK => return Ok(()),
+                reqwest::StatusCode::NOT_FOUND => continue,
+                _ => break,
+            }
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Sets the upload speed limit of the torrents in KiB/s, where 0 is unlimited.
+    pub async fn set_upload_limit(&self, hashes: &[&str], ki_bps: u32) -> Result<(), ClientError> {
+        self.post_speed_limit("api/v2/torrents/setUploadLimit", hashes, ki_bps)
+            .await
+    }
+
+    /// Sets the download speed limit of the torrents in KiB/s, where 0 is unlimited.
+    pub async fn set_download_limit(
+        &self,
+        hashes: &[&str],
+        ki_bps: u32,
+    ) -> Result<(), ClientError> {
+        self.post_speed_limit("api/v2/torrents/setDownloadLimit", hashes, ki_bps)
+            .await
+    }
+
+    async fn post_speed_limit(
+        &self,
+        path: &str,
+        hashes: &[&str],
+        ki_bps: u32,
+    ) -> Result<(), ClientError> {
+        let hashes = hashes.join("|");
+        let limit = (u64::from(ki_bps) * 1024).to_string();
+        let data = HashMap::from([("hashes", hashes.as_str()), ("limit", &limit)]);
+        let url = self.base_url.join(path).expect(URL_FAILURE);
+        let request = self.client.post(url).form(&data);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::OK {
+            return Ok(());
+        }
+        Err(ClientError::BadRequest)
+    }
+
+    /// Fetches the share limits currently set on the torrents, with global limits as `None`.
+    pub async fn share_limits(
+        &self,
+        hashes: &[&str],
+    ) -> Result<HashMap<String, config::RuleLimits>, ClientError> {
+        let url = self
+            .base_url
+            .join("api/v2/torrents/info")
+            .expect(URL_FAILURE);
+        let request = self.client.get(url).query(&[("hashes", hashes.join("|"))]);
+        let response = self.send(request).await.map_err(ClientError::Reqwest)?;
+        check_rate_limit(&response)?;
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err(ClientError::Authentication);
+        }
+        let body = response.bytes().await.map_err(ClientError::Reqwest)?;
+        let info = serde_json::from_slice::<Vec<TorrentInfo>>(&body)
+            .map_err(ClientError::InvalidResponse)?;
+        Ok(info
+            .into_iter()
+            .map(|torrent| {
+                let limits = config::RuleLimits {
+                    ratio: limit_or_global(torrent.ratio_limit, -2.0),
+                    minutes: limit_or_global(torrent.seeding_time_limit, -2),
+                    inactive_minutes: limit_or_global(torrent.inactive_seeding_time_limit, -2),
+                    upload_ki_bps: None,
+                    download_ki_bps: None,
+                };
+                (torrent.hash, limits)
+            })
+            .collect())
+    }
+}
+
+fn limit_or_global<T: PartialEq>(value: T, global: T) -> Option<T> {
+    (value != global).then_some(value)
+}
+
+/// Properties of a torrent as returned by `torrents/properties`.
+#[derive(Deserialize)]
+struct TorrentProperties {
+    is_private: bool,
+}
+
+/// Tracker of a torrent as returned by `torrents/trackers`.
+#[derive(Deserialize)]
+struct TrackerInfo {
+    url: String,
+    #[serde(default)]
+    status: i64,
+    #[serde(default)]
+    msg: String,
+}
+
+/// Status of a tracker as reported by qBittorrent.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum TrackerStatus {
+    Disabled,
+    #[default]
+    NotContacted,
+    Working,
+    Updating,
+    NotWorking,
+}
+
+impl TrackerStatus {
+    fn from_code(code: i64) -> Self {
+        match code {
+            0 => Self::Disabled,
+            2 => Self::Working,
+            3 => Self::Updating,
+            4 => Self::NotWorking,
+            _ => Self::NotContacted,
+        }
+    }
+}
+
+impl fmt::Display for TrackerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Disabled => "disabled",
+            Self::NotContacted => "notContacted",
+            Self::Working => "working",
+            Self::Updating => "updating",
+            Self::NotWorking => "notWorking",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Tracker of a torrent, filled in by `Client::update_trackers`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tracker {
+    pub host: String,
+    /// Announce URL of the tracker.
+    pub url: String,
+    pub status: TrackerStatus,
+    /// Message that the tracker sent with its last response, such as an error.
+    pub message: String,
+}
+
+/// Share limits of a torrent as returned by `torrents/info`.
+#[derive(Deserialize)]
+struct TorrentInfo {
+    hash: String,
+    ratio_limit: Ratio,
+    seeding_time_limit: MaxSeedingTime,
+    #[serde(default = "global_limit")]
+    inactive_seeding_time_limit: MaxSeedingTime,
+}
+
+/// Whether the state is paused, which qBittorrent 5.0 and later call stopped.
+pub fn is_paused(state: &str) -> bool {
+    matches!(state, "pausedUP" | "pausedDL" | "stoppedUP" | "stoppedDL")
+}
+
+/// Limit reported for fields that older versions of qBittorrent don't have.
+fn global_limit() -> MaxSeedingTime {
+    -2
+}
+
+/// Torrent changes received in a single sync.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SyncSummary {
+    pub added: usize,
+    pub full_update: bool,
+    /// Time spent deserializing and merging the server's response.
+    pub parse_time: Duration,
+    pub removed: usize,
+    pub updated: usize,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -246,33 +927,121 @@ struct MainData {
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Torrent {
+    /// Unix timestamp of when the torrent was added.
+    pub added_on: i64,
+    /// Whether Automatic Torrent Management is enabled.
+    pub auto_tmm: bool,
+    /// Distributed copies of the torrent in the swarm; -1 if unknown.
+    pub availability: f64,
     pub category: String,
+    /// Unix timestamp of when the download finished, or a negative value if it hasn't.
+    pub completion_on: i64,
+    /// Path of the torrent's file or root directory; empty if not reported.
+    pub content_path: String,
+    /// Number of other torrents with the same content path.
+    pub cross_seeds: usize,
+    /// Amount of data downloaded in bytes.
+    pub downloaded: u64,
+    /// Download speed limit in bytes per second; zero or negative if unlimited.
+    pub dl_limit: i64,
+    /// Estimated seconds until the torrent finishes; 100 days if unknown.
+    pub eta: usize,
+    /// Number of files in the torrent; `None` if not known. Filled in by
+    /// `Client::update_file_counts`.
+    pub file_count: Option<usize>,
+    /// Whether the torrent was force-started, bypassing the queue.
+    pub force_start: bool,
+    /// Position in the queue starting from 1, or 0 if the torrent isn't queued.
+    pub priority: i64,
+    /// Unix timestamp of the last upload or download.
+    pub last_activity: i64,
+    /// Unix timestamp of when the share limits were last seen changing, or of when jeanne first
+    /// saw the torrent if they haven't changed since.
+    pub limits_changed_on: i64,
+    /// Inactive seeding time limit in minutes; always global before qBittorrent 4.6.
+    pub max_inactive_seeding_time: MaxSeedingTime,
     pub max_ratio: Ratio,
     pub max_seeding_time: MaxSeedingTime,
+    /// Ratio limit set on the torrent itself, where -2 means the global limit. `max_ratio` is
+    /// the limit in effect instead.
+    pub ratio_limit: Option<Ratio>,
+    /// Seeding time limit set on the torrent itself, where -2 means the global limit.
+    pub seeding_time_limit: Option<MaxSeedingTime>,
+    /// Inactive seeding time limit set on the torrent itself, where -2 means the global limit.
+    pub inactive_seeding_time_limit: Option<MaxSeedingTime>,
     pub name: String,
+    /// Number of seeders in the swarm.
+    pub num_complete: usize,
+    /// Number of leechers in the swarm.
+    pub num_incomplete: usize,
+    /// Maindata fields that jeanne doesn't otherwise use, by their qBittorrent names.
+    pub other_fields: serde_json::Map<String, serde_json::Value>,
+    /// Fraction of the selected files downloaded, from 0 to 1.
+    pub progress: f64,
+    /// Whether the torrent is from a private tracker; `None` if not known yet. Older versions of
+    /// qBittorrent only report it through `Client::update_private_flags`.
+    pub private: Option<bool>,
+    /// Share ratio of the torrent.
+    pub ratio: f64,
+    /// Directory the torrent is saved in.
+    pub save_path: String,
     pub seeding_time: usize,
+    /// Total size of the selected files in bytes.
+    pub size: u64,
+    pub state: String,
+    /// Whether the torrent is in super-seeding mode.
+    pub super_seeding: bool,
+    /// Whether the torrent is downloaded in sequential order.
+    pub seq_dl: bool,
     pub tags: TagList,
+    /// Announce URL of the tracker currently in use; empty if no tracker is working.
+    pub tracker: String,
+    /// Upload speed limit in bytes per second; zero or negative if unlimited.
+    pub up_limit: i64,
+    /// All trackers, filled in by `Client::update_trackers`.
+    pub trackers: Vec<Tracker>,
+    pub uploaded: u64,
+    /// Upload speed in bytes per second.
+    pub upspeed: u64,
 }
 
 #[derive(Debug)]
 enum TorrentField {
+    AutoTmm,
     Category,
+    DlLimit,
+    Priority,
     MaxRatio,
     MaxSeedingTime,
     Name,
+    SavePath,
     SeedingTime,
+    State,
+    SuperSeeding,
+    SeqDl,
     Tags,
+    Tracker,
+    UpLimit,
 }
 
 impl fmt::Display for TorrentField {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let name = match self {
+            Self::AutoTmm => "auto_tmm",
             Self::Category => "category",
+            Self::DlLimit => "dl_limit",
+            Self::Priority => "priority",
             Self::MaxRatio => "max_ratio",
             Self::MaxSeedingTime => "max_seeding_time",
             Self::Name => "name",
+            Self::SavePath => "save_path",
             Self::SeedingTime => "seeding_time",
+            Self::State => "state",
+            Self::SuperSeeding => "super_seeding",
+            Self::SeqDl => "seq_dl",
             Self::Tags => "tags",
+            Self::Tracker => "tracker",
+            Self::UpLimit => "up_limit",
         };
         write!(f, "{}", name)
     }
@@ -280,56 +1049,482 @@ impl fmt::Display for TorrentField {
 
 impl Torrent {
     pub fn is_limited(&self) -> bool {
-        self.max_seeding_time >= 0 || self.max_ratio >= 0.0
+        self.max_seeding_time >= 0 || self.max_ratio >= 0.0 || self.max_inactive_seeding_time >= 0
+    }
+
+    /// Whether qBittorrent has stopped the torrent after it reached its share limits. Torrents
+    /// that were stopped by hand or by a rule before reaching any of their limits don't count.
+    pub fn has_reached_limit(&self) -> bool {
+        if self.state != "pausedUP" && self.state != "stoppedUP" {
+            return false;
+        }
+        let reached =
+            |limit: MaxSeedingTime, seconds: usize| limit >= 0 && seconds >= limit as usize * 60;
+        (self.max_ratio >= 0.0 && self.ratio >= self.max_ratio)
+            || reached(self.max_seeding_time, self.seeding_time)
+            || reached(self.max_inactive_seeding_time, self.inactive_time())
+    }
+
+    /// Host name of the tracker currently in use.
+    pub fn tracker_host(&self) -> Option<String> {
+        Url::parse(&self.tracker)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+    }
+
+    /// Seconds elapsed since the torrent was added.
+    pub fn age(&self) -> usize {
+        seconds_since(self.added_on)
+    }
+
+    /// Seconds elapsed since the torrent last uploaded or downloaded anything.
+    pub fn inactive_time(&self) -> usize {
+        seconds_since(self.last_activity)
+    }
+
+    /// Seconds elapsed since the share limits were last seen changing.
+    pub fn time_since_limit_change(&self) -> usize {
+        seconds_since(self.limits_changed_on)
+    }
+
+    /// Carries over when the share limits last changed from the torrent before a full update.
+    /// Limits of torrents seen for the first time count as changed then, as nothing is known
+    /// about changes made before jeanne saw them.
+    fn carry_limits_changed_on(&mut self, previous: Option<&Torrent>) {
+        self.limits_changed_on = match previous {
+            Some(previous) if previous.share_limits() == self.share_limits() => {
+                previous.limits_changed_on
+            }
+            _ => clock::now(),
+        };
+    }
+
+    fn share_limits(&self) -> (Ratio, MaxSeedingTime, MaxSeedingTime) {
+        (
+            self.max_ratio,
+            self.max_seeding_time,
+            self.max_inactive_seeding_time,
+        )
+    }
+
+    /// Upload speed limit in KiB/s rounded up, where 0 is unlimited.
+    pub fn upload_limit(&self) -> u32 {
+        ki_bps(self.up_limit)
+    }
+
+    /// Download speed limit in KiB/s rounded up, where 0 is unlimited.
+    pub fn download_limit(&self) -> u32 {
+        ki_bps(self.dl_limit)
+    }
+
+    /// Seeders per leecher in the swarm; infinite if there are no leechers.
+    pub fn swarm_ratio(&self) -> f64 {
+        if self.num_incomplete == 0 {
+            return f64::INFINITY;
+        }
+        self.num_complete as f64 / self.num_incomplete as f64
+    }
+
+    /// Seconds elapsed since the torrent finished downloading, or zero if it hasn't.
+    pub fn time_since_completion(&self) -> usize {
+        if self.completion_on <= 0 {
+            return 0;
+        }
+        seconds_since(self.completion_on)
     }
 
     fn from_data(torrent_data: PartialTorrent) -> Result<Self, TorrentField> {
+        let auto_tmm = torrent_data.auto_tmm.ok_or(TorrentField::AutoTmm)?;
         let category = torrent_data.category.ok_or(TorrentField::Category)?;
+        // Older versions of qBittorrent don't report the content path.
+        let content_path = torrent_data.content_path.unwrap_or_default();
+        let dl_limit = torrent_data.dl_limit.ok_or(TorrentField::DlLimit)?;
+        let priority = torrent_data.priority.ok_or(TorrentField::Priority)?;
+        // Older versions of qBittorrent don't have inactive seeding time limits.
+        let max_inactive_seeding_time = torrent_data.max_inactive_seeding_time.unwrap_or(-2);
         let max_ratio = torrent_data.max_ratio.ok_or(TorrentField::MaxRatio)?;
         let max_seeding_time = torrent_data
             .max_seeding_time
             .ok_or(TorrentField::MaxSeedingTime)?;
         let name = torrent_data.name.ok_or(TorrentField::Name)?;
+        let save_path = torrent_data.save_path.ok_or(TorrentField::SavePath)?;
         let seeding_time = torrent_data.seeding_time.ok_or(TorrentField::SeedingTime)?;
+        let state = torrent_data.state.ok_or(TorrentField::State)?;
+        let super_seeding = torrent_data
+            .super_seeding
+            .ok_or(TorrentField::SuperSeeding)?;
+        let seq_dl = torrent_data.seq_dl.ok_or(TorrentField::SeqDl)?;
         let tags = TagList::from(torrent_data.tags.ok_or(TorrentField::Tags)?);
+        let tracker = torrent_data.tracker.ok_or(TorrentField::Tracker)?;
+        let up_limit = torrent_data.up_limit.ok_or(TorrentField::UpLimit)?;
+        // Fields that only conditions use are optional, so that torrents missing them are still
+        // managed.
+        let added_on = torrent_data.added_on.unwrap_or_default();
+        let availability = torrent_data.availability.unwrap_or(-1.0);
+        let completion_on = torrent_data.completion_on.unwrap_or(-1);
+        let downloaded = torrent_data.downloaded.unwrap_or_default();
+        let eta = torrent_data.eta.unwrap_or(8_640_000);
+        let force_start = torrent_data.force_start.unwrap_or_default();
+        let last_activity = torrent_data.last_activity.unwrap_or_default();
+        let num_complete = torrent_data.num_complete.unwrap_or_default();
+        let num_incomplete = torrent_data.num_incomplete.unwrap_or_default();
+        let progress = torrent_data.progress.unwrap_or_default();
+        let ratio = torrent_data.ratio.unwrap_or_default();
+        let size = torrent_data.size.unwrap_or_default();
+        let uploaded = torrent_data.uploaded.unwrap_or_default();
+        let upspeed = torrent_data.upspeed.unwrap_or_default();
         Ok(Self {
+            added_on,
+            auto_tmm,
+            availability,
             category,
+            completion_on,
+            content_path,
+            cross_seeds: 0,
+            downloaded,
+            dl_limit,
+            eta,
+            file_count: None,
+            force_start,
+            priority,
+            last_activity,
+            limits_changed_on: 0,
+            max_inactive_seeding_time,
             max_ratio,
             max_seeding_time,
+            ratio_limit: torrent_data.ratio_limit,
+            seeding_time_limit: torrent_data.seeding_time_limit,
+            inactive_seeding_time_limit: torrent_data.inactive_seeding_time_limit,
             name,
+            num_complete,
+            num_incomplete,
+            other_fields: torrent_data.other_fields,
+            progress,
+            private: torrent_data.private,
+            ratio,
+            save_path,
             seeding_time,
+            size,
+            state,
+            super_seeding,
+            seq_dl,
             tags,
+            tracker,
+            up_limit,
+            trackers: Vec::new(),
+            uploaded,
+            upspeed,
         })
     }
 
     fn update(&mut self, torrent_data: PartialTorrent) {
+        let share_limits = self.share_limits();
+        if let Some(added_on) = torrent_data.added_on {
+            self.added_on = added_on
+        }
+        if let Some(auto_tmm) = torrent_data.auto_tmm {
+            self.auto_tmm = auto_tmm
+        }
+        if let Some(availability) = torrent_data.availability {
+            self.availability = availability
+        }
         if let Some(category) = torrent_data.category {
             self.category = category
         }
+        if let Some(completion_on) = torrent_data.completion_on {
+            self.completion_on = completion_on
+        }
+        if let Some(content_path) = torrent_data.content_path {
+            self.content_path = content_path
+        }
+        if let Some(downloaded) = torrent_data.downloaded {
+            self.downloaded = downloaded
+        }
+        if let Some(dl_limit) = torrent_data.dl_limit {
+            self.dl_limit = dl_limit
+        }
+        if let Some(eta) = torrent_data.eta {
+            self.eta = eta
+        }
+        if let Some(force_start) = torrent_data.force_start {
+            self.force_start = force_start
+        }
+        if let Some(priority) = torrent_data.priority {
+            self.priority = priority
+        }
+        if let Some(last_activity) = torrent_data.last_activity {
+            self.last_activity = last_activity
+        }
+        if let Some(max_inactive_seeding_time) = torrent_data.max_inactive_seeding_time {
+            self.max_inactive_seeding_time = max_inactive_seeding_time
+        }
         if let Some(max_ratio) = torrent_data.max_ratio {
             self.max_ratio = max_ratio
         }
         if let Some(max_seeding_time) = torrent_data.max_seeding_time {
             self.max_seeding_time = max_seeding_time
         }
+        if torrent_data.ratio_limit.is_some() {
+            self.ratio_limit = torrent_data.ratio_limit
+        }
+        if torrent_data.seeding_time_limit.is_some() {
+            self.seeding_time_limit = torrent_data.seeding_time_limit
+        }
+        if torrent_data.inactive_seeding_time_limit.is_some() {
+            self.inactive_seeding_time_limit = torrent_data.inactive_seeding_time_limit
+        }
         if let Some(name) = torrent_data.name {
             self.name = name
         }
+        if let Some(num_complete) = torrent_data.num_complete {
+            self.num_complete = num_complete
+        }
+        if let Some(num_incomplete) = torrent_data.num_incomplete {
+            self.num_incomplete = num_incomplete
+        }
+        if let Some(progress) = torrent_data.progress {
+            self.progress = progress
+        }
+        if let Some(private) = torrent_data.private {
+            self.private = Some(private)
+        }
+        if let Some(ratio) = torrent_data.ratio {
+            self.ratio = ratio
+        }
+        if let Some(save_path) = torrent_data.save_path {
+            self.save_path = save_path
+        }
         if let Some(seeding_time) = torrent_data.seeding_time {
             self.seeding_time = seeding_time
         }
+        if let Some(size) = torrent_data.size {
+            self.size = size
+        }
+        if let Some(state) = torrent_data.state {
+            self.state = state
+        }
+        if let Some(super_seeding) = torrent_data.super_seeding {
+            self.super_seeding = super_seeding
+        }
+        if let Some(seq_dl) = torrent_data.seq_dl {
+            self.seq_dl = seq_dl
+        }
         if let Some(tags) = torrent_data.tags {
             self.tags = TagList::from(tags)
         }
+        if let Some(tracker) = torrent_data.tracker {
+            self.tracker = tracker
+        }
+        if let Some(up_limit) = torrent_data.up_limit {
+            self.up_limit = up_limit
+        }
+        if let Some(uploaded) = torrent_data.uploaded {
+            self.uploaded = uploaded
+        }
+        if let Some(upspeed) = torrent_data.upspeed {
+            self.upspeed = upspeed
+        }
+        self.other_fields.extend(torrent_data.other_fields);
+        if self.share_limits() != share_limits {
+            self.limits_changed_on = clock::now();
+        }
+    }
+
+    /// Value of a maindata field by its qBittorrent name, such as `dlspeed`.
+    pub fn field(&self, name: &str) -> Option<serde_json::Value> {
+        use serde_json::Value;
+        let value = match name {
+            "added_on" => Value::from(self.added_on),
+            "auto_tmm" => Value::from(self.auto_tmm),
+            "availability" => Value::from(self.availability),
+            "category" => Value::from(self.category.as_str()),
+            "completion_on" => Value::from(self.completion_on),
+            "content_path" => Value::from(self.content_path.as_str()),
+            "dl_limit" => Value::from(self.dl_limit),
+            "downloaded" => Value::from(self.downloaded),
+            "eta" => Value::from(self.eta),
+            "force_start" => Value::from(self.force_start),
+            "inactive_seeding_time_limit" => Value::from(self.inactive_seeding_time_limit?),
+            "last_activity" => Value::from(self.last_activity),
+            "max_inactive_seeding_time" => Value::from(self.max_inactive_seeding_time),
+            "max_ratio" => Value::from(self.max_ratio),
+            "max_seeding_time" => Value::from(self.max_seeding_time),
+            "name" => Value::from(self.name.as_str()),
+            "num_complete" => Value::from(self.num_complete),
+            "num_incomplete" => Value::from(self.num_incomplete),
+            "priority" => Value::from(self.priority),
+            "private" => Value::from(self.private?),
+            "progress" => Value::from(self.progress),
+            "ratio" => Value::from(self.ratio),
+            "ratio_limit" => Value::from(self.ratio_limit?),
+            "save_path" => Value::from(self.save_path.as_str()),
+            "seeding_time" => Value::from(self.seeding_time),
+            "seeding_time_limit" => Value::from(self.seeding_time_limit?),
+            "seq_dl" => Value::from(self.seq_dl),
+            "size" => Value::from(self.size),
+            "state" => Value::from(self.state.as_str()),
+            "super_seeding" => Value::from(self.super_seeding),
+            "tags" => Value::from(self.tags.iter().cloned().collect::<Vec<_>>().join(", ")),
+            "tracker" => Value::from(self.tracker.as_str()),
+            "up_limit" => Value::from(self.up_limit),
+            "uploaded" => Value::from(self.uploaded),
+            "upspeed" => Value::from(self.upspeed),
+            _ => return self.other_fields.get(name).cloned(),
+        };
+        Some(value)
+    }
+}
+
+/// Counts the torrents that share their content path with each torrent, adding the ones whose
+/// count changed to `changed`.
+fn count_cross_seeds(torrents: &mut TorrentMap, changed: &mut HashSet<String>) {
+    let mut paths: HashMap<String, usize> = HashMap::new();
+    for torrent in torrents.values().filter(|x| !x.content_path.is_empty()) {
+        *paths.entry(torrent.content_path.clone()).or_default() += 1;
+    }
+    for (hash, torrent) in torrents.iter_mut() {
+        let cross_seeds = paths
+            .get(&torrent.content_path)
+            .map_or(0, |count| count - 1);
+        if torrent.cross_seeds != cross_seeds {
+            torrent.cross_seeds = cross_seeds;
+            changed.insert(hash.clone());
+        }
     }
 }
 
+/// Converts a speed limit in bytes per second to KiB/s, rounding up.
+fn ki_bps(bytes_per_second: i64) -> u32 {
+    (bytes_per_second.max(0) as u64)
+        .div_ceil(1024)
+        .try_into()
+        .unwrap_or(u32::MAX)
+}
+
+/// Seconds elapsed since the Unix timestamp.
+fn seconds_since(timestamp: i64) -> usize {
+    clock::now().saturating_sub(timestamp).max(0) as usize
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct PartialTorrent {
+    added_on: Option<i64>,
+    auto_tmm: Option<bool>,
+    availability: Option<f64>,
     category: Option<String>,
+    completion_on: Option<i64>,
+    content_path: Option<String>,
+    downloaded: Option<u64>,
+    dl_limit: Option<i64>,
+    eta: Option<usize>,
+    force_start: Option<bool>,
+    priority: Option<i64>,
+    last_activity: Option<i64>,
+    max_inactive_seeding_time: Option<MaxSeedingTime>,
     max_ratio: Option<Ratio>,
     max_seeding_time: Option<MaxSeedingTime>,
+    ratio_limit: Option<Ratio>,
+    seeding_time_limit: Option<MaxSeedingTime>,
+    inactive_seeding_time_limit: Option<MaxSeedingTime>,
     name: Option<String>,
+    num_complete: Option<usize>,
+    num_incomplete: Option<usize>,
+    progress: Option<f64>,
+    private: Option<bool>,
+    ratio: Option<f64>,
+    save_path: Option<String>,
     seeding_time: Option<usize>,
+    size: Option<u64>,
+    state: Option<String>,
+    super_seeding: Option<bool>,
+    seq_dl: Option<bool>,
     tags: Option<String>,
+    tracker: Option<String>,
+    up_limit: Option<i64>,
+    uploaded: Option<u64>,
+    upspeed: Option<u64>,
+    #[serde(flatten)]
+    other_fields: serde_json::Map<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(Some("120"), 120 ; "seconds")]
+    #[test_case(Some(" 5 "), 5 ; "whitespace")]
+    #[test_case(Some("Wed, 21 Oct 2026 07:28:00 GMT"), 30 ; "http date")]
+    #[test_case(None, 30 ; "missing")]
+    fn test_parse_retry_after(value: Option<&str>, expected: u64) {
+        assert_eq!(parse_retry_after(value), Duration::from_secs(expected));
+    }
+
+    #[test]
+    fn test_count_cross_seeds() {
+        let torrent = |content_path: &str| Torrent {
+            content_path: content_path.to_string(),
+            ..Default::default()
+        };
+        let mut torrents = TorrentMap::from([
+            ("a".to_string(), torrent("/data/Show")),
+            ("b".to_string(), torrent("/data/Show")),
+            ("c".to_string(), torrent("/data/Movie")),
+            ("d".to_string(), torrent("")),
+            ("e".to_string(), torrent("")),
+        ]);
+        let mut changed = HashSet::new();
+        count_cross_seeds(&mut torrents, &mut changed);
+        assert_eq!(torrents["a"].cross_seeds, 1);
+        assert_eq!(torrents["b"].cross_seeds, 1);
+        assert_eq!(torrents["c"].cross_seeds, 0);
+        assert_eq!(torrents["d"].cross_seeds, 0);
+        assert_eq!(changed, HashSet::from(["a".to_string(), "b".to_string()]));
+    }
+
+    #[test]
+    fn test_update_limits_changed_on() {
+        let mut torrent = Torrent::default();
+        torrent.update(serde_json::from_str(r#"{"ratio": 1.5, "max_ratio": 0.0}"#).unwrap());
+        assert_eq!(torrent.limits_changed_on, 0);
+        torrent.update(serde_json::from_str(r#"{"max_ratio": 2.0}"#).unwrap());
+        assert!(torrent.time_since_limit_change() < 60);
+    }
+
+    #[test]
+    fn test_carry_limits_changed_on() {
+        let torrent = |max_ratio| Torrent {
+            max_ratio,
+            ..Default::default()
+        };
+        let previous = Torrent {
+            limits_changed_on: 1000,
+            ..torrent(2.0)
+        };
+        // After a restart or when a torrent is added, limits count as changed when first seen.
+        let mut added = torrent(2.0);
+        added.carry_limits_changed_on(None);
+        assert!(added.time_since_limit_change() < 60);
+        let mut same = torrent(2.0);
+        same.carry_limits_changed_on(Some(&previous));
+        assert_eq!(same.limits_changed_on, 1000);
+        let mut changed = torrent(1.0);
+        changed.carry_limits_changed_on(Some(&previous));
+        assert!(changed.time_since_limit_change() < 60);
+    }
+
+    #[test]
+    fn test_other_fields() {
+        let mut torrent = Torrent::default();
+        torrent.update(serde_json::from_str(r#"{"ratio": 1.5, "dlspeed": 100}"#).unwrap());
+        torrent.update(serde_json::from_str(r#"{"f_l_piece_prio": true}"#).unwrap());
+        assert_eq!(torrent.field("ratio"), Some(serde_json::json!(1.5)));
+        assert_eq!(torrent.field("dlspeed"), Some(serde_json::json!(100)));
+        assert_eq!(
+            torrent.field("f_l_piece_prio"),
+            Some(serde_json::json!(true))
+        );
+        assert_eq!(torrent.field("private"), None);
+        assert_eq!(torrent.field("seen_complete"), None);
+    }
 }
@@ -0,0 +1,281 @@
+use crate::clock;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Time windows in which a rule applies, given as a cron expression such as `* 0-6 * * *` or as
+/// weekdays and hours. Times are in UTC unless an offset is given.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    weekdays: u8,
+    /// Whether the day of the month is restricted, in which case cron matches either day field.
+    restricted_days_of_month: bool,
+    restricted_weekdays: bool,
+    /// Offset from UTC in seconds.
+    offset: i64,
+    description: String,
+}
+
+impl Schedule {
+    pub fn matches(&self) -> bool {
+        self.matches_at(clock::now())
+    }
+
+    /// Whether a minute of the schedule started after `from` and at or before `to`. Only the
+    /// last week before `to` is checked.
+    pub fn fired_between(&self, from: i64, to: i64) -> bool {
+        let last = to.div_euclid(60);
+        let first = (from.div_euclid(60) + 1).max(last - 7 * 24 * 60);
+        (first..=last).any(|minute| self.matches_at(minute * 60))
+    }
+
+    /// Whether the Unix timestamp falls in the schedule.
+    fn matches_at(&self, timestamp: i64) -> bool {
+        let local = timestamp + self.offset;
+        let days = local.div_euclid(86_400);
+        let seconds = local.rem_euclid(86_400);
+        let (_, month, day) = civil_from_days(days);
+        // The Unix epoch was a Thursday.
+        let weekday = (days + 4).rem_euclid(7);
+        let day_matches = match (self.restricted_days_of_month, self.restricted_weekdays) {
+            (true, true) => {
+                bit(self.days_of_month.into(), day) || bit(self.weekdays.into(), weekday)
+            }
+            _ => bit(self.days_of_month.into(), day) && bit(self.weekdays.into(), weekday),
+        };
+        bit(self.minutes, seconds / 60 % 60)
+            && bit(self.hours.into(), seconds / 3600)
+            && bit(self.months.into(), month)
+            && day_matches
+    }
+}
+
+fn bit(set: u64, value: i64) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Year, month and day of the days since the Unix epoch.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Parses a value of a cron field, which may be a weekday name.
+fn parse_value(s: &str, names: &[&str]) -> Result<u32, String> {
+    if let Some(index) = names.iter().position(|x| x.eq_ignore_ascii_case(s)) {
+        return Ok(index as u32);
+    }
+    s.parse().map_err(|_| format!("invalid value '{}'", s))
+}
+
+/// Parses a cron field such as `*`, `1-5`, `*/15` or `0,30` into a bit set. Ranges whose end is
+/// before their start wrap around, so that `22-6` covers the night.
+fn parse_field(s: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut set = 0;
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|x| *x > 0)
+                    .ok_or_else(|| format!("invalid step '{}'", step))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, names)?, parse_value(end, names)?)
+        } else {
+            let value = parse_value(range, names)?;
+            (value, if part.contains('/') { max } else { value })
+        };
+        for value in [start, end] {
+            if value < min || value > max {
+                return Err(format!("{} is not between {} and {}", value, min, max));
+            }
+        }
+        let length = if end >= start {
+            end - start
+        } else {
+            end + max + 1 - min - start
+        };
+        for i in (0..=length).step_by(step as usize) {
+            let value = (start - min + i) % (max + 1 - min) + min;
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, weekdays] = fields[..] else {
+            return Err("a cron expression needs five fields".to_string());
+        };
+        let mut weekday_set = parse_field(weekdays, 0, 7, &WEEKDAYS)?;
+        // Both 0 and 7 are Sunday.
+        if bit(weekday_set, 7) {
+            weekday_set |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59, &[])?,
+            hours: parse_field(hours, 0, 23, &[])? as u32,
+            days_of_month: parse_field(days_of_month, 1, 31, &[])? as u32,
+            months: parse_field(months, 1, 12, &[])? as u16,
+            weekdays: (weekday_set & 0x7f) as u8,
+            restricted_days_of_month: days_of_month != "*",
+            restricted_weekdays: weekdays != "*",
+            offset: 0,
+            description: s.trim().to_string(),
+        })
+    }
+}
+
+/// Parses an offset such as `+02:00` or `-5` into seconds.
+fn parse_offset(s: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid UTC offset '{}'", s);
+    let (sign, rest) = match s.trim().split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(invalid()),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok(sign * (hours * 3600 + minutes * 60))
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase", deny_unknown_fields)]
+        struct Block {
+            cron: Option<String>,
+            #[serde(default)]
+            days: Vec<String>,
+            hours: Option<String>,
+            utc_offset: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Cron(String),
+            Block(Block),
+        }
+
+        let block = match Value::deserialize(d)? {
+            Value::Cron(cron) => return cron.parse().map_err(Error::custom),
+            Value::Block(block) => block,
+        };
+        let mut schedule = match (block.cron, block.days.is_empty() && block.hours.is_none()) {
+            (Some(cron), true) => cron.parse::<Schedule>(),
+            (Some(_), false) => Err("cron can't be combined with days or hours".to_string()),
+            (None, _) => {
+                let days = match block.days.as_slice() {
+                    [] => "*".to_string(),
+                    days => days.join(","),
+                };
+                let hours = block.hours.as_deref().unwrap_or("*");
+                format!("* {} * * {}", hours.replace(' ', ""), days).parse()
+            }
+        }
+        .map_err(Error::custom)?;
+        if let Some(offset) = block.utc_offset {
+            schedule.offset = parse_offset(&offset).map_err(Error::custom)?;
+            schedule.description = format!("{} (UTC{})", schedule.description, offset.trim());
+        }
+        Ok(schedule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    /// Saturday 2026-10-17 23:30 UTC.
+    const SATURDAY_NIGHT: i64 = 1_792_279_800;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(SATURDAY_NIGHT / 86_400), (2026, 10, 17));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test_case("* * * * *", true ; "always")]
+    #[test_case("* 23 * * *", true ; "hour")]
+    #[test_case("* 22-6 * * *", true ; "wrapping hours")]
+    #[test_case("* 0-6 * * *", false ; "other hours")]
+    #[test_case("*/15 * * * *", true ; "step")]
+    #[test_case("*/20 * * * *", false ; "other step")]
+    #[test_case("* * * * sat,sun", true ; "weekday names")]
+    #[test_case("* * * * 1-5", false ; "weekdays")]
+    #[test_case("* * 17 10 *", true ; "date")]
+    #[test_case("* * 1 * 6", true ; "day of month or weekday")]
+    fn test_matches_cron(cron: &str, expected: bool) {
+        let schedule: Schedule = cron.parse().unwrap();
+        assert_eq!(schedule.matches_at(SATURDAY_NIGHT), expected);
+    }
+
+    #[test_case("30 23 * * *", -60, 0, true ; "at the end")]
+    #[test_case("30 23 * * *", 0, 59, false ; "at the start")]
+    #[test_case("0 23 * * *", -3600, 0, true ; "in between")]
+    #[test_case("0 22 * * *", -3600, 0, false ; "before")]
+    fn test_fired_between(cron: &str, from: i64, to: i64, expected: bool) {
+        let schedule: Schedule = cron.parse().unwrap();
+        assert_eq!(
+            schedule.fired_between(SATURDAY_NIGHT + from, SATURDAY_NIGHT + to),
+            expected
+        );
+    }
+
+    #[test_case("{days: [sat, sun], hours: 22-6}", true ; "block")]
+    #[test_case("{days: [mon]}", false ; "other day")]
+    #[test_case("{hours: 0-6, utcOffset: '+02:00'}", true ; "offset")]
+    #[test_case("{cron: '* 1 * * sun', utcOffset: '+1:30'}", true ; "cron with offset")]
+    fn test_matches_block(yaml: &str, expected: bool) {
+        let schedule: Schedule = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(schedule.matches_at(SATURDAY_NIGHT), expected);
+    }
+
+    #[test_case("* * * *", "a cron expression needs five fields" ; "fields")]
+    #[test_case("60 * * * *", "60 is not between 0 and 59" ; "range")]
+    #[test_case("* * * * funday", "invalid value 'funday'" ; "weekday")]
+    #[test_case("*/0 * * * *", "invalid step '0'" ; "step")]
+    fn test_parse_error(cron: &str, expected: &str) {
+        assert_eq!(cron.parse::<Schedule>().unwrap_err(), expected);
+    }
+}
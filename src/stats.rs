@@ -0,0 +1,226 @@
+use crate::{clock, plan};
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+const DATABASE_FILE: &str = "jeanne.sqlite3";
+/// Name under which uploads of torrents not matched by any rule are recorded.
+const UNMATCHED: &str = "(unmatched)";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS changes (
+    timestamp INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    name TEXT NOT NULL,
+    rule TEXT,
+    field TEXT NOT NULL,
+    current TEXT NOT NULL,
+    target TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS uploads (
+    timestamp INTEGER NOT NULL,
+    rule TEXT NOT NULL,
+    bytes INTEGER NOT NULL
+);
+";
+
+/// Changes and uploaded bytes recorded for a single rule.
+#[derive(Debug, PartialEq)]
+pub struct RuleStats {
+    pub rule: String,
+    pub changes: u64,
+    pub uploaded: u64,
+}
+
+/// Statistics database in the state directory.
+pub struct Stats {
+    connection: Connection,
+    /// Uploaded bytes of each torrent on the previous cycle.
+    uploaded: HashMap<String, u64>,
+}
+
+impl Stats {
+    pub fn open(state_dir: &Path) -> Result<Self, rusqlite::Error> {
+        Self::from_connection(Connection::open(state_dir.join(DATABASE_FILE))?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self, rusqlite::Error> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection,
+            uploaded: HashMap::new(),
+        })
+    }
+
+    pub fn record_change(&self, change: &plan::Change<'_>) -> Result<(), rusqlite::Error> {
+        let timestamp = clock::now();
+        for entry in change.diff() {
+            self.connection.execute(
+                "INSERT INTO changes (timestamp, hash, name, rule, field, current, target) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    timestamp,
+                    entry.hash,
+                    entry.name,
+                    entry.rule,
+                    entry.field,
+                    entry.current,
+                    entry.target
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Records how much each rule's torrents have uploaded since the previous cycle.
+    pub fn record_uploads(&mut self, plan: &plan::Plan<'_>) -> Result<(), rusqlite::Error> {
+        let mut deltas = BTreeMap::<String, u64>::new();
+        let mut uploaded = HashMap::with_capacity(plan.evaluated.len());
+        for (hash, torrent, rule) in &plan.evaluated {
+            if let Some(previous) = self.uploaded.get(*hash) {
+                let delta = torrent.uploaded.saturating_sub(*previous);
+                if delta > 0 {
+                    let name = match rule {
+                        Some((i, rule)) => rule.name(*i).into_owned(),
+                        None => UNMATCHED.to_string(),
+                    };
+                    *deltas.entry(name).or_default() += delta;
+                }
+            }
+            uploaded.insert(hash.to_string(), torrent.uploaded);
+        }
+        self.uploaded = uploaded;
+
+        let timestamp = clock::now();
+        for (rule, bytes) in deltas {
+            self.connection.execute(
+                "INSERT INTO uploads (timestamp, rule, bytes) VALUES (?1, ?2, ?3)",
+                params![timestamp, rule, bytes as i64],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Totals per rule since the given Unix timestamp.
+    pub fn summary(&self, since: i64) -> Result<Vec<RuleStats>, rusqlite::Error> {
+        let mut statement = self.connection.prepare(
+            "SELECT rule, SUM(changes), SUM(uploaded) FROM (
+                SELECT COALESCE(rule, ?2) AS rule, COUNT(DISTINCT timestamp || hash) AS changes,
+                    0 AS uploaded
+                FROM changes WHERE timestamp >= ?1 GROUP BY 1
+                UNION ALL
+                SELECT rule, 0, SUM(bytes) FROM uploads WHERE timestamp >= ?1 GROUP BY 1
+            ) GROUP BY rule ORDER BY rule",
+        )?;
+        let rows = statement.query_map(params![since, UNMATCHED], |row| {
+            Ok(RuleStats {
+                rule: row.get(0)?,
+                changes: row.get::<_, i64>(1)? as u64,
+                uploaded: row.get::<_, i64>(2)? as u64,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Summary of the last `days` days as a plain text table.
+    pub fn report(&self, days: u32) -> Result<String, rusqlite::Error> {
+        let since = clock::now() - i64::from(days) * 86400;
+        let summary = self.summary(since)?;
+        let width = summary
+            .iter()
+            .map(|x| x.rule.chars().count())
+            .chain(std::iter::once(4))
+            .max()
+            .unwrap_or_default();
+        let mut report = format!("{:width$}  {:>7}  {:>10}\n", "RULE", "CHANGES", "UPLOADED");
+        for stats in summary {
+            report.push_str(&format!(
+                "{:width$}  {:>7}  {:>10}\n",
+                stats.rule,
+                stats.changes,
+                format_bytes(stats.uploaded)
+            ));
+        }
+        Ok(report)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, qbittorrent};
+    use test_case::test_case;
+
+    #[test_case(0, "0 B" ; "zero")]
+    #[test_case(1023, "1023 B" ; "bytes")]
+    #[test_case(1536, "1.5 KiB" ; "kibibytes")]
+    #[test_case(10 * 1024 * 1024 * 1024, "10.0 GiB" ; "gibibytes")]
+    fn test_format_bytes(bytes: u64, expected: &str) {
+        assert_eq!(format_bytes(bytes), expected);
+    }
+
+    #[test]
+    fn test_record() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules: [{id: alien, category: Alien, limits: {ratio: 2.0}}]",
+        )
+        .unwrap();
+        let torrent = |category: &str, uploaded| qbittorrent::Torrent {
+            category: category.to_string(),
+            max_ratio: 1.0,
+            uploaded,
+            ..Default::default()
+        };
+        let mut stats = Stats::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", 100)),
+            ("b".to_string(), torrent("Ghost", 100)),
+        ]);
+        let plan = plan::Plan::new(&config, &torrents);
+        for change in &plan.changes {
+            stats.record_change(change).unwrap();
+        }
+        stats.record_uploads(&plan).unwrap();
+
+        let torrents = qbittorrent::TorrentMap::from([
+            ("a".to_string(), torrent("Alien", 150)),
+            ("b".to_string(), torrent("Ghost", 120)),
+        ]);
+        stats
+            .record_uploads(&plan::Plan::new(&config, &torrents))
+            .unwrap();
+
+        assert_eq!(
+            stats.summary(0).unwrap(),
+            vec![
+                RuleStats {
+                    rule: UNMATCHED.to_string(),
+                    changes: 1,
+                    uploaded: 20
+                },
+                RuleStats {
+                    rule: "alien".to_string(),
+                    changes: 1,
+                    uploaded: 50
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{clock, config, plan};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Number of most recent errors kept in the state file.
+const MAX_ERRORS: usize = 20;
+
+#[derive(Serialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct TorrentCounts {
+    total: usize,
+    ignored: usize,
+    unmatched: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct RuleMatches {
+    rule: String,
+    matches: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct ErrorEntry {
+    timestamp: i64,
+    message: String,
+}
+
+/// Status of the latest cycle, written as JSON for external consumers.
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    /// Unix timestamp of the last update.
+    updated: i64,
+    torrents: TorrentCounts,
+    rules: Vec<RuleMatches>,
+    errors: VecDeque<ErrorEntry>,
+    /// Changes applied during the latest cycle.
+    changes: Vec<plan::DiffEntry>,
+}
+
+pub struct StatusFile {
+    path: PathBuf,
+    status: Status,
+}
+
+impl StatusFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            status: Status::default(),
+        }
+    }
+
+    /// Resets the status for a new cycle from the evaluated plan.
+    pub fn start_cycle(&mut self, plan: &plan::Plan<'_>, rules: &config::RuleList) {
+        self.status.torrents = TorrentCounts {
+            total: plan.total,
+            ignored: plan.ignored,
+            unmatched: plan.unmatched,
+        };
+        self.status.rules = rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| RuleMatches {
+                rule: rule.name(i).into_owned(),
+                matches: plan.matches[i],
+            })
+            .collect();
+        self.status.changes.clear();
+    }
+
+    pub fn record_change(&mut self, change: &plan::Change<'_>) {
+        self.status.changes.extend(change.diff());
+    }
+
+    pub fn record_error(&mut self, message: String) {
+        if self.status.errors.len() == MAX_ERRORS {
+            self.status.errors.pop_front();
+        }
+        self.status.errors.push_back(ErrorEntry {
+            timestamp: clock::now(),
+            message,
+        });
+    }
+
+    /// Writes the status to a temporary file and renames it over the previous one, so readers
+    /// never see a partially written file.
+    pub fn write(&mut self) -> io::Result<()> {
+        self.status.updated = clock::now();
+        let json = serde_json::to_vec_pretty(&self.status).map_err(io::Error::from)?;
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, json)?;
+        fs::rename(&temporary, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qbittorrent;
+
+    #[test]
+    fn test_status() {
+        let config: config::Config = serde_yaml::from_str(
+            "server: {address: http://localhost}\n\
+            rules: [{id: alien, category: Alien, limits: {ratio: 2.0}}]",
+        )
+        .unwrap();
+        let torrents = qbittorrent::TorrentMap::from([
+            (
+                "a".to_string(),
+                qbittorrent::Torrent {
+                    category: "Alien".to_string(),
+                    max_ratio: 1.0,
+                    name: "Alien".to_string(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "b".to_string(),
+                qbittorrent::Torrent {
+                    max_inactive_seeding_time: -2,
+                    max_ratio: -1.0,
+                    max_seeding_time: -1,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let plan = plan::Plan::new(&config, &torrents);
+        let mut status = StatusFile::new(PathBuf::new());
+        status.start_cycle(&plan, &config.rules);
+        for change in &plan.changes {
+            status.record_change(change);
+        }
+        for i in 0..=MAX_ERRORS {
+            status.record_error(format!("error {}", i));
+        }
+
+        assert_eq!(
+            status.status.torrents,
+            TorrentCounts {
+                total: 2,
+                ignored: 0,
+                unmatched: 1
+            }
+        );
+        assert_eq!(
+            status.status.rules,
+            vec![RuleMatches {
+                rule: "alien".to_string(),
+                matches: 1
+            }]
+        );
+        assert_eq!(status.status.changes.len(), 1);
+        assert_eq!(status.status.errors.len(), MAX_ERRORS);
+        assert_eq!(status.status.errors[0].message, "error 1");
+
+        status.start_cycle(&plan, &config.rules);
+        assert!(status.status.changes.is_empty());
+    }
+}
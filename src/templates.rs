@@ -0,0 +1,168 @@
+use serde_yaml::{Mapping, Value};
+
+const TEMPLATES: &str = "templates";
+const EXTENDS: &str = "extends";
+const RULE_DEFAULTS: &str = "ruleDefaults";
+
+/// Resolves the `extends` keys of the rules and of the `ruleDefaults` section against the
+/// `templates` section, then removes that section. Returns whether the configuration used
+/// templates at all.
+pub fn expand(config: &mut Value) -> Result<bool, String> {
+    let Some(config) = config.as_mapping_mut() else {
+        return Ok(false);
+    };
+    let templates = match config.remove(TEMPLATES) {
+        Some(Value::Mapping(templates)) => templates,
+        Some(Value::Null) | None => Mapping::new(),
+        Some(_) => return Err("templates must map names to rules".to_string()),
+    };
+    let mut expanded = !templates.is_empty();
+    if let Some(Value::Sequence(rules)) = config.get_mut("rules") {
+        for (i, rule) in rules.iter_mut().enumerate() {
+            expanded |= resolve(rule, &templates, &mut Vec::new())
+                .map_err(|error| format!("rule #{} {}", i + 1, error))?;
+        }
+    }
+    if let Some(rule) = config.get_mut("default") {
+        expanded |= resolve(rule, &templates, &mut Vec::new())
+            .map_err(|error| format!("the default rule {}", error))?;
+    }
+    if let Some(defaults) = config.get_mut(RULE_DEFAULTS) {
+        expanded |= resolve(defaults, &templates, &mut Vec::new())
+            .map_err(|error| format!("ruleDefaults {}", error))?;
+    }
+    Ok(expanded)
+}
+
+/// Replaces the `extends` key of the rule with the templates it names, of which later ones take
+/// precedence. Keys of the rule itself take precedence over all templates. `stack` holds the
+/// templates being resolved, to catch templates that extend themselves.
+fn resolve(rule: &mut Value, templates: &Mapping, stack: &mut Vec<String>) -> Result<bool, String> {
+    let Some(mapping) = rule.as_mapping_mut() else {
+        return Ok(false);
+    };
+    let names = match mapping.remove(EXTENDS) {
+        None => return Ok(false),
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Sequence(names)) => names
+            .into_iter()
+            .map(|x| match x {
+                Value::String(name) => Ok(name),
+                _ => Err("extends a template that is not a name".to_string()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("extends a template that is not a name".to_string()),
+    };
+    let mut base = Value::Mapping(Mapping::new());
+    for name in names {
+        if stack.contains(&name) {
+            return Err(format!("extends template \"{}\" in a loop", name));
+        }
+        let Some(template) = templates.get(name.as_str()) else {
+            return Err(format!("extends unknown template \"{}\"", name));
+        };
+        let mut template = template.clone();
+        stack.push(name);
+        resolve(&mut template, templates, stack)?;
+        stack.pop();
+        merge(&mut base, template);
+    }
+    merge(&mut base, std::mem::take(rule));
+    *rule = base;
+    Ok(true)
+}
+
+/// Merges `value` into `base`. Mappings are merged key by key, so that a rule can override a
+/// single limit of a template; other values replace the base.
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Mapping(base), Value::Mapping(value)) => {
+            for (key, value) in value {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn expanded(yaml: &str) -> Result<Value, String> {
+        let mut config: Value = serde_yaml::from_str(yaml).unwrap();
+        expand(&mut config)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_expand() {
+        let config = expanded(
+            "templates:\n  \
+              private: {tracker: example.org, limits: {ratio: 2.0, minutes: 600}}\n  \
+              tagged: {actions: {addTags: [private]}}\n\
+            rules:\n\
+            - {extends: [private, tagged], category: Alien, limits: {ratio: 5.0}}\n\
+            - {category: Ghost, limits: {}}",
+        )
+        .unwrap();
+        let expected: Value = serde_yaml::from_str(
+            "rules:\n\
+            - tracker: example.org\n  \
+              limits: {ratio: 5.0, minutes: 600}\n  \
+              actions: {addTags: [private]}\n  \
+              category: Alien\n\
+            - {category: Ghost, limits: {}}",
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn test_expand_nested() {
+        let config = expanded(
+            "templates:\n  \
+              base: {limits: {ratio: 2.0}}\n  \
+              long: {extends: base, limits: {minutes: 600}}\n\
+            default: {extends: long}",
+        )
+        .unwrap();
+        let expected: Value =
+            serde_yaml::from_str("default: {limits: {ratio: 2.0, minutes: 600}}").unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn test_expand_rule_defaults() {
+        let config = expanded(
+            "templates:\n  \
+              careful: {cooldown: 1h, dryRun: true}\n\
+            ruleDefaults: {extends: careful, dryRun: false}\n\
+            rules:\n\
+            - {category: Alien, limits: {minutes: 600}}",
+        )
+        .unwrap();
+        let expected: Value = serde_yaml::from_str(
+            "ruleDefaults: {cooldown: 1h, dryRun: false}\n\
+            rules:\n\
+            - {category: Alien, limits: {minutes: 600}}",
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+    }
+
+    #[test_case("rules: [{extends: base}]", "rule #1 extends unknown template \"base\"" ; "unknown")]
+    #[test_case("templates: {a: {extends: b}, b: {extends: a}}\nrules: [{extends: a}]", "rule #1 extends template \"a\" in a loop" ; "cycle")]
+    #[test_case("rules: [{extends: {a: b}}]", "rule #1 extends a template that is not a name" ; "not a name")]
+    #[test_case("templates: [a]", "templates must map names to rules" ; "templates")]
+    #[test_case("ruleDefaults: {extends: a}", "ruleDefaults extends unknown template \"a\"" ; "rule defaults template")]
+    fn test_expand_invalid(yaml: &str, expected: &str) {
+        assert_eq!(expanded(yaml).unwrap_err(), expected);
+    }
+}
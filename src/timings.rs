@@ -0,0 +1,85 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Durations of the phases of a single cycle.
+#[derive(Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn record(&mut self, phase: &'static str, duration: Duration) {
+        self.phases.push((phase, duration));
+    }
+
+    /// Describes the phases and the latency percentiles of the cycle's requests.
+    pub fn report(&self, mut latencies: Vec<Duration>) -> String {
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|(phase, duration)| format!("{} {}", phase, format_duration(*duration)))
+            .collect();
+        let mut report = if phases.is_empty() {
+            String::from("Cycle timings: no phases completed")
+        } else {
+            format!("Cycle timings: {}", phases.join(", "))
+        };
+        latencies.sort();
+        let _ = write!(report, "; {} requests", latencies.len());
+        if let Some(max) = latencies.last() {
+            let _ = write!(
+                report,
+                ", latency p50 {}, p90 {}, p99 {}, max {}",
+                format_duration(percentile(&latencies, 50)),
+                format_duration(percentile(&latencies, 90)),
+                format_duration(percentile(&latencies, 99)),
+                format_duration(*max),
+            );
+        }
+        report
+    }
+}
+
+/// Nearest-rank percentile of sorted, non-empty durations.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(50, 5 ; "p50")]
+    #[test_case(90, 9 ; "p90")]
+    #[test_case(99, 10 ; "p99")]
+    #[test_case(1, 1 ; "p1")]
+    fn test_percentile(percent: usize, expected: u64) {
+        let sorted: Vec<Duration> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(
+            percentile(&sorted, percent),
+            Duration::from_millis(expected)
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let mut timings = Timings::default();
+        timings.record("sync", Duration::from_millis(120));
+        timings.record("parse", Duration::from_micros(1500));
+        assert_eq!(
+            timings.report(vec![Duration::from_millis(20), Duration::from_millis(10)]),
+            "Cycle timings: sync 120.0ms, parse 1.5ms; 2 requests, \
+            latency p50 10.0ms, p90 20.0ms, p99 20.0ms, max 20.0ms"
+        );
+        assert_eq!(
+            Timings::default().report(Vec::new()),
+            "Cycle timings: no phases completed; 0 requests"
+        );
+    }
+}
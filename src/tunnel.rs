@@ -0,0 +1,166 @@
+use crate::config;
+use std::io;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::time;
+
+/// How long to wait for the forwarded port to accept connections after starting SSH.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(15);
+/// How often to check whether the forwarded port accepts connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// SSH process forwarding a local port to the WebUI, restarted whenever it exits.
+pub struct Tunnel {
+    child: Option<Child>,
+    config: config::TunnelConfig,
+    local_port: u16,
+}
+
+impl Tunnel {
+    pub fn new(config: config::TunnelConfig, local_port: u16) -> Self {
+        Self {
+            child: None,
+            config,
+            local_port,
+        }
+    }
+
+    /// Starts the tunnel unless it's already running.
+    pub async fn ensure(&mut self) -> Result<(), io::Error> {
+        if let Some(child) = &mut self.child {
+            match child.try_wait()? {
+                None => return Ok(()),
+                Some(status) => log::warn!("SSH tunnel exited with {}: restarting", status),
+            }
+        }
+        self.child = Some(self.command().spawn()?);
+        self.wait_until_ready().await?;
+        log::info!(
+            "Opened SSH tunnel from port {} to {}:{} via {}",
+            self.local_port,
+            self.config.remote_host,
+            self.config.remote_port,
+            self.config.host
+        );
+        Ok(())
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new("ssh");
+        command
+            .arg("-N")
+            .args(["-o", "BatchMode=yes"])
+            .args(["-o", "ExitOnForwardFailure=yes"])
+            .args(["-o", "ServerAliveInterval=30"])
+            .args(["-p", &self.config.port.to_string()])
+            .arg("-L")
+            .arg(format!(
+                "127.0.0.1:{}:{}:{}",
+                self.local_port, self.config.remote_host, self.config.remote_port
+            ));
+        if let Some(key) = &self.config.key {
+            command.arg("-i").arg(key);
+        }
+        match &self.config.user {
+            Some(user) => command.arg(format!("{}@{}", user, self.config.host)),
+            None => command.arg(&self.config.host),
+        };
+        command.stdin(Stdio::null()).kill_on_drop(true);
+        command
+    }
+
+    async fn wait_until_ready(&mut self) -> Result<(), io::Error> {
+        let deadline = time::Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            let connected = TcpStream::connect(("127.0.0.1", self.local_port))
+                .await
+                .is_ok();
+            if connected {
+                // Something else already listening on the port also accepts connections, in
+                // which case SSH can't forward the port and exits shortly after.
+                time::sleep(POLL_INTERVAL).await;
+            }
+            if let Some(child) = &mut self.child {
+                if let Some(status) = child.try_wait()? {
+                    self.child = None;
+                    return Err(io::Error::other(format!("ssh exited with {}", status)));
+                }
+            }
+            if connected {
+                return Ok(());
+            }
+            if time::Instant::now() >= deadline {
+                // Keeping the process would have the next call take it for a working tunnel.
+                if let Some(mut child) = self.child.take() {
+                    if let Err(error) = child.kill().await {
+                        log::warn!("Could not stop SSH: {}", error);
+                    }
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "forwarded port did not open in time",
+                ));
+            }
+            time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(yaml: &str) -> Vec<String> {
+        let config = serde_yaml::from_str(yaml).unwrap();
+        let command = Tunnel::new(config, 8080).command();
+        assert_eq!(command.as_std().get_program(), "ssh");
+        command
+            .as_std()
+            .get_args()
+            .map(|x| x.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_command() {
+        assert_eq!(
+            args("{host: seedbox.example.org, remotePort: 9090}"),
+            vec![
+                "-N",
+                "-o",
+                "BatchMode=yes",
+                "-o",
+                "ExitOnForwardFailure=yes",
+                "-o",
+                "ServerAliveInterval=30",
+                "-p",
+                "22",
+                "-L",
+                "127.0.0.1:8080:127.0.0.1:9090",
+                "seedbox.example.org",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_user_and_key() {
+        let args = args(
+            "{host: seedbox.example.org, port: 2222, user: momo, key: /config/id_ed25519, \
+            remoteHost: 10.0.0.2, remotePort: 9090}",
+        );
+        assert_eq!(
+            args[7..],
+            [
+                "-p",
+                "2222",
+                "-L",
+                "127.0.0.1:8080:10.0.0.2:9090",
+                "-i",
+                "/config/id_ed25519",
+                "momo@seedbox.example.org",
+            ]
+        );
+    }
+}